@@ -0,0 +1,40 @@
+use access_unit::{detect_audio, detect_audio_with, DetectOptions};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+// Deterministic noise with sync-like bytes scattered through it
+fn noise(len: usize) -> Vec<u8> {
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn bench_detect(c: &mut Criterion) {
+    let random = noise(1 << 20);
+    let flac = std::fs::read("testdata/s24le.wav.flac").unwrap();
+    let scan_all = DetectOptions {
+        max_probe_bytes: usize::MAX,
+        ..Default::default()
+    };
+
+    c.bench_function("detect_audio 1 MiB random", |b| {
+        b.iter(|| detect_audio(black_box(&random)))
+    });
+    c.bench_function("detect_audio_with full scan 1 MiB random", |b| {
+        b.iter(|| detect_audio_with(black_box(&random), &scan_all))
+    });
+    c.bench_function("detect_audio s24le.wav.flac", |b| {
+        b.iter(|| detect_audio(black_box(&flac)))
+    });
+    c.bench_function("detect_audio_with full scan s24le.wav.flac", |b| {
+        b.iter(|| detect_audio_with(black_box(&flac), &scan_all))
+    });
+}
+
+criterion_group!(benches, bench_detect);
+criterion_main!(benches);
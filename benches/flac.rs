@@ -0,0 +1,20 @@
+use access_unit::flac;
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn bench_split(c: &mut Criterion) {
+    let flac = Bytes::from(std::fs::read("testdata/s24le.wav.flac").unwrap());
+
+    // Copies every frame into its own Vec
+    #[allow(deprecated)]
+    c.bench_function("split_flac_frames s24le.wav.flac", |b| {
+        b.iter(|| flac::split_flac_frames(black_box(&flac)))
+    });
+    // Allocates only the Vec of handles, but also checks both CRCs
+    c.bench_function("split_frames s24le.wav.flac", |b| {
+        b.iter(|| flac::split_frames(black_box(&flac)).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_split);
+criterion_main!(benches);
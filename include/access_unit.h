@@ -0,0 +1,64 @@
+/* C interface to access-unit, built with the `ffi` feature. */
+#ifndef ACCESS_UNIT_H
+#define ACCESS_UNIT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define AU_OK 0
+#define AU_ERROR (-1)
+
+/* Return values of au_detect_audio. These never change. */
+#define AU_AUDIO_UNKNOWN 0
+#define AU_AUDIO_AAC 1
+#define AU_AUDIO_FLAC 2
+#define AU_AUDIO_OPUS 3
+#define AU_AUDIO_AC3 4
+#define AU_AUDIO_EAC3 5
+#define AU_AUDIO_VORBIS 6
+#define AU_AUDIO_AIFF 7
+#define AU_AUDIO_AMR_NB 8
+#define AU_AUDIO_AMR_WB 9
+
+typedef struct {
+    uint8_t profile;        /* 0 Main, 1 LC, 2 SSR, 3 LTP */
+    uint32_t sample_rate;
+    uint8_t channels;       /* 0: signalled in the payload */
+    uint8_t has_crc;
+    uint32_t header_length;
+    uint32_t frame_length;  /* header included */
+} AuAdtsHeader;
+
+typedef struct {
+    uint8_t is_var_size;
+    uint16_t block_size;
+    uint32_t sample_rate;
+    uint8_t channels;
+    uint8_t ch_mode;
+    uint8_t bps;
+    uint64_t frame_or_sample_num;
+} AuFlacFrameInfo;
+
+/* Every function reads at most `len` bytes from `data`. `data` may be NULL
+ * only when `len` is 0. Failures return AU_ERROR and set the message
+ * returned by au_last_error_message. */
+
+int32_t au_detect_audio(const uint8_t *data, size_t len);
+
+int32_t au_aac_parse_adts(const uint8_t *data, size_t len, AuAdtsHeader *out);
+
+int32_t au_flac_decode_header(const uint8_t *data, size_t len, AuFlacFrameInfo *out);
+
+/* The last error on the calling thread, or NULL. Valid until the next
+ * failing call on the same thread. */
+const char *au_last_error_message(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
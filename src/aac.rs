@@ -1,177 +1,3871 @@
+use crate::bits::{BitReader, BitWriter, EndOfInput};
+use crate::flac::crc16_update;
+use crate::time::Ticks90k;
+use crate::{AccessUnit, StreamType};
 use bytes::{Bytes, BytesMut};
+use std::fmt;
+use std::ops::Range;
+use std::time::Duration;
 
-pub fn is_aac(input: &[u8]) -> bool {
-    // Check if we have at least 7 bytes (minimum ADTS header size)
-    if input.len() < 7 {
-        return false;
+/// Fields of an ADIF header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdifHeader {
+    /// Bits per second for a constant rate stream, otherwise the peak rate;
+    /// zero when unspecified.
+    pub bitrate: u32,
+    pub is_cbr: bool,
+    /// The first program config element.
+    pub program: ProgramConfig,
+}
+
+/// Channel and sample rate information from a program config element.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProgramConfig {
+    /// ADTS-style profile: 0 Main, 1 LC, 2 SSR, 3 LTP.
+    pub profile: u8,
+    pub sample_rate: u32,
+    /// Output channels, counting two for each channel pair element and
+    /// including LFE channels.
+    pub channels: u8,
+}
+
+/// The ADTS ID bit: which standard the stream declares itself under.
+/// Decoders treat both alike, except that MPEG-2 reserves profile 3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MpegVersion {
+    /// ISO/IEC 13818-7, ID bit set.
+    Mpeg2,
+    /// ISO/IEC 14496-3, ID bit clear.
+    #[default]
+    Mpeg4,
+}
+
+/// Fields of an ADTS frame header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdtsHeader {
+    pub mpeg_version: MpegVersion,
+    /// ADTS profile: 0 Main, 1 LC, 2 SSR, 3 LTP. The audio object type is
+    /// one more than this.
+    pub profile: u8,
+    pub sampling_frequency_index: u8,
+    pub sample_rate: u32,
+    /// Channel configuration; 0 means it is signalled in the payload.
+    pub channel_configuration: u8,
+    /// Frame length, header included.
+    pub frame_length: usize,
+    /// 0x7FF for a variable rate stream.
+    pub buffer_fullness: u16,
+    /// Raw data blocks in the frame, minus one.
+    pub number_of_raw_data_blocks: u8,
+    pub protection_absent: bool,
+    /// The CRC word, present when `protection_absent` is false.
+    pub crc: Option<u16>,
+}
+
+impl AdtsHeader {
+    pub fn audio_object_type(&self) -> u8 {
+        self.profile + 1
+    }
+
+    /// 7 bytes. With a CRC, 9 plus two for each raw_data_block_position
+    /// entry of a frame with several raw data blocks.
+    pub fn header_length(&self) -> usize {
+        adts_header_length(self.protection_absent, self.number_of_raw_data_blocks)
+    }
+
+    /// Samples in the frame. ADTS only carries 1024-sample raw data blocks.
+    pub fn samples_per_frame(&self) -> u32 {
+        1024 * (self.number_of_raw_data_blocks as u32 + 1)
+    }
+
+    /// See [`verify_crc`].
+    pub fn verify_crc(&self, frame: &[u8]) -> bool {
+        self.crc.is_none() || verify_crc(frame)
+    }
+
+    /// The AudioSpecificConfig describing this stream. A channel
+    /// configuration of 0 is copied as is, without the program config
+    /// element that would follow it.
+    pub fn audio_specific_config(&self) -> AudioSpecificConfig {
+        core_audio_specific_config(
+            self.audio_object_type(),
+            self.sample_rate,
+            self.channel_configuration,
+        )
+    }
+
+    /// [`AdtsHeader::audio_specific_config`] serialised.
+    pub fn to_asc(&self) -> Vec<u8> {
+        self.audio_specific_config().to_bytes()
+    }
+
+    /// RFC 6381 codec string. An MPEG-2 header gives the MPEG-2 AAC
+    /// object type indication, such as `mp4a.67` for LC; otherwise see
+    /// [`codec_string`].
+    pub fn codec_string(&self) -> String {
+        match (self.mpeg_version, self.profile) {
+            (MpegVersion::Mpeg2, profile @ 0..=2) => format!("mp4a.{:x}", 0x66 + profile),
+            _ => codec_string(&self.audio_specific_config()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdtsError {
+    /// Shorter than the header, or for [`ensure_adts`], too short to be a
+    /// raw data block.
+    Truncated,
+    InvalidSyncWord,
+    /// The layer bits must be zero.
+    InvalidLayer(u8),
+    /// Profile 3 in an MPEG-2 header.
+    ReservedProfile,
+    ReservedSamplingIndex(u8),
+    /// The frame length cannot hold the header.
+    FrameTooShort {
+        frame_length: usize,
+        header_length: usize,
+    },
+    /// The header's frame length runs past the end of the input.
+    FrameLengthExceedsInput {
+        frame_length: usize,
+        available: usize,
+    },
+    /// The frame carries a CRC that does not match its contents.
+    CrcMismatch,
+    /// Another frame starts inside this one, so bytes were lost or the
+    /// frame length is damaged.
+    OverlapsNextFrame,
+    /// ADTS can only signal the Main, LC, SSR and LTP object types.
+    UnsupportedObjectType(u8),
+    /// Not in the sampling frequency table.
+    UnsupportedSampleRate(u32),
+    /// Configuration 0 needs a program config element, and ADTS has no
+    /// room for configurations past 7.
+    UnsupportedChannelConfiguration(u8),
+    /// Larger than the 13-bit frame_length field.
+    FrameTooLong(usize),
+    /// A later frame's object type differs from the first frame's.
+    ObjectTypeChanged {
+        expected: u8,
+        found: u8,
+    },
+    /// A later frame's sample rate differs from the first frame's.
+    SampleRateChanged {
+        expected: u32,
+        found: u32,
+    },
+    /// A frame with several raw data blocks but no CRC, so no block
+    /// positions.
+    MissingBlockPositions,
+    /// A raw_data_block_position entry points outside the frame or before
+    /// the previous block.
+    InvalidBlockPosition,
+}
+
+impl fmt::Display for AdtsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdtsError::Truncated => write!(f, "ADTS header truncated"),
+            AdtsError::InvalidSyncWord => write!(f, "Invalid ADTS sync word"),
+            AdtsError::InvalidLayer(layer) => write!(f, "Invalid ADTS layer: {}", layer),
+            AdtsError::ReservedProfile => write!(f, "Reserved ADTS profile"),
+            AdtsError::ReservedSamplingIndex(index) => {
+                write!(f, "Reserved sampling frequency index: {}", index)
+            }
+            AdtsError::FrameTooShort {
+                frame_length,
+                header_length,
+            } => write!(
+                f,
+                "Frame length {} is shorter than the {}-byte header",
+                frame_length, header_length
+            ),
+            AdtsError::FrameLengthExceedsInput {
+                frame_length,
+                available,
+            } => write!(
+                f,
+                "Frame length {} exceeds the {} bytes available",
+                frame_length, available
+            ),
+            AdtsError::CrcMismatch => write!(f, "ADTS CRC mismatch"),
+            AdtsError::OverlapsNextFrame => {
+                write!(f, "ADTS frame overlaps the frame after it")
+            }
+            AdtsError::UnsupportedObjectType(object_type) => {
+                write!(
+                    f,
+                    "Audio object type {} cannot be signalled in ADTS",
+                    object_type
+                )
+            }
+            AdtsError::UnsupportedSampleRate(rate) => {
+                write!(f, "Unsupported ADTS sample rate: {}", rate)
+            }
+            AdtsError::UnsupportedChannelConfiguration(configuration) => {
+                write!(
+                    f,
+                    "Unsupported ADTS channel configuration: {}",
+                    configuration
+                )
+            }
+            AdtsError::FrameTooLong(length) => {
+                write!(f, "Frame length {} does not fit in an ADTS header", length)
+            }
+            AdtsError::ObjectTypeChanged { expected, found } => write!(
+                f,
+                "Audio object type changed from {} to {}",
+                expected, found
+            ),
+            AdtsError::SampleRateChanged { expected, found } => {
+                write!(f, "Sample rate changed from {} to {}", expected, found)
+            }
+            AdtsError::MissingBlockPositions => {
+                write!(f, "ADTS frame has several raw data blocks but no positions")
+            }
+            AdtsError::InvalidBlockPosition => write!(f, "Invalid raw data block position"),
+        }
+    }
+}
+
+impl std::error::Error for AdtsError {}
+
+fn adts_header_length(protection_absent: bool, number_of_raw_data_blocks: u8) -> usize {
+    if protection_absent {
+        7
+    } else {
+        9 + 2 * number_of_raw_data_blocks as usize
     }
+}
 
-    // Check for the ADTS sync word (12 bits)
+/// Parses the ADTS header at the start of `input`. The frame itself may
+/// extend past the end of `input`.
+pub fn parse_adts_header(input: &[u8]) -> Result<AdtsHeader, AdtsError> {
+    if input.len() < 7 {
+        return Err(AdtsError::Truncated);
+    }
     if input[0] != 0xFF || (input[1] & 0xF0) != 0xF0 {
-        return false;
+        return Err(AdtsError::InvalidSyncWord);
     }
 
     let layer = (input[1] & 0x06) >> 1;
     if layer != 0 {
-        // Layer must be '00' for AAC
-        return false;
+        return Err(AdtsError::InvalidLayer(layer));
+    }
+    let protection_absent = input[1] & 0x01 == 0x01;
+    let number_of_raw_data_blocks = input[6] & 0x03;
+    let header_length = adts_header_length(protection_absent, number_of_raw_data_blocks);
+    if input.len() < header_length {
+        return Err(AdtsError::Truncated);
     }
 
-    // Check profile (2 bits)
+    // '11' is LTP in MPEG-4 but reserved in MPEG-2 AAC
     let profile = (input[2] & 0xC0) >> 6;
-    if profile == 3 {
-        // '11' is reserved
-        return false;
+    let mpeg_version = if input[1] & 0x08 != 0 {
+        MpegVersion::Mpeg2
+    } else {
+        MpegVersion::Mpeg4
+    };
+    if profile == 3 && mpeg_version == MpegVersion::Mpeg2 {
+        return Err(AdtsError::ReservedProfile);
     }
 
-    // Check sampling frequency index (4 bits)
-    let sampling_freq_index = (input[2] & 0x3C) >> 2;
-    if sampling_freq_index > 11 {
-        // Valid range is 0-11
+    let sampling_frequency_index = (input[2] & 0x3C) >> 2;
+    let sample_rate = sample_rate_from_index(sampling_frequency_index)
+        .ok_or(AdtsError::ReservedSamplingIndex(sampling_frequency_index))?;
+
+    let frame_length = adts_frame_length(input);
+    if frame_length < header_length {
+        return Err(AdtsError::FrameTooShort {
+            frame_length,
+            header_length,
+        });
+    }
+
+    Ok(AdtsHeader {
+        mpeg_version,
+        profile,
+        sampling_frequency_index,
+        sample_rate,
+        channel_configuration: ((input[2] & 0x01) << 2) | (input[3] >> 6),
+        frame_length,
+        buffer_fullness: ((input[5] as u16 & 0x1F) << 6) | (input[6] as u16 >> 2),
+        number_of_raw_data_blocks,
+        protection_absent,
+        crc: (!protection_absent)
+            .then(|| u16::from_be_bytes([input[header_length - 2], input[header_length - 1]])),
+    })
+}
+
+/// Whether `input` starts with an ADTS frame that either ends exactly at
+/// the end of `input` or is followed by another header with the same
+/// sampling frequency index and channel configuration. A lone 0xFFF sync
+/// is common in other binary data, so one header is not enough; see
+/// [`is_aac_single_frame`] for buffers too short to hold a whole frame.
+pub fn is_aac(input: &[u8]) -> bool {
+    let Ok(header) = parse_adts_header(input) else {
         return false;
+    };
+    match input.get(header.frame_length..) {
+        Some([]) => true,
+        Some(next) => matches!(
+            parse_adts_header(next),
+            Ok(next) if next.sampling_frequency_index == header.sampling_frequency_index
+                && next.channel_configuration == header.channel_configuration
+        ),
+        None => false,
     }
+}
 
-    // All checks passed
-    true
+/// Checks only the ADTS header at the start of `input`.
+pub fn is_aac_single_frame(input: &[u8]) -> bool {
+    parse_adts_header(input).is_ok()
 }
 
-pub fn extract_aac_data(sound_data: &Bytes) -> Option<Bytes> {
-    if sound_data.len() < 7 {
-        return None;
+/// One ADTS frame from an [`AdtsFrameIter`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdtsFrame<'a> {
+    pub header: AdtsHeader,
+    /// The whole frame, header included.
+    pub data: &'a [u8],
+}
+
+impl<'a> AdtsFrame<'a> {
+    /// The raw data blocks after the header, with their CRC words when
+    /// the frame is protected and holds several blocks.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[self.header.header_length()..]
     }
+}
 
-    // Check for the ADTS sync word
-    if sound_data[0] != 0xFF || (sound_data[1] & 0xF0) != 0xF0 {
-        return None;
+/// Finds the first ADTS frame at or after `start` whose length leads to
+/// another header, or to the end of `data`. When there is none, a frame
+/// cut short by the end of `data` is returned instead, as its successor
+/// cannot be checked yet.
+pub fn find_adts_frame(data: &[u8], start: usize) -> Option<(usize, AdtsHeader)> {
+    let candidates = start.min(data.len())..data.len();
+    confirmed_adts_frame(data, candidates.clone()).or_else(|| {
+        candidates.into_iter().find_map(|pos| {
+            let header = parse_adts_header(&data[pos..]).ok()?;
+            (pos + header.frame_length > data.len()).then_some((pos, header))
+        })
+    })
+}
+
+fn confirmed_adts_frame(data: &[u8], candidates: Range<usize>) -> Option<(usize, AdtsHeader)> {
+    candidates.filter(|&pos| data[pos] == 0xFF).find_map(|pos| {
+        let header = parse_adts_header(&data[pos..]).ok()?;
+        let next = data.get(pos + header.frame_length..)?;
+        adts_header_follows(next).then_some((pos, header))
+    })
+}
+
+// Whether the bytes after a frame start with another header, or with the
+// start of one cut short by the end of the data. The header may change
+// the sample rate or channels.
+fn adts_header_follows(next: &[u8]) -> bool {
+    match parse_adts_header(next) {
+        Ok(_) => true,
+        Err(AdtsError::Truncated) => {
+            next.is_empty()
+                || (next[0] == 0xFF && !matches!(next.get(1), Some(b) if b & 0xF0 != 0xF0))
+        }
+        Err(_) => false,
     }
+}
+
+/// Walks the ADTS frames of a raw AAC stream.
+///
+/// A header that fails to parse is yielded as an error, after which the
+/// iterator resumes at the next frame [`find_adts_frame`] finds. So is a
+/// frame that another frame starts inside, as happens when bytes are lost
+/// in transit. A frame that [`check_crc`] finds damaged is yielded as
+/// [`AdtsError::CrcMismatch`] and skipped. Iteration ends at a trailing
+/// frame that is cut short; [`AdtsFrameIter::remaining`] then reports its
+/// length.
+#[derive(Debug, Clone)]
+pub struct AdtsFrameIter<'a> {
+    data: &'a [u8],
+    pos: usize,
+    skipped: usize,
+    done: bool,
+}
 
-    // Parse the ADTS header
-    let protection_absent: bool = (sound_data[1] & 0x01) == 0x01;
-    let header_size: usize = if protection_absent { 7 } else { 9 };
+impl<'a> AdtsFrameIter<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            skipped: 0,
+            done: false,
+        }
+    }
 
-    if sound_data.len() < header_size {
-        return None;
+    /// Bytes not yet consumed. Once iteration has ended, this is the
+    /// length of the trailing partial frame, or zero.
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    /// Bytes passed over so far while resynchronising after an error.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    fn resync(&mut self) {
+        let next = match find_adts_frame(self.data, self.pos + 1) {
+            Some((pos, _)) => pos,
+            // Leave a header cut short at the end for remaining()
+            None => (self.pos + 1..self.data.len())
+                .find(|&i| adts_header_follows(&self.data[i..]))
+                .unwrap_or(self.data.len()),
+        };
+        self.skipped += next - self.pos;
+        self.pos = next;
+    }
+}
+
+impl<'a> Iterator for AdtsFrameIter<'a> {
+    type Item = Result<AdtsFrame<'a>, AdtsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.pos >= self.data.len() {
+            return None;
+        }
+
+        let input = &self.data[self.pos..];
+        match parse_adts_header(input) {
+            Ok(header) if header.frame_length <= input.len() => {
+                let data = &input[..header.frame_length];
+                let end = self.pos + header.frame_length;
+                if !adts_header_follows(&input[header.frame_length..])
+                    && confirmed_adts_frame(self.data, self.pos + 1..end).is_some()
+                {
+                    self.resync();
+                    return Some(Err(AdtsError::OverlapsNextFrame));
+                }
+                self.pos = end;
+                if !header.verify_crc(data) {
+                    return Some(Err(AdtsError::CrcMismatch));
+                }
+                Some(Ok(AdtsFrame { header, data }))
+            }
+            Ok(_) | Err(AdtsError::Truncated) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.resync();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Turns the frames of an ADTS stream into access units, one per frame,
+/// with 90 kHz timestamps counted from a base PTS.
+///
+/// Each unit holds the frame's raw data blocks, or the whole frame with
+/// [`AdtsAccessUnitIter::keep_header`]. Bad frames are yielded as errors
+/// and skipped, as with [`AdtsFrameIter`].
+#[derive(Debug, Clone)]
+pub struct AdtsAccessUnitIter<'a> {
+    frames: AdtsFrameIter<'a>,
+    keep_header: bool,
+    // Timestamps are counted in samples since the last sample rate change,
+    // so rounding to 90 kHz does not accumulate
+    segment_start: u64,
+    sample_rate: u32,
+    samples: u64,
+}
+
+impl<'a> AdtsAccessUnitIter<'a> {
+    pub fn new(data: &'a [u8], base_pts: u64) -> Self {
+        Self {
+            frames: AdtsFrameIter::new(data),
+            keep_header: false,
+            segment_start: base_pts,
+            sample_rate: 0,
+            samples: 0,
+        }
+    }
+
+    /// Keep the ADTS header in each unit's data.
+    pub fn keep_header(mut self, keep_header: bool) -> Self {
+        self.keep_header = keep_header;
+        self
+    }
+
+    /// See [`AdtsFrameIter::remaining`].
+    pub fn remaining(&self) -> usize {
+        self.frames.remaining()
+    }
+
+    fn pts(&self) -> u64 {
+        if self.sample_rate == 0 {
+            return self.segment_start;
+        }
+        self.segment_start + Ticks90k::from_samples(self.samples, self.sample_rate).0
+    }
+}
+
+impl Iterator for AdtsAccessUnitIter<'_> {
+    type Item = Result<AccessUnit, AdtsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = match self.frames.next()? {
+            Ok(frame) => frame,
+            Err(err) => return Some(Err(err)),
+        };
+
+        let sample_rate = frame.header.sample_rate;
+        if sample_rate != self.sample_rate {
+            self.segment_start = self.pts();
+            self.sample_rate = sample_rate;
+            self.samples = 0;
+        }
+        let pts = self.pts();
+        self.samples += frame.header.samples_per_frame() as u64;
+
+        let data = if self.keep_header {
+            frame.data
+        } else {
+            frame.payload()
+        };
+        Some(Ok(AccessUnit {
+            key: true,
+            pts,
+            dts: pts,
+            data: Bytes::copy_from_slice(data),
+            stream_type: StreamType::AAC.into(),
+            id: 0,
+        }))
+    }
+}
+
+/// Every frame of an ADTS stream as an access unit, failing on the first
+/// bad or incomplete frame. See [`AdtsAccessUnitIter`].
+pub fn to_access_units(data: &[u8], base_pts: u64) -> Result<Vec<AccessUnit>, AdtsError> {
+    let mut iter = AdtsAccessUnitIter::new(data, base_pts);
+    let units = iter.by_ref().collect::<Result<Vec<_>, _>>()?;
+    match iter.remaining() {
+        0 => Ok(units),
+        remaining => Err(complete_frame_header(&data[data.len() - remaining..])
+            .err()
+            .unwrap_or(AdtsError::Truncated)),
     }
+}
+
+/// Splits an ADTS stream into raw data blocks, sliced from `data`, and the
+/// AudioSpecificConfig describing them, as an MP4 muxer needs. Every frame
+/// must be complete, pass its CRC check and agree with the first on
+/// object type and sample rate.
+pub fn strip_adts(data: &Bytes) -> Result<(AudioSpecificConfig, Vec<Bytes>), AdtsError> {
+    let mut config: Option<AudioSpecificConfig> = None;
+    let mut blocks = Vec::new();
+
+    let mut frames = AdtsFrameIter::new(data);
+    for frame in frames.by_ref() {
+        let frame = frame?;
+        let header = frame.header;
+        match config {
+            None => config = Some(header.audio_specific_config()),
+            Some(config) if config.audio_object_type != header.audio_object_type() => {
+                return Err(AdtsError::ObjectTypeChanged {
+                    expected: config.audio_object_type,
+                    found: header.audio_object_type(),
+                });
+            }
+            Some(config) if config.sampling_frequency != header.sample_rate => {
+                return Err(AdtsError::SampleRateChanged {
+                    expected: config.sampling_frequency,
+                    found: header.sample_rate,
+                });
+            }
+            Some(_) => {}
+        }
+
+        let start = frame.data.as_ptr() as usize - data.as_ptr() as usize;
+        for (range, _) in raw_data_blocks(frame.data, &header)? {
+            blocks.push(data.slice(start + range.start..start + range.end));
+        }
+    }
+
+    if frames.remaining() > 0 {
+        let rest = &data[data.len() - frames.remaining()..];
+        return Err(complete_frame_header(rest)
+            .err()
+            .unwrap_or(AdtsError::Truncated));
+    }
+    config
+        .map(|config| (config, blocks))
+        .ok_or(AdtsError::Truncated)
+}
 
-    let frame_length: usize = (((sound_data[3] as usize & 0x03) << 11)
-        | ((sound_data[4] as usize) << 3)
-        | ((sound_data[5] as usize) >> 5)) as usize;
+/// Frame count, duration and frame size figures for an ADTS stream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdtsStreamInfo {
+    pub frames: u64,
+    pub samples: u64,
+    /// The first frame's sample rate.
+    pub sample_rate: u32,
+    pub duration: Duration,
+    /// Average bits per second.
+    pub bitrate: u32,
+    /// Frame sizes, headers included.
+    pub min_frame_size: usize,
+    pub avg_frame_size: usize,
+    pub max_frame_size: usize,
+    /// Every frame signals a buffer fullness other than the 0x7FF of a
+    /// variable rate stream.
+    pub is_cbr: bool,
+    /// The scan stopped at its frame budget, and the frame and sample
+    /// counts, and so the duration, were extrapolated from the bytes read.
+    pub estimated: bool,
+}
+
+/// Walks every ADTS frame in `data`. Each raw data block is taken as 1024
+/// samples. `None` if there are no frames.
+pub fn stream_info(data: &[u8]) -> Option<AdtsStreamInfo> {
+    stream_info_with_budget(data, usize::MAX)
+}
+
+/// Like [`stream_info`], but reads at most `max_frames` frames and
+/// extrapolates the rest from their average size.
+pub fn stream_info_with_budget(data: &[u8], max_frames: usize) -> Option<AdtsStreamInfo> {
+    let mut iter = AdtsFrameIter::new(data);
+    let mut frames = 0u64;
+    let mut samples = 0u64;
+    let mut seconds = 0.0;
+    let mut bytes = 0;
+    let mut sample_rate = 0;
+    let (mut min_frame_size, mut max_frame_size) = (usize::MAX, 0);
+    let mut is_cbr = true;
 
-    if sound_data.len() < frame_length {
+    for frame in iter.by_ref().flatten() {
+        let header = frame.header;
+        let frame_samples = header.samples_per_frame() as u64;
+        if frames == 0 {
+            sample_rate = header.sample_rate;
+        }
+        frames += 1;
+        samples += frame_samples;
+        seconds += frame_samples as f64 / header.sample_rate as f64;
+        bytes += frame.data.len();
+        min_frame_size = min_frame_size.min(frame.data.len());
+        max_frame_size = max_frame_size.max(frame.data.len());
+        is_cbr &= header.buffer_fullness != 0x7FF;
+
+        if frames as usize >= max_frames {
+            break;
+        }
+    }
+    if frames == 0 {
         return None;
     }
 
-    Some(sound_data.slice(header_size..frame_length))
+    // Scale by the share of the input read so far
+    let read = data.len() - iter.remaining();
+    let estimated = iter.next().is_some();
+    if estimated {
+        let scale = data.len() as f64 / read as f64;
+        frames = (frames as f64 * scale).round() as u64;
+        samples = (samples as f64 * scale).round() as u64;
+        seconds *= scale;
+        bytes = data.len();
+    }
+
+    Some(AdtsStreamInfo {
+        frames,
+        samples,
+        sample_rate,
+        duration: Duration::from_secs_f64(seconds),
+        bitrate: (bytes as f64 * 8.0 / seconds).round() as u32,
+        min_frame_size,
+        avg_frame_size: bytes / frames as usize,
+        max_frame_size,
+        is_cbr,
+        estimated,
+    })
 }
 
-pub fn ensure_adts_header(data: Bytes, channels: u8, sample_rate: u32) -> Bytes {
-    // Assume that the first byte might contain the ASC if `extract_aac_data` finds no ADTS header
-    if extract_aac_data(&data).is_none() {
-        // Assuming data[0] is present and is the first byte of ASC
-        // Parse the profile from the ASC
-        let audio_object_type = data[0] >> 3; // First 5 bits contain the audio object type
-        let profile = match audio_object_type {
-            1 => 0x66, // AAC-LC
-            2 => 0x67, // HE-AAC v1
-            5 => 0x68, // HE-AAC v2
-            _ => 0x66, // Default to AAC-LC if unknown
+/// What [`detect_sbr`] could tell about SBR and PS from the raw data blocks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SbrSignal {
+    /// SBR data follows the core channel elements, so the stream is
+    /// HE-AAC whatever its ADTS header says.
+    Present {
+        /// `Some(false)` when the SBR data belongs to a channel pair, which
+        /// cannot carry PS. Finding PS in a mono stream's SBR data takes
+        /// Huffman decoding, so that is `None`.
+        ps: Option<bool>,
+        /// Twice the ADTS sample rate.
+        output_sample_rate: u32,
+    },
+    /// A raw data block was walked to its end without meeting SBR data.
+    Absent,
+    /// No raw data block could be walked far enough to tell.
+    Unknown,
+}
+
+// HE-AAC encoders start with silent frames, which are the ones whose
+// elements can be walked without Huffman decoding.
+const SBR_PROBE_FRAMES: usize = 8;
+
+/// Looks for an SBR fill element in the raw data blocks of the first few
+/// complete ADTS `frames`. Only the syntactic elements are walked, so a
+/// channel element carrying spectral data ends the walk of its block.
+pub fn detect_sbr(frames: &[Bytes]) -> SbrSignal {
+    let mut signal = SbrSignal::Unknown;
+    for frame in frames.iter().take(SBR_PROBE_FRAMES) {
+        let Ok(header) = complete_frame_header(frame) else {
+            continue;
         };
+        let Ok(blocks) = raw_data_blocks(frame, &header) else {
+            continue;
+        };
+        for (range, _) in blocks {
+            match scan_raw_data_block(&frame[range]) {
+                Ok(BlockScan::Sbr { ps }) => {
+                    return SbrSignal::Present {
+                        ps,
+                        output_sample_rate: 2 * header.sample_rate,
+                    }
+                }
+                Ok(BlockScan::NoSbr) => signal = SbrSignal::Absent,
+                Ok(BlockScan::Opaque) | Err(EndOfInput) => {}
+            }
+        }
+    }
+    signal
+}
 
-        let header = create_adts_header(profile, channels, sample_rate, data.len() - 2, false);
-        let mut payload = BytesMut::from(&header[..]);
-        payload.extend_from_slice(&data[2..]); // Skip the first two bytes if they are part of ASC
+enum BlockScan {
+    Sbr { ps: Option<bool> },
+    NoSbr,
+    Opaque,
+}
 
-        return payload.freeze();
+const EIGHT_SHORT_SEQUENCE: u32 = 2;
+const ZERO_HCB: u32 = 0;
+const EXT_SBR_DATA: u32 = 0xD;
+const EXT_SBR_DATA_CRC: u32 = 0xE;
+
+fn scan_raw_data_block(block: &[u8]) -> Result<BlockScan, EndOfInput> {
+    let mut reader = BitReader::new(block);
+    // Whether the last SCE or CPE was a pair, for the SBR data after it
+    let mut last_pair = None;
+
+    loop {
+        match reader.read(3)? {
+            // SCE and LFE
+            id @ (0 | 3) => {
+                reader.read(4)?;
+                if !skip_silent_ics(&mut reader, None)? {
+                    return Ok(BlockScan::Opaque);
+                }
+                if id == 0 {
+                    last_pair = Some(false);
+                }
+            }
+            // CPE
+            1 => {
+                reader.read(4)?;
+                let mut common = None;
+                if reader.read_bit()? {
+                    let Some(info) = read_ics_info(&mut reader)? else {
+                        return Ok(BlockScan::Opaque);
+                    };
+                    match reader.read(2)? {
+                        0 | 2 => {}
+                        1 => reader.skip((info.window_groups * info.max_sfb) as usize)?,
+                        _ => return Ok(BlockScan::Opaque),
+                    }
+                    common = Some(info);
+                }
+                for _ in 0..2 {
+                    if !skip_silent_ics(&mut reader, common)? {
+                        return Ok(BlockScan::Opaque);
+                    }
+                }
+                last_pair = Some(true);
+            }
+            // CCE
+            2 => return Ok(BlockScan::Opaque),
+            // DSE
+            4 => {
+                reader.read(4)?;
+                let byte_align = reader.read_bit()?;
+                let mut count = reader.read(8)?;
+                if count == 255 {
+                    count += reader.read(8)?;
+                }
+                if byte_align {
+                    reader.align_to_byte();
+                }
+                reader.skip(8 * count as usize)?;
+            }
+            // PCE
+            5 => {
+                if read_program_config(&mut reader)?.is_none() {
+                    return Ok(BlockScan::Opaque);
+                }
+            }
+            // FIL
+            6 => {
+                let mut count = reader.read(4)?;
+                if count == 15 {
+                    count = count + reader.read(8)? - 1;
+                }
+                if count > 0 {
+                    let extension_type = reader.read(4)?;
+                    if matches!(extension_type, EXT_SBR_DATA | EXT_SBR_DATA_CRC) {
+                        // PS is only allowed after a single channel element
+                        return Ok(BlockScan::Sbr {
+                            ps: last_pair.and_then(|pair| pair.then_some(false)),
+                        });
+                    }
+                    reader.skip(8 * count as usize - 4)?;
+                }
+            }
+            // END
+            _ => return Ok(BlockScan::NoSbr),
+        }
     }
+}
 
-    return data;
+#[derive(Clone, Copy)]
+struct IcsInfo {
+    short: bool,
+    max_sfb: u32,
+    window_groups: u32,
 }
 
-pub fn create_adts_header(
-    codec_id: u8,
-    channels: u8,
-    sample_rate: u32,
-    aac_frame_length: usize,
-    has_crc: bool,
-) -> Vec<u8> {
-    let profile_object_type = match codec_id {
-        0x66 => 1, // AAC LC (internally set as `1`, should directly be `01` in bits)
-        0x67 => 2, // AAC HEV1
-        0x68 => 3, // AAC HEV2
-        _ => 1,    // Default to AAC LC
+// None when prediction or LTP data follows, whose layout depends on the
+// object type.
+fn read_ics_info(reader: &mut BitReader) -> Result<Option<IcsInfo>, EndOfInput> {
+    let _ics_reserved_bit = reader.read_bit()?;
+    let window_sequence = reader.read(2)?;
+    let _window_shape = reader.read_bit()?;
+    if window_sequence == EIGHT_SHORT_SEQUENCE {
+        let max_sfb = reader.read(4)?;
+        // A new group starts at every window whose grouping bit is clear
+        let scale_factor_grouping = reader.read(7)?;
+        return Ok(Some(IcsInfo {
+            short: true,
+            max_sfb,
+            window_groups: 8 - scale_factor_grouping.count_ones(),
+        }));
+    }
+
+    let max_sfb = reader.read(6)?;
+    if reader.read_bit()? {
+        return Ok(None);
+    }
+    Ok(Some(IcsInfo {
+        short: false,
+        max_sfb,
+        window_groups: 1,
+    }))
+}
+
+// Skips an individual_channel_stream whose sections all use the zero
+// codebook, leaving no scale factors or spectral data to decode. Any other
+// stream needs Huffman decoding to find its end, and gives false.
+fn skip_silent_ics(reader: &mut BitReader, common: Option<IcsInfo>) -> Result<bool, EndOfInput> {
+    let _global_gain = reader.read(8)?;
+    let info = match common {
+        Some(info) => info,
+        None => match read_ics_info(reader)? {
+            Some(info) => info,
+            None => return Ok(false),
+        },
     };
 
-    let sample_rate_index = sample_rate_index(sample_rate);
-    let channel_config = channels.min(7);
-    let header_length = if has_crc { 9 } else { 7 };
-    let frame_length = aac_frame_length + header_length;
+    let (sect_bits, windows) = if info.short { (3, 8) } else { (5, 1) };
+    let sect_esc_val = (1 << sect_bits) - 1;
+    for _ in 0..info.window_groups {
+        let mut band = 0;
+        while band < info.max_sfb {
+            if reader.read(4)? != ZERO_HCB {
+                return Ok(false);
+            }
+            loop {
+                let sect_len_incr = reader.read(sect_bits)?;
+                band += sect_len_incr;
+                if sect_len_incr != sect_esc_val {
+                    break;
+                }
+            }
+        }
+    }
 
-    let mut header = Vec::with_capacity(header_length);
-    let protection_absent = if has_crc { 0 } else { 1 };
+    // Pulse data
+    if reader.read_bit()? {
+        let number_pulse = reader.read(2)?;
+        reader.skip(6 + 9 * (number_pulse as usize + 1))?;
+    }
 
-    header.push(0xFF);
-    header.push(0xF0 | protection_absent);
+    // TNS data
+    if reader.read_bit()? {
+        let (n_filt_bits, length_bits, order_bits) = if info.short { (1, 4, 3) } else { (2, 6, 5) };
+        for _ in 0..windows {
+            let n_filt = reader.read(n_filt_bits)?;
+            if n_filt == 0 {
+                continue;
+            }
+            let coef_res = reader.read_bit()? as usize;
+            for _ in 0..n_filt {
+                reader.skip(length_bits)?;
+                let order = reader.read(order_bits)? as usize;
+                if order > 0 {
+                    let _direction = reader.read_bit()?;
+                    let coef_compress = reader.read_bit()? as usize;
+                    reader.skip(order * (3 + coef_res - coef_compress))?;
+                }
+            }
+        }
+    }
 
-    let profile_and_sampling =
-        (profile_object_type << 6) | (sample_rate_index << 2) | (channel_config >> 2);
-    header.push(profile_and_sampling);
+    // Gain control data only appears in SSR streams
+    Ok(!reader.read_bit()?)
+}
 
-    let frame_length_high = ((frame_length >> 11) & 0x03) as u8;
-    let frame_length_mid = ((frame_length >> 3) & 0xFF) as u8;
-    header.push((channel_config & 3) << 6 | frame_length_high);
-    header.push(frame_length_mid);
+pub(crate) const LOAS_HEADER_LEN: usize = 3;
 
-    let frame_length_low = ((frame_length & 0x07) << 5) | 0x1F;
-    header.push(frame_length_low as u8);
-    header.push(0xFC);
+/// Checks for a LOAS AudioSyncStream: a frame with the 0x2B7 sync whose
+/// length leads straight to another sync. A single frame is not enough.
+pub fn is_loas(input: &[u8]) -> bool {
+    loas_frame_length(input)
+        .and_then(|len| input.get(len..))
+        .and_then(loas_frame_length)
+        .is_some()
+}
 
-    if has_crc {
-        header.extend_from_slice(&[0x00, 0x00]);
+/// Length of the LOAS frame at the start of `input`, its three-byte
+/// header included.
+pub fn loas_frame_length(input: &[u8]) -> Option<usize> {
+    let header = input.get(..LOAS_HEADER_LEN)?;
+    // 11-bit sync, then the 13-bit audioMuxLengthBytes
+    if header[0] != 0x56 || header[1] & 0xE0 != 0xE0 {
+        return None;
     }
+    let length = ((header[1] as usize & 0x1F) << 8) | header[2] as usize;
+    (length > 0).then_some(LOAS_HEADER_LEN + length)
+}
 
-    header
+/// [`adts_to_loas`] repeats the StreamMuxConfig at least this often.
+pub const LOAS_MUX_CONFIG_INTERVAL: usize = 20;
+
+/// LATM StreamMuxConfig for one program with one layer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamMuxConfig {
+    pub audio_specific_config: AudioSpecificConfig,
+    /// 0xFF for a variable rate stream.
+    pub latm_buffer_fullness: u8,
 }
 
-fn sample_rate_index(sample_rate: u32) -> u8 {
-    match sample_rate {
-        96000 => 0x0,
-        88200 => 0x1,
-        64000 => 0x2,
-        48000 => 0x3,
-        44100 => 0x4,
-        32000 => 0x5,
-        24000 => 0x6,
-        22050 => 0x7,
-        16000 => 0x8,
-        12000 => 0x9,
-        11025 => 0xA,
-        8000 => 0xB,
-        7350 => 0xC,
-        _ => 0xF, // Invalid sample rate
+impl StreamMuxConfig {
+    pub fn new(audio_specific_config: AudioSpecificConfig) -> Self {
+        Self {
+            audio_specific_config,
+            latm_buffer_fullness: 0xFF,
+        }
+    }
+
+    fn write(&self, writer: &mut BitWriter) {
+        // audioMuxVersion 0, allStreamsSameTimeFraming, one subframe, one
+        // program and one layer
+        writer.write(0, 1);
+        writer.write(1, 1);
+        writer.write(0, 6);
+        writer.write(0, 4);
+        writer.write(0, 3);
+        self.audio_specific_config.write(writer);
+
+        // frameLengthType 0: payload lengths are signalled per frame
+        writer.write(0, 3);
+        writer.write(self.latm_buffer_fullness as u64, 8);
+        // otherDataPresent and crcCheckPresent
+        writer.write(0, 2);
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use mse_fmp4::aac::{AdtsHeader, ChannelConfiguration, SamplingFrequency};
+/// One parsed LOAS frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoasFrame {
+    /// The frame's own StreamMuxConfig, or the one it refers back to.
+    pub config: StreamMuxConfig,
+    /// The raw data block.
+    pub payload: Bytes,
+}
 
-    #[test]
-    fn test_adts_header_parsing() {
-        let data = vec![0u8; 200]; // Dummy AAC frame data
-        let channels = 2u8;
-        let sample_rate = 44100u32;
-        let adts_payload = create_adts_header(0x66, channels, sample_rate, data.len(), false);
-        let mut full_payload = adts_payload.clone();
-        full_payload.extend_from_slice(&data);
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LatmError {
+    InvalidSyncWord,
+    /// Shorter than its audioMuxLengthBytes, or than the fields it signals.
+    Truncated,
+    /// The frame reuses a StreamMuxConfig that has not been seen.
+    MissingStreamMuxConfig,
+    /// audioMuxVersionA 1 is reserved.
+    UnsupportedVersion,
+    /// Only one program with one layer is supported.
+    MultipleProgramsOrLayers {
+        programs: u8,
+        layers: u8,
+    },
+    MultipleSubFrames(u8),
+    /// Only frameLengthType 0, variable length AAC payloads, is supported.
+    UnsupportedFrameLengthType(u8),
+    InvalidAudioSpecificConfig(AscError),
+}
 
-        let adts = AdtsHeader::read_from(&full_payload[..]).unwrap();
-        assert_eq!(adts.frame_len, 207);
-        assert_eq!(adts.sampling_frequency, SamplingFrequency::Hz44100);
-        assert_eq!(
-            adts.channel_configuration,
-            ChannelConfiguration::TwoChannels
-        );
-        assert_eq!(adts.profile, mse_fmp4::aac::AacProfile::Lc);
+impl fmt::Display for LatmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LatmError::InvalidSyncWord => write!(f, "Invalid LOAS sync word"),
+            LatmError::Truncated => write!(f, "LOAS frame truncated"),
+            LatmError::MissingStreamMuxConfig => {
+                write!(f, "LOAS frame refers to a StreamMuxConfig not yet seen")
+            }
+            LatmError::UnsupportedVersion => write!(f, "Unsupported LATM version"),
+            LatmError::MultipleProgramsOrLayers { programs, layers } => write!(
+                f,
+                "Unsupported LATM layout: {} programs, {} layers",
+                programs, layers
+            ),
+            LatmError::MultipleSubFrames(sub_frames) => {
+                write!(f, "Unsupported LATM subframe count: {}", sub_frames)
+            }
+            LatmError::UnsupportedFrameLengthType(frame_length_type) => {
+                write!(f, "Unsupported LATM frameLengthType: {}", frame_length_type)
+            }
+            LatmError::InvalidAudioSpecificConfig(err) => {
+                write!(f, "Invalid AudioSpecificConfig in LATM: {}", err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LatmError {}
+
+impl From<EndOfInput> for LatmError {
+    fn from(_: EndOfInput) -> Self {
+        LatmError::Truncated
+    }
+}
+
+impl From<AscError> for LatmError {
+    fn from(err: AscError) -> Self {
+        match err {
+            AscError::Truncated => LatmError::Truncated,
+            err => LatmError::InvalidAudioSpecificConfig(err),
+        }
+    }
+}
+
+/// Parses a LOAS frame that carries its own StreamMuxConfig. Use a
+/// [`LatmParser`] for streams that only send it now and then.
+pub fn parse_loas_frame(input: &[u8]) -> Result<LoasFrame, LatmError> {
+    LatmParser::default().parse(input)
+}
+
+/// Parses a stream of LOAS frames, remembering the last StreamMuxConfig
+/// for frames that reuse it.
+#[derive(Debug, Clone, Default)]
+pub struct LatmParser {
+    config: Option<StreamMuxConfig>,
+}
+
+impl LatmParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The most recent StreamMuxConfig.
+    pub fn config(&self) -> Option<&StreamMuxConfig> {
+        self.config.as_ref()
+    }
+
+    pub fn parse(&mut self, input: &[u8]) -> Result<LoasFrame, LatmError> {
+        let header = input.get(..LOAS_HEADER_LEN).ok_or(LatmError::Truncated)?;
+        if header[0] != 0x56 || header[1] & 0xE0 != 0xE0 {
+            return Err(LatmError::InvalidSyncWord);
+        }
+        let length = ((header[1] as usize & 0x1F) << 8) | header[2] as usize;
+        let element = input
+            .get(LOAS_HEADER_LEN..LOAS_HEADER_LEN + length)
+            .ok_or(LatmError::Truncated)?;
+
+        // AudioMuxElement with muxConfigPresent
+        let mut reader = BitReader::new(element);
+        let use_same_stream_mux = reader.read_bit()?;
+        let config = if use_same_stream_mux {
+            self.config.ok_or(LatmError::MissingStreamMuxConfig)?
+        } else {
+            read_stream_mux_config(&mut reader)?
+        };
+
+        // PayloadLengthInfo, then PayloadMux
+        let mut payload_length = 0;
+        loop {
+            let chunk = reader.read(8)? as usize;
+            payload_length += chunk;
+            if chunk != 255 {
+                break;
+            }
+        }
+        let payload = (0..payload_length)
+            .map(|_| reader.read(8).map(|byte| byte as u8))
+            .collect::<Result<Vec<u8>, _>>()?;
+
+        self.config = Some(config);
+        Ok(LoasFrame {
+            config,
+            payload: Bytes::from(payload),
+        })
+    }
+}
+
+fn read_stream_mux_config(reader: &mut BitReader) -> Result<StreamMuxConfig, LatmError> {
+    let audio_mux_version = reader.read_bit()?;
+    if audio_mux_version {
+        if reader.read_bit()? {
+            return Err(LatmError::UnsupportedVersion);
+        }
+        let _tara_buffer_fullness = read_latm_value(reader)?;
+    }
+
+    let _all_streams_same_time_framing = reader.read_bit()?;
+    let sub_frames = reader.read(6)? as u8;
+    let programs = reader.read(4)? as u8;
+    let layers = reader.read(3)? as u8;
+    if programs != 0 || layers != 0 {
+        return Err(LatmError::MultipleProgramsOrLayers {
+            programs: programs + 1,
+            layers: layers + 1,
+        });
+    }
+    if sub_frames != 0 {
+        return Err(LatmError::MultipleSubFrames(sub_frames + 1));
+    }
+
+    // Version 1 gives the config length; version 0 leaves it implicit, so
+    // nothing past the core config can be looked for
+    let audio_specific_config = if audio_mux_version {
+        let length_bits = read_latm_value(reader)? as usize;
+        let start = reader.bit_position();
+        let config = read_audio_specific_config(reader, length_bits)?;
+        let read = reader.bit_position() - start;
+        reader.skip(length_bits.saturating_sub(read))?;
+        config
+    } else {
+        read_audio_specific_config(reader, 0)?
+    };
+
+    let frame_length_type = reader.read(3)? as u8;
+    if frame_length_type != 0 {
+        return Err(LatmError::UnsupportedFrameLengthType(frame_length_type));
+    }
+    let latm_buffer_fullness = reader.read(8)? as u8;
+
+    if reader.read_bit()? {
+        // otherDataLenBits, skipped along with the payload
+        if audio_mux_version {
+            read_latm_value(reader)?;
+        } else {
+            while reader.read_bit()? {
+                reader.read(8)?;
+            }
+            reader.read(8)?;
+        }
+    }
+    if reader.read_bit()? {
+        let _crc_check_sum = reader.read(8)?;
+    }
+
+    Ok(StreamMuxConfig {
+        audio_specific_config,
+        latm_buffer_fullness,
+    })
+}
+
+// LatmGetValue: a two-bit byte count less one, then the bytes.
+fn read_latm_value(reader: &mut BitReader) -> Result<u32, EndOfInput> {
+    let bytes = reader.read(2)? + 1;
+    let mut value = 0;
+    for _ in 0..bytes {
+        value = (value << 8) | reader.read(8)?;
+    }
+    Ok(value)
+}
+
+/// Wraps one raw data block in a LOAS frame that carries `config`, or
+/// `None` if the frame would not fit the 13-bit length field.
+pub fn wrap_loas(payload: &[u8], config: &StreamMuxConfig) -> Option<Bytes> {
+    loas_frame(payload, Some(config))
+}
+
+// An AudioSyncStream frame around an AudioMuxElement, repeating the
+// previous StreamMuxConfig when `config` is None.
+fn loas_frame(payload: &[u8], config: Option<&StreamMuxConfig>) -> Option<Bytes> {
+    let mut writer = BitWriter::default();
+    writer.write(config.is_none() as u64, 1);
+    if let Some(config) = config {
+        config.write(&mut writer);
+    }
+
+    // PayloadLengthInfo counts the length in bytes of up to 255
+    let mut remaining = payload.len();
+    while remaining >= 255 {
+        writer.write(255, 8);
+        remaining -= 255;
+    }
+    writer.write(remaining as u64, 8);
+    for &byte in payload {
+        writer.write(byte as u64, 8);
+    }
+
+    let element = writer.finish();
+    if element.len() > 0x1FFF {
+        return None;
+    }
+    let mut frame = BytesMut::with_capacity(LOAS_HEADER_LEN + element.len());
+    frame.extend_from_slice(&[0x56, 0xE0 | (element.len() >> 8) as u8, element.len() as u8]);
+    frame.extend_from_slice(&element);
+    Some(frame.freeze())
+}
+
+/// Converts ADTS frames to LOAS, one frame per raw data block. The
+/// StreamMuxConfig is sent with the first frame, whenever the stream
+/// parameters change, and every [`LOAS_MUX_CONFIG_INTERVAL`] frames.
+/// Frames that fail to parse or their CRC check are dropped.
+pub fn adts_to_loas<'a>(frames: impl Iterator<Item = &'a [u8]>) -> Vec<Bytes> {
+    let mut loas = Vec::new();
+    let mut last_config = None;
+    let mut since_config = 0;
+
+    for frame in frames {
+        let Ok(header) = complete_frame_header(frame) else {
+            continue;
+        };
+        let Ok(blocks) = raw_data_blocks(frame, &header) else {
+            continue;
+        };
+        if !header.verify_crc(frame) {
+            continue;
+        }
+
+        let config = StreamMuxConfig::new(header.audio_specific_config());
+        for (range, _) in blocks {
+            let send_config =
+                last_config != Some(config) || since_config >= LOAS_MUX_CONFIG_INTERVAL;
+            let Some(loas_frame) = loas_frame(&frame[range], send_config.then_some(&config)) else {
+                continue;
+            };
+            if send_config {
+                last_config = Some(config);
+                since_config = 0;
+            }
+            since_config += 1;
+            loas.push(loas_frame);
+        }
+    }
+    loas
+}
+
+pub fn is_adif(input: &[u8]) -> bool {
+    input.starts_with(b"ADIF")
+}
+
+/// Parses the ADIF header at the start of `input`, up to the end of the
+/// first program config element.
+pub fn parse_adif_header(input: &[u8]) -> Option<AdifHeader> {
+    if !is_adif(input) {
+        return None;
+    }
+    read_adif_header(&mut BitReader::new(&input[4..])).ok()?
+}
+
+/// Checks whether `input` could be an ADIF header that has not been
+/// fully received yet.
+pub(crate) fn is_truncated_adif(input: &[u8]) -> bool {
+    if input.len() < 4 {
+        return b"ADIF".starts_with(input);
+    }
+    is_adif(input) && read_adif_header(&mut BitReader::new(&input[4..])).is_err()
+}
+
+fn read_adif_header(reader: &mut BitReader) -> Result<Option<AdifHeader>, EndOfInput> {
+    // copyright_id_present, then original_copy and home
+    if reader.read_bit()? {
+        reader.read(32)?;
+        reader.read(32)?;
+        reader.read(8)?;
+    }
+    reader.read(2)?;
+
+    // bitstream_type is 0 for constant rate
+    let is_cbr = !reader.read_bit()?;
+    let bitrate = reader.read(23)?;
+    let _num_program_config_elements = reader.read(4)?;
+    if is_cbr {
+        let _adif_buffer_fullness = reader.read(20)?;
+    }
+
+    Ok(read_program_config(reader)?.map(|program| AdifHeader {
+        bitrate,
+        is_cbr,
+        program,
+    }))
+}
+
+fn read_program_config(reader: &mut BitReader) -> Result<Option<ProgramConfig>, EndOfInput> {
+    let _element_instance_tag = reader.read(4)?;
+    let profile = reader.read(2)? as u8;
+    let Some(sample_rate) = sample_rate_from_index(reader.read(4)? as u8) else {
+        return Ok(None);
+    };
+
+    let front = reader.read(4)?;
+    let side = reader.read(4)?;
+    let back = reader.read(4)?;
+    let lfe = reader.read(2)?;
+    let assoc_data = reader.read(3)?;
+    let valid_cc = reader.read(4)?;
+
+    // mono and stereo mixdown element numbers, matrix mixdown index and
+    // pseudo surround flag
+    for bits in [4, 4, 3] {
+        if reader.read_bit()? {
+            reader.read(bits)?;
+        }
+    }
+
+    // Each front, side and back element is a flag telling a channel pair
+    // element from a single channel element, then its tag
+    let mut channels = lfe;
+    for _ in 0..front + side + back {
+        channels += 1 + reader.read_bit()? as u32;
+        reader.read(4)?;
+    }
+    for _ in 0..lfe + assoc_data {
+        reader.read(4)?;
+    }
+    for _ in 0..valid_cc {
+        reader.read(5)?;
+    }
+
+    // The comment field must be present in full
+    reader.align_to_byte();
+    let comment_field_bytes = reader.read(8)?;
+    for _ in 0..comment_field_bytes {
+        reader.read(8)?;
+    }
+
+    Ok(Some(ProgramConfig {
+        profile,
+        sample_rate,
+        channels: channels as u8,
+    }))
+}
+
+/// Fields of an MPEG-4 AudioSpecificConfig.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AudioSpecificConfig {
+    /// The core object type: 2 for HE-AAC signalled explicitly as SBR (5)
+    /// or PS (29) over LC.
+    pub audio_object_type: u8,
+    /// 0xF when the sampling frequency is coded explicitly.
+    pub sampling_frequency_index: u8,
+    /// The core sampling frequency.
+    pub sampling_frequency: u32,
+    pub channel_configuration: u8,
+    /// GASpecificConfig frameLengthFlag: 960-sample instead of 1024-sample
+    /// frames.
+    pub frame_length_flag: bool,
+    pub sbr_present: bool,
+    pub ps_present: bool,
+    /// Output sampling frequency of the SBR extension.
+    pub extension_sampling_frequency: Option<u32>,
+    /// The program config element when `channel_configuration` is 0.
+    pub program: Option<ProgramConfig>,
+}
+
+impl AudioSpecificConfig {
+    /// The output sample rate, after SBR when it is present.
+    pub fn sample_rate(&self) -> u32 {
+        match self.extension_sampling_frequency {
+            Some(rate) if self.sbr_present => rate,
+            _ => self.sampling_frequency,
+        }
+    }
+
+    /// Output channels, with PS upmixing mono to stereo. Zero when the
+    /// configuration is 0 and there is no program config element.
+    pub fn channels(&self) -> u8 {
+        let channels = match self.channel_configuration {
+            0 => self.program.map_or(0, |program| program.channels),
+            1..=6 => self.channel_configuration,
+            7 | 12 | 14 => 8,
+            11 => 7,
+            13 => 24,
+            _ => 0,
+        };
+        if self.ps_present && channels == 1 {
+            2
+        } else {
+            channels
+        }
+    }
+
+    /// Output samples per frame, after SBR when it is present and
+    /// doubles the rate.
+    pub fn samples_per_frame(&self) -> u32 {
+        let core = match (self.audio_object_type, self.frame_length_flag) {
+            // AAC-LD and ER AAC-ELD
+            (23 | 39, false) => 512,
+            (23 | 39, true) => 480,
+            (_, false) => 1024,
+            (_, true) => 960,
+        };
+        if self.sample_rate() > self.sampling_frequency {
+            core * 2
+        } else {
+            core
+        }
+    }
+
+    /// Serialises the config, signalling SBR and PS explicitly. A program
+    /// config element is not written, nor is the low delay SBR of ELD,
+    /// whose headers are not kept.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = BitWriter::default();
+        self.write(&mut writer);
+        writer.finish()
+    }
+
+    fn write(&self, writer: &mut BitWriter) {
+        if self.audio_object_type == 39 {
+            write_audio_object_type(writer, self.audio_object_type);
+            write_sampling_frequency(writer, self.sampling_frequency);
+            writer.write(self.channel_configuration as u64, 4);
+
+            // ELDSpecificConfig with no resilience, SBR or extensions, then
+            // epConfig
+            writer.write(self.frame_length_flag as u64, 1);
+            writer.write(0, 10);
+            return;
+        }
+
+        if self.sbr_present {
+            write_audio_object_type(writer, if self.ps_present { 29 } else { 5 });
+            write_sampling_frequency(writer, self.sampling_frequency);
+            writer.write(self.channel_configuration as u64, 4);
+            write_sampling_frequency(writer, self.sample_rate());
+            write_audio_object_type(writer, self.audio_object_type);
+        } else {
+            write_audio_object_type(writer, self.audio_object_type);
+            write_sampling_frequency(writer, self.sampling_frequency);
+            writer.write(self.channel_configuration as u64, 4);
+        }
+
+        // GASpecificConfig without a core coder
+        writer.write(self.frame_length_flag as u64, 1);
+        writer.write(0, 1);
+        match self.audio_object_type {
+            // Error resilient AAC sets the extension flag, clears the three
+            // resilience flags and extensionFlag3, and ends with epConfig
+            17 | 19 | 20 | 23 => writer.write(0b100_0000, 7),
+            21 | 22 => writer.write(0, 3),
+            _ => writer.write(0, 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AscError {
+    /// Shorter than the fields it signals.
+    Truncated,
+    ReservedSamplingIndex(u8),
+    /// Not one of the general audio object types GASpecificConfig covers.
+    UnsupportedObjectType(u8),
+}
+
+impl fmt::Display for AscError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AscError::Truncated => write!(f, "AudioSpecificConfig truncated"),
+            AscError::ReservedSamplingIndex(index) => {
+                write!(f, "Reserved sampling frequency index: {}", index)
+            }
+            AscError::UnsupportedObjectType(object_type) => {
+                write!(f, "Unsupported audio object type: {}", object_type)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AscError {}
+
+impl From<EndOfInput> for AscError {
+    fn from(_: EndOfInput) -> Self {
+        AscError::Truncated
+    }
+}
+
+/// Parses an AudioSpecificConfig, as carried in an MP4 esds box or an FLV
+/// AAC sequence header.
+pub fn parse_audio_specific_config(input: &[u8]) -> Result<AudioSpecificConfig, AscError> {
+    read_audio_specific_config(&mut BitReader::new(input), input.len() * 8)
+}
+
+// Reads a config taking at most `length_bits`, which bounds where the
+// backward compatible SBR and PS signalling may be looked for.
+fn read_audio_specific_config(
+    reader: &mut BitReader,
+    length_bits: usize,
+) -> Result<AudioSpecificConfig, AscError> {
+    let start = reader.bit_position();
+    let bits_left = |reader: &BitReader| length_bits.saturating_sub(reader.bit_position() - start);
+
+    let mut audio_object_type = read_audio_object_type(reader)?;
+    let (sampling_frequency_index, sampling_frequency) = read_sampling_frequency(reader)?;
+    let channel_configuration = reader.read(4)? as u8;
+
+    // Explicit hierarchical signalling puts SBR or PS first
+    let mut sbr_present = false;
+    let mut ps_present = false;
+    let mut extension_sampling_frequency = None;
+    if matches!(audio_object_type, 5 | 29) {
+        sbr_present = true;
+        ps_present = audio_object_type == 29;
+        extension_sampling_frequency = Some(read_sampling_frequency(reader)?.1);
+        audio_object_type = read_audio_object_type(reader)?;
+        if audio_object_type == 22 {
+            let _extension_channel_configuration = reader.read(4)?;
+        }
+    }
+
+    if !matches!(audio_object_type, 1..=4 | 6 | 7 | 17 | 19..=23 | 39) {
+        return Err(AscError::UnsupportedObjectType(audio_object_type));
+    }
+
+    let frame_length_flag;
+    let mut program = None;
+    if audio_object_type == 39 {
+        let ld_sbr_dual_rate;
+        (frame_length_flag, ld_sbr_dual_rate) =
+            read_eld_specific_config(reader, channel_configuration)?;
+        if let Some(dual_rate) = ld_sbr_dual_rate {
+            sbr_present = true;
+            extension_sampling_frequency = Some(if dual_rate {
+                2 * sampling_frequency
+            } else {
+                sampling_frequency
+            });
+        }
+    } else {
+        // GASpecificConfig
+        frame_length_flag = reader.read_bit()?;
+        if reader.read_bit()? {
+            let _core_coder_delay = reader.read(14)?;
+        }
+        let extension_flag = reader.read_bit()?;
+        if channel_configuration == 0 {
+            program = read_program_config(reader)?;
+        }
+        if matches!(audio_object_type, 6 | 20) {
+            let _layer_nr = reader.read(3)?;
+        }
+        if extension_flag {
+            if audio_object_type == 22 {
+                let _num_of_sub_frame = reader.read(5)?;
+                let _layer_length = reader.read(11)?;
+            }
+            if matches!(audio_object_type, 17 | 19 | 20 | 23) {
+                // The three resilience flags
+                reader.read(3)?;
+            }
+            let _extension_flag3 = reader.read_bit()?;
+        }
+    }
+    if matches!(audio_object_type, 17 | 19..=23 | 39) {
+        let _ep_config = reader.read(2)?;
+    }
+
+    // Backward compatible signalling appends SBR and PS after the core
+    // config, where decoders unaware of it stop reading
+    if !sbr_present
+        && bits_left(reader) >= 16
+        && reader.read(11)? == 0x2B7
+        && read_audio_object_type(reader)? == 5
+    {
+        sbr_present = reader.read_bit()?;
+        if sbr_present {
+            extension_sampling_frequency = Some(read_sampling_frequency(reader)?.1);
+            if bits_left(reader) >= 12 && reader.read(11)? == 0x548 {
+                ps_present = reader.read_bit()?;
+            }
+        }
+    }
+
+    Ok(AudioSpecificConfig {
+        audio_object_type,
+        sampling_frequency_index,
+        sampling_frequency,
+        channel_configuration,
+        frame_length_flag,
+        sbr_present,
+        ps_present,
+        extension_sampling_frequency,
+        program,
+    })
+}
+
+// ELDSpecificConfig: the frame length flag and, when low delay SBR is
+// present, whether it runs at twice the core rate.
+fn read_eld_specific_config(
+    reader: &mut BitReader,
+    channel_configuration: u8,
+) -> Result<(bool, Option<bool>), EndOfInput> {
+    let frame_length_flag = reader.read_bit()?;
+    // The three resilience flags
+    reader.read(3)?;
+
+    let mut ld_sbr_dual_rate = None;
+    if reader.read_bit()? {
+        ld_sbr_dual_rate = Some(reader.read_bit()?);
+        let _ld_sbr_crc_flag = reader.read_bit()?;
+        let sbr_headers = match channel_configuration {
+            1 | 2 => 1,
+            3 => 2,
+            4..=6 => 3,
+            7 => 4,
+            _ => 0,
+        };
+        for _ in 0..sbr_headers {
+            skip_sbr_header(reader)?;
+        }
+    }
+
+    // Extensions up to ELDEXT_TERM
+    while reader.read(4)? != 0 {
+        let mut eld_ext_len = reader.read(4)?;
+        if eld_ext_len == 15 {
+            let eld_ext_len_add = reader.read(8)?;
+            eld_ext_len += eld_ext_len_add;
+            if eld_ext_len_add == 255 {
+                eld_ext_len += reader.read(16)?;
+            }
+        }
+        reader.skip(8 * eld_ext_len as usize)?;
+    }
+
+    Ok((frame_length_flag, ld_sbr_dual_rate))
+}
+
+fn skip_sbr_header(reader: &mut BitReader) -> Result<(), EndOfInput> {
+    // bs_amp_res, bs_start_freq, bs_stop_freq, bs_xover_band and
+    // bs_reserved
+    reader.read(14)?;
+    let header_extra_1 = reader.read_bit()?;
+    let header_extra_2 = reader.read_bit()?;
+    if header_extra_1 {
+        // bs_freq_scale, bs_alter_scale and bs_noise_bands
+        reader.read(5)?;
+    }
+    if header_extra_2 {
+        // bs_limiter_bands, bs_limiter_gains, bs_interpol_freq and
+        // bs_smoothing_mode
+        reader.read(6)?;
+    }
+    Ok(())
+}
+
+/// RFC 6381 codec string for `asc`, `mp4a.40.` then the object type. SBR
+/// and PS signalled in the config give HE-AAC v1 (`mp4a.40.5`) and v2
+/// (`mp4a.40.29`); when they are left implicit only the core object type
+/// is known.
+pub fn codec_string(asc: &AudioSpecificConfig) -> String {
+    let object_type = match asc.audio_object_type {
+        // The low delay SBR of ELD is part of the ELD object type
+        39 => 39,
+        _ if asc.ps_present => 29,
+        _ if asc.sbr_present => 5,
+        object_type => object_type,
+    };
+    format!("mp4a.40.{}", object_type)
+}
+
+/// Builds the AudioSpecificConfig for a general audio object type, such
+/// as 2 for LC. A rate outside the sampling frequency table is coded
+/// explicitly. `channels` is the channel count; counts without a channel
+/// configuration of their own are written as configuration 0. Panics for
+/// an object type [`try_build_audio_specific_config`] rejects.
+pub fn build_audio_specific_config(object_type: u8, sample_rate: u32, channels: u8) -> Vec<u8> {
+    try_build_audio_specific_config(object_type, sample_rate, channels)
+        .expect("audio object type cannot be written to an AudioSpecificConfig")
+}
+
+/// Like [`build_audio_specific_config`], but returns `None` for 31, the
+/// escape value, and for types above 95, which the escaped form cannot
+/// reach.
+pub fn try_build_audio_specific_config(
+    object_type: u8,
+    sample_rate: u32,
+    channels: u8,
+) -> Option<Vec<u8>> {
+    if object_type == 31 || object_type > MAX_AUDIO_OBJECT_TYPE {
+        return None;
+    }
+    Some(
+        core_audio_specific_config(object_type, sample_rate, channel_configuration(channels))
+            .to_bytes(),
+    )
+}
+
+fn channel_configuration(channels: u8) -> u8 {
+    match channels {
+        1..=6 => channels,
+        7 => 11,
+        8 => 7,
+        _ => 0,
+    }
+}
+
+fn core_audio_specific_config(
+    object_type: u8,
+    sample_rate: u32,
+    channel_configuration: u8,
+) -> AudioSpecificConfig {
+    AudioSpecificConfig {
+        audio_object_type: object_type,
+        sampling_frequency_index: sample_rate_index(sample_rate),
+        sampling_frequency: sample_rate,
+        channel_configuration,
+        frame_length_flag: false,
+        sbr_present: false,
+        ps_present: false,
+        extension_sampling_frequency: None,
+        program: None,
+    }
+}
+
+// 31 escapes to six more bits holding the type less 32.
+const MAX_AUDIO_OBJECT_TYPE: u8 = 32 + 63;
+
+fn write_audio_object_type(writer: &mut BitWriter, object_type: u8) {
+    if object_type > 31 {
+        writer.write(31, 5);
+        writer.write((object_type - 32) as u64, 6);
+    } else {
+        writer.write(object_type as u64, 5);
+    }
+}
+
+fn write_sampling_frequency(writer: &mut BitWriter, sample_rate: u32) {
+    match sample_rate_index(sample_rate) {
+        0xF => {
+            writer.write(0xF, 4);
+            writer.write(sample_rate as u64, 24);
+        }
+        index => writer.write(index as u64, 4),
+    }
+}
+
+fn read_audio_object_type(reader: &mut BitReader) -> Result<u8, EndOfInput> {
+    match reader.read(5)? as u8 {
+        31 => Ok(32 + reader.read(6)? as u8),
+        object_type => Ok(object_type),
+    }
+}
+
+fn read_sampling_frequency(reader: &mut BitReader) -> Result<(u8, u32), AscError> {
+    let index = reader.read(4)? as u8;
+    if index == 0xF {
+        return Ok((index, reader.read(24)?));
+    }
+    sample_rate_from_index(index)
+        .map(|rate| (index, rate))
+        .ok_or(AscError::ReservedSamplingIndex(index))
+}
+
+/// The payload of the ADTS frame at the start of `sound_data`, or `None`
+/// if there is no complete, intact frame. See [`extract_aac_data_checked`]
+/// for the reason.
+pub fn extract_aac_data(sound_data: &Bytes) -> Option<Bytes> {
+    extract_aac_data_checked(sound_data).ok()
+}
+
+/// The payload of the ADTS frame at the start of `sound_data`, after
+/// checking that the whole frame is present and that [`check_crc`] finds
+/// no damage.
+pub fn extract_aac_data_checked(sound_data: &Bytes) -> Result<Bytes, AdtsError> {
+    let header = complete_frame_header(sound_data)?;
+    if !header.verify_crc(sound_data) {
+        return Err(AdtsError::CrcMismatch);
+    }
+    Ok(sound_data.slice(header.header_length()..header.frame_length))
+}
+
+fn complete_frame_header(frame: &[u8]) -> Result<AdtsHeader, AdtsError> {
+    let header = parse_adts_header(frame)?;
+    if frame.len() < header.frame_length {
+        return Err(AdtsError::FrameLengthExceedsInput {
+            frame_length: header.frame_length,
+            available: frame.len(),
+        });
+    }
+    Ok(header)
+}
+
+/// Splits a complete ADTS frame into its raw data blocks. Only protected
+/// frames say where each block starts, so an unprotected frame with more
+/// than one block fails with [`AdtsError::MissingBlockPositions`].
+pub fn extract_aac_blocks(frame: &Bytes) -> Result<Vec<Bytes>, AdtsError> {
+    let header = complete_frame_header(frame)?;
+    Ok(raw_data_blocks(frame, &header)?
+        .into_iter()
+        .map(|(range, _)| frame.slice(range))
+        .collect())
+}
+
+// Byte range of a raw data block, with the CRC word that follows it when
+// the frame holds several.
+type RawDataBlock = (Range<usize>, Option<u16>);
+
+fn raw_data_blocks(frame: &[u8], header: &AdtsHeader) -> Result<Vec<RawDataBlock>, AdtsError> {
+    let start = header.header_length();
+    let end = header.frame_length;
+    if header.number_of_raw_data_blocks == 0 {
+        return Ok(vec![(start..end, None)]);
+    }
+    if header.protection_absent {
+        return Err(AdtsError::MissingBlockPositions);
+    }
+
+    // raw_data_block_position[i] is block i's offset from the first block
+    let mut offsets = vec![0];
+    offsets.extend(
+        frame[7..start - 2]
+            .chunks_exact(2)
+            .map(|position| u16::from_be_bytes([position[0], position[1]]) as usize),
+    );
+    offsets.push(end - start);
+
+    offsets
+        .windows(2)
+        .map(|pair| {
+            let (block_start, block_end) = (start + pair[0], start + pair[1]);
+            if block_end < block_start + 2 || block_end > end {
+                return Err(AdtsError::InvalidBlockPosition);
+            }
+            let crc = u16::from_be_bytes([frame[block_end - 2], frame[block_end - 1]]);
+            Ok((block_start..block_end - 2, Some(crc)))
+        })
+        .collect()
+}
+
+/// Stream parameters for an ADTS header when there is no
+/// AudioSpecificConfig to take them from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdtsParams {
+    /// Audio object type: 1 Main, 2 LC, 3 SSR or 4 LTP.
+    pub object_type: u8,
+    pub sample_rate: u32,
+    pub channels: u8,
+}
+
+/// Returns `data` untouched when it already starts with an ADTS header.
+/// Otherwise `data` is taken as one raw data block and an ADTS header is
+/// put in front of it, built from `asc` when given and from `fallback`
+/// otherwise.
+pub fn ensure_adts(
+    data: Bytes,
+    asc: Option<&AudioSpecificConfig>,
+    fallback: &AdtsParams,
+) -> Result<Bytes, AdtsError> {
+    if is_aac_single_frame(&data) {
+        return Ok(data);
+    }
+    if data.len() < 2 {
+        return Err(AdtsError::Truncated);
+    }
+
+    // With SBR or PS the ADTS header describes the core stream
+    let (object_type, sample_rate, channel_configuration) = match asc {
+        Some(asc) => (
+            asc.audio_object_type,
+            asc.sampling_frequency,
+            asc.channel_configuration,
+        ),
+        None => (
+            fallback.object_type,
+            fallback.sample_rate,
+            channel_configuration(fallback.channels),
+        ),
+    };
+    let header = object_type_adts_header(
+        object_type,
+        sample_rate,
+        channel_configuration,
+        data.len(),
+        false,
+    )?;
+    let mut frame = BytesMut::with_capacity(7 + data.len());
+    frame.extend_from_slice(&header[..7]);
+    frame.extend_from_slice(&data);
+    Ok(frame.freeze())
+}
+
+#[deprecated(note = "use aac::ensure_adts, which keeps every payload byte")]
+pub fn ensure_adts_header(data: Bytes, channels: u8, sample_rate: u32) -> Bytes {
+    // Too short to hold an ASC, leave it to the caller
+    if data.len() < 2 {
+        return data;
+    }
+
+    // Assume that the first byte might contain the ASC if `extract_aac_data` finds no ADTS header
+    if complete_frame_header(&data).is_err() {
+        // Assuming data[0] is present and is the first byte of ASC
+        // Parse the profile from the ASC
+        let audio_object_type = data[0] >> 3; // First 5 bits contain the audio object type
+        let object_type = match audio_object_type {
+            2 => AacObjectType::HeV1,
+            5 => AacObjectType::HeV2,
+            _ => AacObjectType::Lc, // Default to AAC-LC if unknown
+        };
+
+        let params = AdtsParams {
+            object_type: object_type.audio_object_type(),
+            sample_rate,
+            channels,
+        };
+        let mut payload = BytesMut::with_capacity(7 + data.len() - 2);
+        write_adts_header(&mut payload, &params, data.len() - 2, false)
+            .expect("every AacObjectType has a header");
+        payload.extend_from_slice(&data[2..]); // Skip the first two bytes if they are part of ASC
+
+        return payload.freeze();
+    }
+
+    return data;
+}
+
+/// CRC-16 with polynomial x^16 + x^15 + x^2 + 1, MSB first, starting from
+/// all ones, as ISO 13818-7 specifies for adts_error_check.
+pub fn adts_crc(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, &b| crc16_update(crc, b))
+}
+
+/// Checks the CRCs of a complete ADTS frame. A single raw data block
+/// shares one CRC with the header fields; with several blocks the header
+/// and its block positions have one CRC and each block its own.
+///
+/// A block's CRC covers only parts of it: the leading 192 bits of the
+/// first and 128 bits of the second individual_channel_stream of each
+/// channel element, zero padded when shorter, and the whole of each data
+/// stream and program config element. Finding where a channel stream with
+/// spectral data ends takes Huffman decoding, so only blocks whose channel
+/// streams are silent can be checked.
+///
+/// `None` when the frame has no CRC or its protected bits cannot be
+/// located. Frames that cannot be parsed or are cut short give
+/// `Some(false)`.
+pub fn check_crc(frame: &[u8]) -> Option<bool> {
+    let Ok(header) = complete_frame_header(frame) else {
+        return Some(false);
+    };
+    let crc = header.crc?;
+    let Ok(blocks) = raw_data_blocks(frame, &header) else {
+        return Some(false);
+    };
+
+    if header.number_of_raw_data_blocks == 0 {
+        let (range, _) = blocks[0].clone();
+        return match protected_crc(adts_crc(&frame[..7]), &frame[range]) {
+            Ok(block_crc) => block_crc.map(|block_crc| block_crc == crc),
+            Err(EndOfInput) => Some(false),
+        };
+    }
+
+    if crc != adts_crc(&frame[..header.header_length() - 2]) {
+        return Some(false);
+    }
+    let mut checked = Some(true);
+    for (range, block_crc) in blocks {
+        match protected_crc(0xFFFF, &frame[range]) {
+            Ok(Some(crc)) if Some(crc) == block_crc => {}
+            Ok(None) => checked = None,
+            Ok(Some(_)) | Err(EndOfInput) => return Some(false),
+        }
+    }
+    checked
+}
+
+/// Whether a complete ADTS frame passes [`check_crc`]. Frames without a
+/// CRC, or whose protected bits cannot be located, pass.
+pub fn verify_crc(frame: &[u8]) -> bool {
+    check_crc(frame) != Some(false)
+}
+
+// Bits of a raw data block covered by its CRC, counted from `start`.
+// Channel streams shorter than `padded_len` are zero padded.
+struct CrcRegion {
+    start: usize,
+    len: usize,
+    padded_len: usize,
+}
+
+const FIRST_ICS_CRC_BITS: usize = 192;
+const SECOND_ICS_CRC_BITS: usize = 128;
+
+// Continues `crc` over the protected bits of a raw data block, or None
+// when they cannot be located.
+fn protected_crc(crc: u16, block: &[u8]) -> Result<Option<u16>, EndOfInput> {
+    let Some(regions) = crc_regions(block)? else {
+        return Ok(None);
+    };
+
+    let mut crc = crc;
+    for region in regions {
+        for i in 0..region.padded_len {
+            let pos = region.start + i;
+            let bit = i < region.len && block[pos / 8] & (0x80 >> (pos % 8)) != 0;
+            let feedback = (crc & 0x8000 != 0) != bit;
+            crc <<= 1;
+            if feedback {
+                crc ^= 0x8005;
+            }
+        }
+    }
+    Ok(Some(crc))
+}
+
+fn crc_regions(block: &[u8]) -> Result<Option<Vec<CrcRegion>>, EndOfInput> {
+    let mut reader = BitReader::new(block);
+    let mut regions = Vec::new();
+
+    loop {
+        match reader.read(3)? {
+            // SCE and LFE
+            0 | 3 => {
+                reader.read(4)?;
+                if !push_ics_region(&mut reader, &mut regions, None, FIRST_ICS_CRC_BITS)? {
+                    return Ok(None);
+                }
+            }
+            // CPE
+            1 => {
+                reader.read(4)?;
+                let mut common = None;
+                if reader.read_bit()? {
+                    let Some(info) = read_ics_info(&mut reader)? else {
+                        return Ok(None);
+                    };
+                    match reader.read(2)? {
+                        0 | 2 => {}
+                        1 => reader.skip((info.window_groups * info.max_sfb) as usize)?,
+                        _ => return Ok(None),
+                    }
+                    common = Some(info);
+                }
+                for padded_len in [FIRST_ICS_CRC_BITS, SECOND_ICS_CRC_BITS] {
+                    if !push_ics_region(&mut reader, &mut regions, common, padded_len)? {
+                        return Ok(None);
+                    }
+                }
+            }
+            // CCE
+            2 => return Ok(None),
+            // DSE and PCE are covered from their element_instance_tag on
+            id @ (4 | 5) => {
+                let start = reader.bit_position();
+                if id == 4 {
+                    reader.read(4)?;
+                    let byte_align = reader.read_bit()?;
+                    let mut count = reader.read(8)?;
+                    if count == 255 {
+                        count += reader.read(8)?;
+                    }
+                    if byte_align {
+                        reader.align_to_byte();
+                    }
+                    reader.skip(8 * count as usize)?;
+                } else if read_program_config(&mut reader)?.is_none() {
+                    return Ok(None);
+                }
+                let len = reader.bit_position() - start;
+                regions.push(CrcRegion {
+                    start,
+                    len,
+                    padded_len: len,
+                });
+            }
+            // FIL
+            6 => {
+                let mut count = reader.read(4)?;
+                if count == 15 {
+                    count = count + reader.read(8)? - 1;
+                }
+                reader.skip(8 * count as usize)?;
+            }
+            // END
+            _ => return Ok(Some(regions)),
+        }
+    }
+}
+
+// Adds the channel stream at the reader, giving false if it carries
+// spectral data and so cannot be walked.
+fn push_ics_region(
+    reader: &mut BitReader,
+    regions: &mut Vec<CrcRegion>,
+    common: Option<IcsInfo>,
+    padded_len: usize,
+) -> Result<bool, EndOfInput> {
+    let start = reader.bit_position();
+    let silent = skip_silent_ics(reader, common)?;
+    regions.push(CrcRegion {
+        start,
+        len: (reader.bit_position() - start).min(padded_len),
+        padded_len,
+    });
+    Ok(silent)
+}
+
+/// Builds a complete ADTS frame around `payload`, a single raw data block,
+/// with its CRC. `None` for a low delay object type, or when the
+/// protected bits of `payload` cannot be located; see [`check_crc`].
+pub fn create_adts_header_with_crc(
+    object_type: AacObjectType,
+    channels: u8,
+    sample_rate: u32,
+    payload: &[u8],
+) -> Option<Vec<u8>> {
+    let mut frame =
+        create_adts_header_for(object_type, channels, sample_rate, payload.len(), true).ok()?;
+    let crc = protected_crc(adts_crc(&frame[..7]), payload).ok()??;
+    frame[7..9].copy_from_slice(&crc.to_be_bytes());
+    frame.extend_from_slice(payload);
+    Some(frame)
+}
+
+/// AAC object types an ADTS header can describe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AacObjectType {
+    Main,
+    Lc,
+    Ssr,
+    Ltp,
+    /// LC with SBR.
+    HeV1,
+    /// LC with SBR and PS.
+    HeV2,
+    /// ER AAC-LD, with 480 or 512-sample frames.
+    Ld,
+    /// ER AAC-ELD, with 480 or 512-sample frames.
+    Eld,
+}
+
+impl AacObjectType {
+    /// The variant for an MPEG-4 audio object type, if it is one of these.
+    pub fn from_audio_object_type(object_type: u8) -> Option<Self> {
+        match object_type {
+            1 => Some(AacObjectType::Main),
+            2 => Some(AacObjectType::Lc),
+            3 => Some(AacObjectType::Ssr),
+            4 => Some(AacObjectType::Ltp),
+            5 => Some(AacObjectType::HeV1),
+            29 => Some(AacObjectType::HeV2),
+            23 => Some(AacObjectType::Ld),
+            39 => Some(AacObjectType::Eld),
+            _ => None,
+        }
+    }
+
+    /// The MPEG-4 audio object type.
+    pub fn audio_object_type(&self) -> u8 {
+        match self {
+            AacObjectType::Main => 1,
+            AacObjectType::Lc => 2,
+            AacObjectType::Ssr => 3,
+            AacObjectType::Ltp => 4,
+            AacObjectType::HeV1 => 5,
+            AacObjectType::HeV2 => 29,
+            AacObjectType::Ld => 23,
+            AacObjectType::Eld => 39,
+        }
+    }
+
+    /// The ADTS profile field. HE-AAC is signalled as its LC core, with
+    /// SBR and PS left implicit. The low delay types have no ADTS profile.
+    pub fn adts_profile(&self) -> Option<u8> {
+        match self {
+            AacObjectType::Main => Some(0),
+            AacObjectType::Lc | AacObjectType::HeV1 | AacObjectType::HeV2 => Some(1),
+            AacObjectType::Ssr => Some(2),
+            AacObjectType::Ltp => Some(3),
+            AacObjectType::Ld | AacObjectType::Eld => None,
+        }
+    }
+}
+
+/// Writes an ADTS header for a frame of `payload_len` payload bytes.
+/// `sample_rate` is the rate of the AAC core, half the output rate for
+/// HE-AAC. With `crc` the CRC word is left as zero; use
+/// [`create_adts_header_with_crc`] for a frame decoders will accept.
+///
+/// A sample rate outside the table is written as the reserved index 0xF
+/// and channels are clamped to 7, giving headers decoders reject;
+/// [`try_create_adts_header`] reports these instead. Fails only for the
+/// low delay types, which ADTS cannot carry.
+pub fn create_adts_header_for(
+    object_type: AacObjectType,
+    channels: u8,
+    sample_rate: u32,
+    payload_len: usize,
+    crc: bool,
+) -> Result<Vec<u8>, AdtsError> {
+    create_adts_header_with_version(
+        MpegVersion::Mpeg4,
+        object_type,
+        channels,
+        sample_rate,
+        payload_len,
+        crc,
+    )
+}
+
+/// [`create_adts_header_for`] with the ID bit set for `mpeg_version`, for
+/// output that must match an MPEG-2 source. MPEG-2 has no LTP profile, so
+/// [`AacObjectType::Ltp`] gives a header decoders reject.
+pub fn create_adts_header_with_version(
+    mpeg_version: MpegVersion,
+    object_type: AacObjectType,
+    channels: u8,
+    sample_rate: u32,
+    payload_len: usize,
+    crc: bool,
+) -> Result<Vec<u8>, AdtsError> {
+    let header = lenient_adts_header(
+        mpeg_version,
+        object_type,
+        channels,
+        sample_rate,
+        payload_len,
+        crc,
+    )?;
+    Ok(header[..adts_header_length(!crc, 0)].to_vec())
+}
+
+fn lenient_adts_header(
+    mpeg_version: MpegVersion,
+    object_type: AacObjectType,
+    channels: u8,
+    sample_rate: u32,
+    payload_len: usize,
+    crc: bool,
+) -> Result<[u8; 9], AdtsError> {
+    let profile = object_type
+        .adts_profile()
+        .ok_or(AdtsError::UnsupportedObjectType(
+            object_type.audio_object_type(),
+        ))?;
+    Ok(adts_header_array(
+        mpeg_version,
+        profile,
+        sample_rate_index(sample_rate),
+        channels.min(7),
+        payload_len + adts_header_length(!crc, 0),
+        crc,
+    ))
+}
+
+/// Writes an ADTS header, taking the object type from an FLV-style codec
+/// id: 0x66 LC, 0x67 HE-AAC v1 and 0x68 HE-AAC v2.
+#[deprecated(note = "use aac::create_adts_header_for")]
+pub fn create_adts_header(
+    codec_id: u8,
+    channels: u8,
+    sample_rate: u32,
+    aac_frame_length: usize,
+    has_crc: bool,
+) -> Vec<u8> {
+    let object_type = match codec_id {
+        0x67 => AacObjectType::HeV1,
+        0x68 => AacObjectType::HeV2,
+        _ => AacObjectType::Lc,
+    };
+    create_adts_header_for(
+        object_type,
+        channels,
+        sample_rate,
+        aac_frame_length,
+        has_crc,
+    )
+    .expect("LC and HE-AAC have an ADTS profile")
+}
+
+/// Like [`create_adts_header_for`], but fails instead of writing a header
+/// decoders reject: for a low delay object type, for a sample rate outside
+/// the table, for channel configuration 0 or above 7, and for a frame too
+/// long for the 13-bit length field.
+///
+/// `channel_config` is the ADTS channel configuration, not a channel
+/// count: 1 to 6 match their counts, and 7 is 7.1, eight channels.
+/// [`ensure_adts`] takes a count instead.
+pub fn try_create_adts_header(
+    object_type: AacObjectType,
+    channel_config: u8,
+    sample_rate: u32,
+    payload_len: usize,
+    crc: bool,
+) -> Result<Vec<u8>, AdtsError> {
+    let profile = object_type
+        .adts_profile()
+        .ok_or(AdtsError::UnsupportedObjectType(
+            object_type.audio_object_type(),
+        ))?;
+    let header = checked_adts_header(profile, sample_rate, channel_config, payload_len, crc)?;
+    Ok(header[..adts_header_length(!crc, 0)].to_vec())
+}
+
+/// Appends the ADTS header for a frame of `payload_len` payload bytes to
+/// `buf`, without allocating a header of its own. The bytes are those
+/// [`create_adts_header_for`] writes: `params.channels` is the channel
+/// configuration, clamped to 7, and HE-AAC is written as LC. Fails for an
+/// object type [`AacObjectType`] does not cover and for the low delay
+/// types, which ADTS cannot carry.
+pub fn write_adts_header(
+    buf: &mut BytesMut,
+    params: &AdtsParams,
+    payload_len: usize,
+    crc: bool,
+) -> Result<(), AdtsError> {
+    let object_type = AacObjectType::from_audio_object_type(params.object_type)
+        .ok_or(AdtsError::UnsupportedObjectType(params.object_type))?;
+    let header = lenient_adts_header(
+        MpegVersion::Mpeg4,
+        object_type,
+        params.channels,
+        params.sample_rate,
+        payload_len,
+        crc,
+    )?;
+    buf.extend_from_slice(&header[..adts_header_length(!crc, 0)]);
+    Ok(())
+}
+
+// checked_adts_header from an audio object type rather than a profile
+fn object_type_adts_header(
+    object_type: u8,
+    sample_rate: u32,
+    channel_configuration: u8,
+    payload_len: usize,
+    crc: bool,
+) -> Result<[u8; 9], AdtsError> {
+    if !(1..=4).contains(&object_type) {
+        return Err(AdtsError::UnsupportedObjectType(object_type));
+    }
+    checked_adts_header(
+        object_type - 1,
+        sample_rate,
+        channel_configuration,
+        payload_len,
+        crc,
+    )
+}
+
+fn checked_adts_header(
+    profile: u8,
+    sample_rate: u32,
+    channel_configuration: u8,
+    aac_frame_length: usize,
+    has_crc: bool,
+) -> Result<[u8; 9], AdtsError> {
+    let sample_rate_index = match sample_rate_index(sample_rate) {
+        0xF => return Err(AdtsError::UnsupportedSampleRate(sample_rate)),
+        index => index,
+    };
+    if !(1..=7).contains(&channel_configuration) {
+        return Err(AdtsError::UnsupportedChannelConfiguration(
+            channel_configuration,
+        ));
+    }
+    let frame_length = aac_frame_length + adts_header_length(!has_crc, 0);
+    if frame_length > 0x1FFF {
+        return Err(AdtsError::FrameTooLong(frame_length));
+    }
+
+    Ok(adts_header_array(
+        MpegVersion::Mpeg4,
+        profile,
+        sample_rate_index,
+        channel_configuration,
+        frame_length,
+        has_crc,
+    ))
+}
+
+// The header in the first 7 bytes, or 9 with a zeroed CRC word
+fn adts_header_array(
+    mpeg_version: MpegVersion,
+    profile: u8,
+    sample_rate_index: u8,
+    channel_config: u8,
+    frame_length: usize,
+    has_crc: bool,
+) -> [u8; 9] {
+    let id = match mpeg_version {
+        MpegVersion::Mpeg2 => 0x08,
+        MpegVersion::Mpeg4 => 0x00,
+    };
+    let protection_absent = if has_crc { 0 } else { 1 };
+
+    [
+        0xFF,
+        0xF0 | id | protection_absent,
+        (profile << 6) | (sample_rate_index << 2) | (channel_config >> 2),
+        (channel_config & 3) << 6 | ((frame_length >> 11) & 0x03) as u8,
+        ((frame_length >> 3) & 0xFF) as u8,
+        (((frame_length & 0x07) << 5) | 0x1F) as u8,
+        0xFC,
+        0x00,
+        0x00,
+    ]
+}
+
+/// Reads the 13-bit frame_length field (header included) from an ADTS header.
+pub(crate) fn adts_frame_length(header: &[u8]) -> usize {
+    ((header[3] as usize & 0x03) << 11) | ((header[4] as usize) << 3) | ((header[5] as usize) >> 5)
+}
+
+pub(crate) fn sample_rate_from_index(index: u8) -> Option<u32> {
+    match index {
+        0x0 => Some(96000),
+        0x1 => Some(88200),
+        0x2 => Some(64000),
+        0x3 => Some(48000),
+        0x4 => Some(44100),
+        0x5 => Some(32000),
+        0x6 => Some(24000),
+        0x7 => Some(22050),
+        0x8 => Some(16000),
+        0x9 => Some(12000),
+        0xA => Some(11025),
+        0xB => Some(8000),
+        0xC => Some(7350),
+        _ => None,
+    }
+}
+
+pub(crate) fn sample_rate_index(sample_rate: u32) -> u8 {
+    match sample_rate {
+        96000 => 0x0,
+        88200 => 0x1,
+        64000 => 0x2,
+        48000 => 0x3,
+        44100 => 0x4,
+        32000 => 0x5,
+        24000 => 0x6,
+        22050 => 0x7,
+        16000 => 0x8,
+        12000 => 0x9,
+        11025 => 0xA,
+        8000 => 0xB,
+        7350 => 0xC,
+        _ => 0xF, // Invalid sample rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mse_fmp4::aac::{ChannelConfiguration, SamplingFrequency};
+
+    #[test]
+    fn test_adts_header_parsing() {
+        let data = vec![0u8; 200]; // Dummy AAC frame data
+        let channels = 2u8;
+        let sample_rate = 44100u32;
+        let adts_payload =
+            create_adts_header_for(AacObjectType::Lc, channels, sample_rate, data.len(), false)
+                .unwrap();
+        let mut full_payload = adts_payload.clone();
+        full_payload.extend_from_slice(&data);
+
+        let adts = mse_fmp4::aac::AdtsHeader::read_from(&full_payload[..]).unwrap();
+        assert_eq!(adts.frame_len, 207);
+        assert_eq!(adts.sampling_frequency, SamplingFrequency::Hz44100);
+        assert_eq!(
+            adts.channel_configuration,
+            ChannelConfiguration::TwoChannels
+        );
+        assert_eq!(adts.profile, mse_fmp4::aac::AacProfile::Lc);
+    }
+
+    #[test]
+    fn test_parse_adts_header_round_trip() {
+        for (object_type, profile, channels, sample_rate, has_crc) in [
+            (AacObjectType::Lc, 1, 2, 44100, false),
+            (AacObjectType::Main, 0, 1, 22050, true),
+            (AacObjectType::Ssr, 2, 6, 48000, false),
+            (AacObjectType::Ltp, 3, 7, 7350, true),
+            (AacObjectType::HeV1, 1, 2, 24000, false),
+            (AacObjectType::HeV2, 1, 1, 22050, true),
+        ] {
+            let header =
+                create_adts_header_for(object_type, channels, sample_rate, 300, has_crc).unwrap();
+            let parsed = parse_adts_header(&header).unwrap();
+            assert_eq!(
+                parsed,
+                AdtsHeader {
+                    mpeg_version: MpegVersion::Mpeg4,
+                    profile,
+                    sampling_frequency_index: sample_rate_index(sample_rate),
+                    sample_rate,
+                    channel_configuration: channels,
+                    frame_length: 300 + header.len(),
+                    buffer_fullness: 0x7FF,
+                    number_of_raw_data_blocks: 0,
+                    protection_absent: !has_crc,
+                    crc: has_crc.then_some(0),
+                }
+            );
+            assert_eq!(parsed.header_length(), header.len());
+        }
+    }
+
+    #[test]
+    fn test_mpeg2_id_bit() {
+        // MPEG-2 LC, 44.1 kHz stereo, 16-byte frames
+        let header = [0xFF, 0xF9, 0x50, 0x80, 0x02, 0x1F, 0xFC];
+        let parsed = parse_adts_header(&header).unwrap();
+        assert_eq!(parsed.mpeg_version, MpegVersion::Mpeg2);
+        assert_eq!(parsed.profile, 1);
+        assert_eq!(
+            create_adts_header_with_version(
+                MpegVersion::Mpeg2,
+                AacObjectType::Lc,
+                2,
+                44100,
+                9,
+                false
+            )
+            .unwrap(),
+            header
+        );
+        assert_eq!(
+            parse_adts_header(
+                &create_adts_header_for(AacObjectType::Lc, 2, 44100, 9, false).unwrap()
+            )
+            .unwrap()
+            .mpeg_version,
+            MpegVersion::Mpeg4
+        );
+
+        let mut frame = header.to_vec();
+        frame.extend([0x22; 9]);
+        let stream = [&frame[..], &frame[..]].concat();
+        assert!(is_aac(&stream));
+        assert_eq!(AdtsFrameIter::new(&stream).flatten().count(), 2);
+        assert_eq!(
+            extract_aac_data(&Bytes::from(frame)),
+            Some(Bytes::from(vec![0x22; 9]))
+        );
+
+        let ltp = create_adts_header_with_version(
+            MpegVersion::Mpeg2,
+            AacObjectType::Ltp,
+            2,
+            44100,
+            9,
+            false,
+        )
+        .unwrap();
+        assert_eq!(parse_adts_header(&ltp), Err(AdtsError::ReservedProfile));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_codec_id_shim() {
+        for (codec_id, object_type) in [
+            (0x66, AacObjectType::Lc),
+            (0x67, AacObjectType::HeV1),
+            (0x68, AacObjectType::HeV2),
+            (0x00, AacObjectType::Lc),
+        ] {
+            assert_eq!(
+                create_adts_header(codec_id, 2, 44100, 100, false),
+                create_adts_header_for(object_type, 2, 44100, 100, false).unwrap()
+            );
+        }
+        // HE-AAC is signalled as LC
+        let header = create_adts_header(0x67, 2, 24000, 100, false);
+        assert_eq!(parse_adts_header(&header).unwrap().profile, 1);
+    }
+
+    #[test]
+    fn test_parse_adts_header_rejects_invalid_fields() {
+        let header = create_adts_header_for(AacObjectType::Lc, 2, 44100, 0, true).unwrap();
+        assert_eq!(parse_adts_header(&header[..8]), Err(AdtsError::Truncated));
+        assert_eq!(parse_adts_header(&[0xFF; 3]), Err(AdtsError::Truncated));
+
+        let mut reserved = header.clone();
+        reserved[2] |= 0x0F << 2;
+        assert_eq!(
+            parse_adts_header(&reserved),
+            Err(AdtsError::ReservedSamplingIndex(0x0F))
+        );
+        reserved[2] = (reserved[2] & 0xC3) | (0x0D << 2);
+        assert_eq!(
+            parse_adts_header(&reserved),
+            Err(AdtsError::ReservedSamplingIndex(0x0D))
+        );
+
+        let mut short = header.clone();
+        short[3] &= 0xFC;
+        short[4] = 0x01;
+        short[5] = 0x1F;
+        assert_eq!(
+            parse_adts_header(&short),
+            Err(AdtsError::FrameTooShort {
+                frame_length: 8,
+                header_length: 9,
+            })
+        );
+        assert!(!is_aac(&short));
+
+        let mut layer = header;
+        layer[1] |= 0x02;
+        assert_eq!(parse_adts_header(&layer), Err(AdtsError::InvalidLayer(1)));
+    }
+
+    // A mono raw data block: a silent SCE, a byte-aligned DSE carrying
+    // `data` from byte 4, a FIL element carrying `fill`, then END.
+    fn protected_block(data: &[u8], fill: &[u8]) -> Vec<u8> {
+        let mut bits = BitWriter::default();
+        // SCE: global_gain 100, then a long window with max_sfb 0 and no
+        // pulse, TNS or gain control data
+        bits.write(0, 3);
+        bits.write(0, 4);
+        bits.write(100, 8);
+        bits.write(0, 14);
+        // DSE at bit 29, aligned to byte 6
+        bits.write(4, 3);
+        bits.write(0, 4);
+        bits.write(1, 1);
+        bits.write(data.len() as u64, 8);
+        bits.write(0, 3);
+        for &byte in data {
+            bits.write(byte as u64, 8);
+        }
+        bits.write(6, 3);
+        if fill.len() < 15 {
+            bits.write(fill.len() as u64, 4);
+        } else {
+            bits.write(15, 4);
+            bits.write(fill.len() as u64 - 14, 8);
+        }
+        for &byte in fill {
+            bits.write(byte as u64, 8);
+        }
+        bits.write(7, 3);
+        bits.finish()
+    }
+
+    #[test]
+    fn test_adts_crc() {
+        // The CRC-16/CMS catalogue check value pins polynomial, initial
+        // value and bit order
+        assert_eq!(adts_crc(b"123456789"), 0xAEE7);
+
+        let payload = protected_block(b"data", &[0x77; 20]);
+        let frame = create_adts_header_with_crc(AacObjectType::Lc, 1, 44100, &payload).unwrap();
+        assert_eq!(frame.len(), 9 + payload.len());
+
+        // The header fields, the 22-bit channel stream padded to 192 bits,
+        // then the DSE from its element_instance_tag. Element IDs and the
+        // fill element are left out.
+        let protected = [&frame[..7], &[100], &[0; 23], &payload[4..10]].concat();
+        let crc = adts_crc(&protected);
+        assert_eq!(frame[7..9], crc.to_be_bytes());
+
+        let header = parse_adts_header(&frame).unwrap();
+        assert_eq!(header.crc, Some(crc));
+        assert!(header.verify_crc(&frame));
+        assert_eq!(check_crc(&frame), Some(true));
+        assert_eq!(
+            extract_aac_data(&Bytes::from(frame.clone())).unwrap(),
+            &payload[..]
+        );
+
+        // Damage to the fill element goes unseen, unlike damage to the
+        // header, the channel stream or the DSE
+        let mut fill = frame.clone();
+        fill[9 + 14] ^= 0x01;
+        assert_eq!(check_crc(&fill), Some(true));
+        for (at, bit) in [(5, 0x01), (9 + 1, 0x02), (9 + 8, 0x01)] {
+            let mut corrupt = frame.clone();
+            corrupt[at] ^= bit;
+            assert_eq!(check_crc(&corrupt), Some(false), "byte {}", at);
+            assert!(!verify_crc(&corrupt));
+        }
+        assert_eq!(check_crc(&frame[..16]), Some(false));
+
+        // Spectral data hides where the channel stream ends
+        let mut bits = BitWriter::default();
+        bits.write(0, 7);
+        bits.write(100, 8);
+        // A long window with max_sfb 1, coded with codebook 1
+        bits.write(2, 11);
+        bits.write(1, 4);
+        bits.write(1, 5);
+        bits.write(0, 64);
+        let spectral = bits.finish();
+        assert_eq!(
+            create_adts_header_with_crc(AacObjectType::Lc, 1, 44100, &spectral),
+            None
+        );
+        let mut frame =
+            create_adts_header_for(AacObjectType::Lc, 1, 44100, spectral.len(), true).unwrap();
+        frame.extend_from_slice(&spectral);
+        assert_eq!(check_crc(&frame), None);
+        assert!(verify_crc(&frame));
+
+        // Without protection there is nothing to check
+        let unprotected = create_adts_header_for(AacObjectType::Lc, 2, 44100, 0, false).unwrap();
+        assert_eq!(check_crc(&unprotected), None);
+        assert!(verify_crc(&unprotected));
+    }
+
+    #[test]
+    fn test_frame_iter_reports_crc_mismatch() {
+        let payload = protected_block(&[0x5A; 32], &[]);
+        let good = create_adts_header_with_crc(AacObjectType::Lc, 1, 32000, &payload).unwrap();
+        let mut bad = good.clone();
+        bad[20] ^= 0xFF;
+        let data = [&good[..], &bad, &good].concat();
+
+        let results: Vec<_> = AdtsFrameIter::new(&data)
+            .map(|frame| frame.map(|frame| frame.payload().len()))
+            .collect();
+        assert_eq!(
+            results,
+            [
+                Ok(payload.len()),
+                Err(AdtsError::CrcMismatch),
+                Ok(payload.len())
+            ]
+        );
+
+        // A CRC whose regions cannot be located is not held against the frame
+        let mut unknown = create_adts_header_for(AacObjectType::Lc, 2, 44100, 24, true).unwrap();
+        unknown.extend_from_slice(&[0x33; 24]);
+        assert!(AdtsFrameIter::new(&unknown).all(|frame| frame.is_ok()));
+    }
+
+    #[test]
+    fn test_extract_aac_blocks() {
+        let blocks = [
+            protected_block(b"a", b""),
+            protected_block(b"bc", b"x"),
+            protected_block(b"def", b""),
+        ];
+        assert_eq!(blocks.iter().map(Vec::len).collect::<Vec<_>>(), [9, 11, 11]);
+
+        // Three protected blocks: header, two block positions and the
+        // header CRC, then each block followed by its own CRC
+        let mut frame =
+            create_adts_header_for(AacObjectType::Lc, 1, 44100, 41, true).unwrap()[..7].to_vec();
+        frame[6] |= 0x02;
+        frame.extend_from_slice(&[0x00, 0x0B, 0x00, 0x18]);
+        frame.extend_from_slice(&adts_crc(&frame).to_be_bytes());
+        for block in &blocks {
+            frame.extend_from_slice(block);
+            let crc = protected_crc(0xFFFF, block).unwrap().unwrap();
+            frame.extend_from_slice(&crc.to_be_bytes());
+        }
+        assert_eq!(frame.len(), 50);
+
+        let header = parse_adts_header(&frame).unwrap();
+        assert_eq!(header.number_of_raw_data_blocks, 2);
+        assert_eq!(header.header_length(), 13);
+        assert!(verify_crc(&frame));
+        assert_eq!(
+            extract_aac_blocks(&Bytes::from(frame.clone())),
+            Ok(blocks
+                .iter()
+                .map(|block| Bytes::copy_from_slice(block))
+                .collect())
+        );
+
+        let mut corrupt = frame.clone();
+        corrupt[17] ^= 0x01;
+        assert!(!verify_crc(&corrupt));
+
+        let mut misplaced = frame.clone();
+        misplaced[10] = 0x40;
+        assert_eq!(
+            extract_aac_blocks(&Bytes::from(misplaced)),
+            Err(AdtsError::InvalidBlockPosition)
+        );
+
+        let mut unprotected =
+            create_adts_header_for(AacObjectType::Lc, 2, 44100, 12, false).unwrap();
+        unprotected[6] |= 0x01;
+        unprotected.extend_from_slice(&[0; 12]);
+        assert_eq!(
+            extract_aac_blocks(&Bytes::from(unprotected)),
+            Err(AdtsError::MissingBlockPositions)
+        );
+
+        // A single block is the same slice extract_aac_data returns
+        let single = Bytes::from(
+            create_adts_header_with_crc(AacObjectType::Lc, 1, 44100, &protected_block(b"", b""))
+                .unwrap(),
+        );
+        assert_eq!(
+            extract_aac_blocks(&single),
+            Ok(vec![extract_aac_data(&single).unwrap()])
+        );
+    }
+
+    fn adts_stream(frames: usize, sample_rate: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        for i in 0..frames {
+            data.extend(
+                create_adts_header_for(AacObjectType::Lc, 2, sample_rate, 100 + i, false).unwrap(),
+            );
+            data.extend(vec![0x11; 100 + i]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_frame_iter_counts_frames() {
+        let data = adts_stream(43, 44100);
+        let mut iter = AdtsFrameIter::new(&data);
+        let frames: Vec<AdtsFrame> = iter.by_ref().map(Result::unwrap).collect();
+
+        assert_eq!(frames.len(), 43);
+        assert_eq!(iter.remaining(), 0);
+        assert_eq!(frames[5].payload(), &[0x11; 105][..]);
+        let samples: usize = frames
+            .iter()
+            .map(|frame| 1024 * (frame.header.number_of_raw_data_blocks as usize + 1))
+            .sum();
+        assert_eq!(samples * 1000 / 44100, 998);
+    }
+
+    #[test]
+    fn test_stream_info() {
+        // 43 frames of 107 to 149 bytes
+        let data = adts_stream(43, 44100);
+        let info = stream_info(&data).unwrap();
+        assert_eq!(info.frames, 43);
+        assert_eq!(info.samples, 43 * 1024);
+        assert_eq!(info.sample_rate, 44100);
+        let expected = Duration::from_secs_f64(43.0 * 1024.0 / 44100.0);
+        assert!(info.duration.abs_diff(expected) < Duration::from_micros(1));
+        assert_eq!(
+            (
+                info.min_frame_size,
+                info.avg_frame_size,
+                info.max_frame_size
+            ),
+            (107, 128, 149)
+        );
+        assert_eq!(
+            info.bitrate,
+            (data.len() as f64 * 8.0 / expected.as_secs_f64()).round() as u32
+        );
+        assert!(!info.is_cbr && !info.estimated);
+
+        assert_eq!(stream_info(&data[..100]), None);
+    }
+
+    #[test]
+    fn test_stream_info_extrapolates_past_budget() {
+        let mut data = Vec::new();
+        for _ in 0..1000 {
+            data.extend(create_adts_header_for(AacObjectType::Lc, 2, 48000, 200, false).unwrap());
+            data.extend([0x5A; 200]);
+        }
+        let exact = stream_info(&data).unwrap();
+        let estimate = stream_info_with_budget(&data, 50).unwrap();
+
+        assert!(estimate.estimated);
+        assert_eq!(estimate.frames, 1000);
+        let frame = Duration::from_secs_f64(1024.0 / 48000.0);
+        assert!(estimate.duration.abs_diff(exact.duration) <= frame);
+        assert_eq!(estimate.bitrate, exact.bitrate);
+
+        assert!(!stream_info_with_budget(&data, 1000).unwrap().estimated);
+    }
+
+    #[test]
+    fn test_to_access_units() {
+        let data = adts_stream(43, 44100);
+        let units = to_access_units(&data, 1000).unwrap();
+        assert_eq!(units.len(), 43);
+        assert!(units.iter().all(|unit| unit.key && unit.pts == unit.dts));
+        assert!(units.iter().all(|unit| unit.stream_type == 0x0F));
+        assert!(units.windows(2).all(|pair| pair[0].pts < pair[1].pts));
+        assert_eq!(units[1].pts, 1000 + 2090);
+        assert_eq!(units[3].data, vec![0x11; 103]);
+
+        // The last unit ends where stream_info says the stream does
+        let info = stream_info(&data).unwrap();
+        let end = units[42].pts + Ticks90k::from_samples(1024, 44100).0 - 1000;
+        assert!(Ticks90k(end).to_duration().abs_diff(info.duration) < Duration::from_micros(12));
+
+        let framed: Vec<AccessUnit> = AdtsAccessUnitIter::new(&data, 0)
+            .keep_header(true)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            framed.iter().map(|unit| unit.data.len()).sum::<usize>(),
+            data.len()
+        );
+
+        assert_eq!(
+            to_access_units(&data[..data.len() - 1], 0).map(|units| units.len()),
+            Err(AdtsError::FrameLengthExceedsInput {
+                frame_length: 149,
+                available: 148,
+            })
+        );
+    }
+
+    #[test]
+    fn test_access_units_restart_ticks_on_rate_change() {
+        let mut data = adts_stream(3, 44100);
+        data.extend(adts_stream(3, 48000));
+        let pts: Vec<u64> = to_access_units(&data, 0)
+            .unwrap()
+            .iter()
+            .map(|unit| unit.pts)
+            .collect();
+        // 1024 samples are 2089.8 ticks at 44.1 kHz and 1920 at 48 kHz
+        assert_eq!(pts, [0, 2090, 4180, 6269, 8189, 10109]);
+    }
+
+    #[test]
+    fn test_strip_adts_round_trip() {
+        let data = Bytes::from(adts_stream(10, 22050));
+        let (config, blocks) = strip_adts(&data).unwrap();
+        assert_eq!(config.to_bytes(), [0x13, 0x90]);
+        assert_eq!(blocks.len(), 10);
+        assert_eq!(blocks[4], vec![0x11; 104]);
+        // Slices of the input, not copies
+        assert_eq!(blocks[0].as_ptr(), data[7..].as_ptr());
+
+        let fallback = AdtsParams {
+            object_type: 2,
+            sample_rate: 44100,
+            channels: 1,
+        };
+        let rebuilt: Vec<u8> = blocks
+            .into_iter()
+            .flat_map(|block| {
+                ensure_adts(block, Some(&config), &fallback)
+                    .unwrap()
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(rebuilt, data);
+
+        let mut mixed = adts_stream(2, 22050);
+        mixed.extend(adts_stream(1, 44100));
+        assert_eq!(
+            strip_adts(&Bytes::from(mixed)),
+            Err(AdtsError::SampleRateChanged {
+                expected: 22050,
+                found: 44100,
+            })
+        );
+        assert_eq!(
+            strip_adts(&data.slice(..data.len() - 1)).map(|_| ()),
+            Err(AdtsError::FrameLengthExceedsInput {
+                frame_length: 116,
+                available: 115
+            })
+        );
+        assert_eq!(strip_adts(&Bytes::new()), Err(AdtsError::Truncated));
+    }
+
+    // A long-window individual_channel_stream whose `max_sfb` bands use
+    // the zero codebook
+    fn write_silent_ics(bits: &mut BitWriter, max_sfb: u64, with_info: bool) {
+        bits.write(100, 8);
+        if with_info {
+            write_ics_info(bits, max_sfb);
+        }
+        if max_sfb > 0 {
+            bits.write(ZERO_HCB as u64, 4);
+            bits.write(max_sfb, 5);
+        }
+        bits.write(0, 3);
+    }
+
+    fn write_ics_info(bits: &mut BitWriter, max_sfb: u64) {
+        bits.write(0, 4);
+        bits.write(max_sfb, 6);
+        bits.write(0, 1);
+    }
+
+    // A silent SCE, or a CPE sharing one window, then a fill element of
+    // `extension_type`
+    fn sbr_frame(pair: bool, max_sfb: u64, extension_type: u64) -> Bytes {
+        let mut bits = BitWriter::default();
+        if pair {
+            bits.write(1, 3);
+            bits.write(0, 4);
+            bits.write(1, 1);
+            write_ics_info(&mut bits, max_sfb);
+            bits.write(0, 2);
+            write_silent_ics(&mut bits, max_sfb, false);
+            write_silent_ics(&mut bits, max_sfb, false);
+        } else {
+            bits.write(0, 3);
+            bits.write(0, 4);
+            write_silent_ics(&mut bits, max_sfb, true);
+        }
+        bits.write(6, 3);
+        bits.write(3, 4);
+        bits.write(extension_type, 4);
+        bits.write(0, 20);
+        bits.write(7, 3);
+        let block = bits.finish();
+
+        let mut frame =
+            create_adts_header_for(AacObjectType::Lc, 1 + pair as u8, 22050, block.len(), false)
+                .unwrap();
+        frame.extend(block);
+        Bytes::from(frame)
+    }
+
+    #[test]
+    fn test_detect_sbr() {
+        assert_eq!(
+            detect_sbr(&[sbr_frame(false, 0, EXT_SBR_DATA as u64)]),
+            SbrSignal::Present {
+                ps: None,
+                output_sample_rate: 44100,
+            }
+        );
+        assert_eq!(
+            detect_sbr(&[sbr_frame(true, 4, EXT_SBR_DATA_CRC as u64)]),
+            SbrSignal::Present {
+                ps: Some(false),
+                output_sample_rate: 44100,
+            }
+        );
+        // EXT_FILL
+        assert_eq!(detect_sbr(&[sbr_frame(false, 20, 0)]), SbrSignal::Absent);
+
+        // An escaped fill count of 15 + 0 - 1 bytes, cut short here
+        let frame =
+            Bytes::from_static(&[0xFF, 0xF1, 0x50, 0x80, 0x01, 0x5F, 0xFC, 0xDE, 0x00, 0x00]);
+        assert_eq!(detect_sbr(&[frame]), SbrSignal::Unknown);
+        let mut bits = BitWriter::default();
+        bits.write(6, 3);
+        bits.write(15, 4);
+        bits.write(0, 8);
+        for _ in 0..14 {
+            bits.write(0, 8);
+        }
+        bits.write(7, 3);
+        let block = bits.finish();
+        let mut frame =
+            create_adts_header_for(AacObjectType::Lc, 1, 22050, block.len(), false).unwrap();
+        frame.extend(block);
+        assert_eq!(detect_sbr(&[Bytes::from(frame)]), SbrSignal::Absent);
+
+        // A frame with spectral data cannot be walked, but a later silent
+        // one can
+        let loud = Bytes::from(adts_stream(1, 22050));
+        assert_eq!(detect_sbr(std::slice::from_ref(&loud)), SbrSignal::Unknown);
+        assert_eq!(
+            detect_sbr(&[loud, sbr_frame(true, 0, EXT_SBR_DATA as u64)]),
+            SbrSignal::Present {
+                ps: Some(false),
+                output_sample_rate: 44100,
+            }
+        );
+        assert_eq!(detect_sbr(&[]), SbrSignal::Unknown);
+    }
+
+    #[test]
+    fn test_is_aac_needs_a_second_frame() {
+        let mut frame = create_adts_header_for(AacObjectType::Lc, 2, 44100, 9, false).unwrap();
+        frame.extend([0x11; 9]);
+        let two = [&frame[..], &frame[..]].concat();
+        assert!(is_aac(&frame));
+        assert!(is_aac(&two));
+
+        // Running past the buffer, or into a partial or mismatched header
+        assert!(!is_aac(&frame[..10]));
+        assert!(is_aac_single_frame(&frame[..10]));
+        assert!(!is_aac(&two[..frame.len() + 3]));
+        let mut mono = frame.clone();
+        mono.extend(create_adts_header_for(AacObjectType::Lc, 1, 44100, 0, false).unwrap());
+        assert!(!is_aac(&mono));
+
+        // An 0xFFF1 pair inside MP3 data, followed by the next MP3 frame
+        let mut mp3 = frame[..7].to_vec();
+        mp3.extend([0x55; 9]);
+        mp3.extend([0xFF, 0xFB, 0x90, 0x64]);
+        mp3.extend([0; 32]);
+        assert!(is_aac_single_frame(&mp3));
+        assert!(!is_aac(&mp3));
+        assert_eq!(crate::detect_audio(&mp3), crate::AudioType::Unknown);
+    }
+
+    #[test]
+    fn test_frame_iter_recovers_from_lost_bytes() {
+        let data = adts_stream(20, 44100);
+        let frame_10 = (0..10).map(|i| 107 + i).sum::<usize>();
+        assert_eq!(
+            find_adts_frame(&data, 1),
+            Some((107, parse_adts_header(&data[107..]).unwrap()))
+        );
+
+        // A byte lost in the middle of frame 10, and frame 10's length
+        // damaged
+        let mut lost = data.clone();
+        lost.remove(frame_10 + 60);
+        let mut damaged = data.clone();
+        damaged[frame_10 + 4] ^= 0x10;
+        for corrupt in [lost, damaged] {
+            let mut iter = AdtsFrameIter::new(&corrupt);
+            let results: Vec<_> = iter
+                .by_ref()
+                .map(|frame| frame.map(|frame| frame.payload().len()))
+                .collect();
+            assert_eq!(results.len(), 20);
+            assert_eq!(results[10], Err(AdtsError::OverlapsNextFrame));
+            assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 19);
+            assert_eq!(results[11], Ok(111));
+            assert_eq!(iter.skipped(), 117 + corrupt.len() - data.len());
+            assert_eq!(iter.remaining(), 0);
+        }
+
+        // Without a sync inside it, a frame followed by junk is kept: here
+        // the last frame, before an ID3v1 tag
+        let mut tagged = data.clone();
+        tagged.extend(b"TAG");
+        tagged.resize(data.len() + 128, 0);
+        let mut iter = AdtsFrameIter::new(&tagged);
+        assert_eq!(iter.by_ref().filter(Result::is_ok).count(), 20);
+        assert_eq!(iter.skipped(), 128);
+    }
+
+    #[test]
+    fn test_frame_iter_resyncs_and_stops_on_partial_frame() {
+        let mut data = adts_stream(2, 48000);
+        let second = data.len() - 108;
+        // Junk between the frames, starting with a false sync
+        data.splice(second..second, [0xFF, 0xF2, 0x00, 0x12, 0x34]);
+        data.extend(&adts_stream(1, 48000)[..50]);
+
+        let mut iter = AdtsFrameIter::new(&data);
+        assert!(iter.next().unwrap().is_ok());
+        assert_eq!(iter.next(), Some(Err(AdtsError::InvalidLayer(1))));
+        assert_eq!(iter.next().unwrap().unwrap().data.len(), 108);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.remaining(), 50);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_parse_audio_specific_config() {
+        let lc = parse_audio_specific_config(&[0x12, 0x10]).unwrap();
+        assert_eq!(
+            lc,
+            AudioSpecificConfig {
+                audio_object_type: 2,
+                sampling_frequency_index: 4,
+                sampling_frequency: 44100,
+                channel_configuration: 2,
+                frame_length_flag: false,
+                sbr_present: false,
+                ps_present: false,
+                extension_sampling_frequency: None,
+                program: None,
+            }
+        );
+        assert_eq!(
+            (lc.sample_rate(), lc.channels(), lc.samples_per_frame()),
+            (44100, 2, 1024)
+        );
+
+        // Explicit SBR: object type 5 at 24 kHz, extension to 48 kHz, then LC
+        let he = parse_audio_specific_config(&[0x2B, 0x11, 0x88, 0x00]).unwrap();
+        assert_eq!(he.audio_object_type, 2);
+        assert_eq!(he.sampling_frequency, 24000);
+        assert!(he.sbr_present && !he.ps_present);
+        assert_eq!(
+            (he.sample_rate(), he.channels(), he.samples_per_frame()),
+            (48000, 2, 2048)
+        );
+
+        // Backward compatible PS over mono LC at 22.05 kHz, extended to 44.1 kHz
+        let ps = parse_audio_specific_config(&[0x13, 0x88, 0x56, 0xE5, 0xA5, 0x48, 0x80]).unwrap();
+        assert!(ps.sbr_present && ps.ps_present);
+        assert_eq!((ps.sample_rate(), ps.channels()), (44100, 2));
+
+        // 7350 Hz coded with the 24-bit escape, mono, 960-sample frames
+        let escaped = parse_audio_specific_config(&[0x17, 0x80, 0x0E, 0x5B, 0x0C]).unwrap();
+        assert_eq!(escaped.sampling_frequency_index, 0xF);
+        assert_eq!(
+            (
+                escaped.sample_rate(),
+                escaped.channels(),
+                escaped.samples_per_frame()
+            ),
+            (7350, 1, 960)
+        );
+
+        // Escape-coded object type 32 + 10 = 42, USAC
+        assert_eq!(
+            parse_audio_specific_config(&[0xF9, 0x48, 0x40]),
+            Err(AscError::UnsupportedObjectType(42))
+        );
+        assert_eq!(
+            parse_audio_specific_config(&[0x16, 0x90]),
+            Err(AscError::ReservedSamplingIndex(0xD))
+        );
+        assert_eq!(
+            parse_audio_specific_config(&[0x12]),
+            Err(AscError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_parse_low_delay_audio_specific_config() {
+        // ELD, escape-coded object type 32 + 7, 48 kHz stereo, 480-sample
+        // frames
+        let eld = parse_audio_specific_config(&[0xF8, 0xE6, 0x50, 0x00]).unwrap();
+        assert_eq!(
+            eld.audio_object_type,
+            AacObjectType::Eld.audio_object_type()
+        );
+        assert!(eld.frame_length_flag && !eld.sbr_present);
+        assert_eq!(
+            (eld.sample_rate(), eld.channels(), eld.samples_per_frame()),
+            (48000, 2, 480)
+        );
+        assert_eq!(eld.to_bytes(), [0xF8, 0xE6, 0x50, 0x00]);
+
+        // ELD at 24 kHz with dual rate low delay SBR and one SBR header
+        let eld_sbr =
+            parse_audio_specific_config(&[0xF8, 0xEC, 0x51, 0xAB, 0x20, 0xAC, 0x00]).unwrap();
+        assert!(eld_sbr.sbr_present && !eld_sbr.ps_present);
+        assert_eq!(
+            (
+                eld_sbr.sample_rate(),
+                eld_sbr.channels(),
+                eld_sbr.samples_per_frame()
+            ),
+            (48000, 2, 960)
+        );
+
+        // LD at 48 kHz, mono, 480-sample frames
+        let ld = parse_audio_specific_config(&[0xB9, 0x8D, 0x00]).unwrap();
+        assert_eq!(ld.audio_object_type, AacObjectType::Ld.audio_object_type());
+        assert_eq!(
+            (ld.sample_rate(), ld.channels(), ld.samples_per_frame()),
+            (48000, 1, 480)
+        );
+        assert_eq!(ld.to_bytes(), [0xB9, 0x8D, 0x00]);
+    }
+
+    #[test]
+    fn test_codec_string() {
+        for (asc, expected) in [
+            (&[0x12, 0x10][..], "mp4a.40.2"),
+            (&[0x2B, 0x11, 0x88, 0x00], "mp4a.40.5"),
+            (&[0x13, 0x88, 0x56, 0xE5, 0xA5, 0x48, 0x80], "mp4a.40.29"),
+            (&[0xB9, 0x8D, 0x00], "mp4a.40.23"),
+            (&[0xF8, 0xEC, 0x51, 0xAB, 0x20, 0xAC, 0x00], "mp4a.40.39"),
+            // HE-AAC with implicit SBR looks like LC at the core rate
+            (&[0x13, 0x10], "mp4a.40.2"),
+        ] {
+            let config = parse_audio_specific_config(asc).unwrap();
+            assert_eq!(codec_string(&config), expected);
+        }
+
+        let mpeg4 = create_adts_header_for(AacObjectType::HeV2, 1, 24000, 0, false).unwrap();
+        assert_eq!(
+            parse_adts_header(&mpeg4).unwrap().codec_string(),
+            "mp4a.40.2"
+        );
+        for (object_type, expected) in [
+            (AacObjectType::Main, "mp4a.66"),
+            (AacObjectType::Lc, "mp4a.67"),
+            (AacObjectType::Ssr, "mp4a.68"),
+        ] {
+            let mpeg2 = create_adts_header_with_version(
+                MpegVersion::Mpeg2,
+                object_type,
+                2,
+                48000,
+                0,
+                false,
+            )
+            .unwrap();
+            assert_eq!(parse_adts_header(&mpeg2).unwrap().codec_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_build_audio_specific_config() {
+        assert_eq!(build_audio_specific_config(2, 44100, 2), [0x12, 0x10]);
+        assert_eq!(build_audio_specific_config(2, 48000, 8), [0x11, 0xB8]);
+
+        // 31 is the escape, and the escaped type tops out at 95
+        assert_eq!(try_build_audio_specific_config(31, 44100, 2), None);
+        assert_eq!(try_build_audio_specific_config(96, 44100, 2), None);
+        assert_eq!(
+            try_build_audio_specific_config(2, 44100, 2),
+            Some(build_audio_specific_config(2, 44100, 2))
+        );
+
+        let table_rates = (0..=12).filter_map(sample_rate_from_index);
+        for sample_rate in table_rates.chain([37800]) {
+            for channels in 1..=8 {
+                let asc = build_audio_specific_config(2, sample_rate, channels);
+                assert_eq!(asc.len(), if sample_rate == 37800 { 5 } else { 2 });
+
+                let config = parse_audio_specific_config(&asc).unwrap();
+                assert_eq!(config.audio_object_type, 2);
+                assert_eq!(config.sample_rate(), sample_rate);
+                assert_eq!(config.channels(), channels);
+                assert_eq!(config.samples_per_frame(), 1024);
+            }
+        }
+
+        let frame = create_adts_header_for(AacObjectType::Lc, 2, 7350, 10, false).unwrap();
+        let header = parse_adts_header(&frame).unwrap();
+        let config = parse_audio_specific_config(&header.to_asc()).unwrap();
+        assert_eq!(config.sampling_frequency_index, 0xC);
+        assert_eq!((config.sample_rate(), config.channels()), (7350, 2));
+    }
+
+    #[test]
+    fn test_write_escaped_object_type() {
+        assert_eq!(
+            build_audio_specific_config(32, 44100, 2),
+            [0xF8, 0x08, 0x40]
+        );
+
+        // 30 still fits the five-bit field, 32 and up take the escape
+        for object_type in (1..=30).chain(32..=95) {
+            let asc = build_audio_specific_config(object_type, 44100, 2);
+            assert_eq!(asc[0] >> 3 == 31, object_type > 31);
+            let mut reader = BitReader::new(&asc);
+            assert_eq!(read_audio_object_type(&mut reader), Ok(object_type));
+        }
+    }
+
+    #[test]
+    fn test_parse_adif_header() {
+        let header = [
+            b'A', b'D', b'I', b'F',
+            // No copyright id, VBR at 128 kbit/s peak, one PCE: LC at
+            // 44.1 kHz with one front CPE and one LFE, no comment
+            0x10, 0x3E, 0x80, 0x00, 0x0A, 0x08, 0x02, 0x00, 0x40, 0x00, 0x00,
+        ];
+        assert!(is_adif(&header));
+        assert_eq!(
+            parse_adif_header(&header),
+            Some(AdifHeader {
+                bitrate: 128_000,
+                is_cbr: false,
+                program: ProgramConfig {
+                    profile: 1,
+                    sample_rate: 44100,
+                    channels: 3,
+                },
+            })
+        );
+
+        // Truncated before the comment field
+        assert_eq!(parse_adif_header(&header[..header.len() - 1]), None);
+        assert!(!is_aac(&header));
+    }
+
+    #[test]
+    fn test_loas_frames() {
+        let mut data = vec![0x56, 0xE0, 0x04, 0x20, 0x00, 0x10, 0x30];
+        assert_eq!(loas_frame_length(&data), Some(7));
+        assert!(!is_loas(&data));
+
+        data.extend_from_slice(&[0x56, 0xE0, 0x02, 0x20, 0x00]);
+        assert!(is_loas(&data));
+
+        // The length must land on the next sync
+        data[2] = 0x05;
+        assert!(!is_loas(&data));
+        assert_eq!(loas_frame_length(&[0x56, 0xC0, 0x04]), None);
+        assert!(!is_loas(&[0xFF, 0xFB, 0x90, 0x64, 0x00]));
+    }
+
+    #[test]
+    fn test_adts_to_loas() {
+        let payload = [0xAB, 0xCD];
+        let frame = create_adts_header_for(AacObjectType::Lc, 2, 44100, 2, false).unwrap();
+        let frame = [&frame[..], &payload].concat();
+
+        let loas = adts_to_loas([&frame[..], &frame[..]].into_iter());
+        // useSameStreamMux 0, audioMuxVersion 0, allStreamsSameTimeFraming,
+        // no extra subframes, programs or layers, ASC 0x1210, frameLengthType
+        // 0, buffer fullness 0xFF, no other data or CRC, length 2, payload
+        assert_eq!(
+            loas[0],
+            &[0x56, 0xE0, 0x09, 0x20, 0x00, 0x12, 0x10, 0x1F, 0xE0, 0x15, 0x5E, 0x68][..]
+        );
+        // useSameStreamMux 1, length 2, payload
+        assert_eq!(loas[1], &[0x56, 0xE0, 0x04, 0x81, 0x55, 0xE6, 0x80][..]);
+
+        let config = StreamMuxConfig::new(parse_audio_specific_config(&[0x12, 0x10]).unwrap());
+        assert_eq!(wrap_loas(&payload, &config).unwrap(), loas[0]);
+    }
+
+    #[test]
+    fn test_adts_to_loas_repeats_config() {
+        let mut frames = Vec::new();
+        for i in 0..45 {
+            let sample_rate = if i < 30 { 44100 } else { 48000 };
+            let mut frame =
+                create_adts_header_for(AacObjectType::Lc, 2, sample_rate, 300, false).unwrap();
+            frame.extend(vec![i as u8; 300]);
+            frames.push(frame);
+        }
+
+        let loas = adts_to_loas(frames.iter().map(Vec::as_slice));
+        assert_eq!(loas.len(), 45);
+        let with_config: Vec<usize> = (0..45).filter(|&i| loas[i][3] & 0x80 == 0).collect();
+        assert_eq!(with_config, [0, 20, 30]);
+
+        let stream = loas.concat();
+        assert!(is_loas(&stream));
+        for frame in &loas {
+            assert_eq!(loas_frame_length(frame), Some(frame.len()));
+        }
+
+        // useSameStreamMux 1, then 300 as the two PayloadLengthInfo bytes
+        // 255 and 45
+        assert_eq!(loas[1][3..6], [0xFF, 0x96, 0x80]);
+    }
+
+    #[test]
+    fn test_parse_loas_frames() {
+        let mut frames = Vec::new();
+        for i in 0..3 {
+            let mut frame =
+                create_adts_header_for(AacObjectType::Lc, 2, 48000, 300 + i, false).unwrap();
+            frame.extend(vec![i as u8; 300 + i]);
+            frames.push(frame);
+        }
+        let loas = adts_to_loas(frames.iter().map(Vec::as_slice));
+
+        let first = parse_loas_frame(&loas[0]).unwrap();
+        assert_eq!(first.payload, vec![0; 300]);
+        let asc = first.config.audio_specific_config;
+        assert_eq!((asc.sample_rate(), asc.channels()), (48000, 2));
+        assert_eq!(
+            parse_loas_frame(&loas[1]),
+            Err(LatmError::MissingStreamMuxConfig)
+        );
+
+        let mut parser = LatmParser::new();
+        for (i, frame) in loas.iter().enumerate() {
+            let parsed = parser.parse(frame).unwrap();
+            assert_eq!(parsed.payload, vec![i as u8; 300 + i]);
+            assert_eq!(parsed.config, first.config);
+        }
+
+        assert_eq!(
+            parse_loas_frame(&loas[0][..loas[0].len() - 1]),
+            Err(LatmError::Truncated)
+        );
+        assert_eq!(
+            parse_loas_frame(&frames[0]),
+            Err(LatmError::InvalidSyncWord)
+        );
+    }
+
+    #[test]
+    fn test_parse_loas_version_1_and_layers() {
+        let latm = |version: u64, layers: u64| {
+            let mut writer = BitWriter::default();
+            writer.write(0, 1);
+            writer.write(version, 1);
+            if version == 1 {
+                // audioMuxVersionA, then taraBufferFullness as a one-byte value
+                writer.write(0, 1);
+                writer.write(0, 2);
+                writer.write(0xFF, 8);
+            }
+            writer.write(1, 1);
+            writer.write(0, 6);
+            writer.write(0, 4);
+            writer.write(layers, 3);
+            if version == 1 {
+                // ascLen of 24 bits: 0x1210 and eight fill bits
+                writer.write(0, 2);
+                writer.write(24, 8);
+                writer.write(0x1210FF, 24);
+            } else {
+                writer.write(0x1210, 16);
+            }
+            writer.write(0, 3);
+            writer.write(0xFF, 8);
+            writer.write(0, 2);
+            writer.write(3, 8);
+            writer.write(0xABCDEF, 24);
+            let element = writer.finish();
+
+            let mut frame = vec![0x56, 0xE0, element.len() as u8];
+            frame.extend(element);
+            frame
+        };
+
+        let frame = parse_loas_frame(&latm(1, 0)).unwrap();
+        assert_eq!(frame.payload, &[0xAB, 0xCD, 0xEF][..]);
+        assert_eq!(frame.config.audio_specific_config.sample_rate(), 44100);
+        assert_eq!(
+            parse_loas_frame(&latm(0, 0)).unwrap().payload,
+            frame.payload
+        );
+
+        assert_eq!(
+            parse_loas_frame(&latm(0, 1)),
+            Err(LatmError::MultipleProgramsOrLayers {
+                programs: 1,
+                layers: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_try_create_adts_header() {
+        for object_type in [
+            AacObjectType::Main,
+            AacObjectType::Lc,
+            AacObjectType::Ltp,
+            AacObjectType::HeV2,
+        ] {
+            for channel_config in 1..=7 {
+                for sample_rate in (0..=12).filter_map(sample_rate_from_index) {
+                    for (length, has_crc) in [(0, false), (200, true), (8184, false)] {
+                        assert_eq!(
+                            try_create_adts_header(
+                                object_type,
+                                channel_config,
+                                sample_rate,
+                                length,
+                                has_crc
+                            ),
+                            create_adts_header_for(
+                                object_type,
+                                channel_config,
+                                sample_rate,
+                                length,
+                                has_crc
+                            )
+                        );
+                    }
+                }
+            }
+        }
+
+        assert_eq!(
+            try_create_adts_header(AacObjectType::Lc, 2, 44000, 100, false),
+            Err(AdtsError::UnsupportedSampleRate(44000))
+        );
+        assert_eq!(
+            try_create_adts_header(AacObjectType::Lc, 8, 48000, 100, false),
+            Err(AdtsError::UnsupportedChannelConfiguration(8))
+        );
+        assert_eq!(
+            try_create_adts_header(AacObjectType::Lc, 0, 48000, 100, false),
+            Err(AdtsError::UnsupportedChannelConfiguration(0))
+        );
+        assert_eq!(
+            try_create_adts_header(AacObjectType::Lc, 2, 48000, 8183, true),
+            Err(AdtsError::FrameTooLong(8192))
+        );
+        assert_eq!(
+            try_create_adts_header(AacObjectType::Eld, 2, 48000, 100, false),
+            Err(AdtsError::UnsupportedObjectType(39))
+        );
+
+        // The lenient writer still has no profile for the low delay types
+        assert_eq!(
+            create_adts_header_for(AacObjectType::Ld, 2, 48000, 100, false),
+            Err(AdtsError::UnsupportedObjectType(23))
+        );
+        assert_eq!(
+            create_adts_header_with_version(
+                MpegVersion::Mpeg2,
+                AacObjectType::Eld,
+                2,
+                48000,
+                100,
+                false
+            ),
+            Err(AdtsError::UnsupportedObjectType(39))
+        );
+        let payload = [0u8; 4];
+        assert_eq!(
+            create_adts_header_with_crc(AacObjectType::Eld, 2, 48000, &payload),
+            None
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_write_adts_header_matches_allocating_writers() {
+        let object_types = [
+            AacObjectType::Main,
+            AacObjectType::Lc,
+            AacObjectType::Ssr,
+            AacObjectType::Ltp,
+            AacObjectType::HeV1,
+            AacObjectType::HeV2,
+            AacObjectType::Ld,
+            AacObjectType::Eld,
+        ];
+        let mut buf = BytesMut::with_capacity(9);
+        for object_type in object_types {
+            for sample_rate in (0..=12).filter_map(sample_rate_from_index).chain([44000]) {
+                for channels in 0..=8 {
+                    for payload_len in [0, 1, 200, 2047, 8184] {
+                        for crc in [false, true] {
+                            let params = AdtsParams {
+                                object_type: object_type.audio_object_type(),
+                                sample_rate,
+                                channels,
+                            };
+                            buf.clear();
+                            let written = write_adts_header(&mut buf, &params, payload_len, crc)
+                                .map(|()| buf.to_vec());
+                            let expected = create_adts_header_for(
+                                object_type,
+                                channels,
+                                sample_rate,
+                                payload_len,
+                                crc,
+                            );
+                            assert_eq!(written, expected);
+                        }
+                    }
+                }
+            }
+        }
+
+        // The FLV codec ids of create_adts_header
+        for (codec_id, object_type) in [(0x66, 2), (0x67, 5), (0x68, 29)] {
+            for channels in 1..=8 {
+                let params = AdtsParams {
+                    object_type,
+                    sample_rate: 44100,
+                    channels,
+                };
+                buf.clear();
+                write_adts_header(&mut buf, &params, 10, false).unwrap();
+                assert_eq!(
+                    buf[..],
+                    create_adts_header(codec_id, channels, 44100, 10, false)[..]
+                );
+            }
+        }
+
+        // Seven channels are written as configuration 7, as before
+        buf.clear();
+        let params = AdtsParams {
+            object_type: 2,
+            sample_rate: 48000,
+            channels: 7,
+        };
+        write_adts_header(&mut buf, &params, 100, false).unwrap();
+        assert_eq!(buf[..], [0xFF, 0xF1, 0x4D, 0xC0, 0x0D, 0x7F, 0xFC]);
+
+        let params = AdtsParams {
+            object_type: 6,
+            sample_rate: 44100,
+            channels: 2,
+        };
+        assert_eq!(
+            write_adts_header(&mut buf, &params, 10, false),
+            Err(AdtsError::UnsupportedObjectType(6))
+        );
+
+        // The deprecated shim still writes what create_adts_header_for did
+        for (asc_byte, object_type) in [
+            (0x10, AacObjectType::HeV1),
+            (0x28, AacObjectType::HeV2),
+            (0x08, AacObjectType::Lc),
+        ] {
+            for sample_rate in [8000, 44100, 96000] {
+                for channels in 1..=8 {
+                    let data = Bytes::from(vec![asc_byte; 40]);
+                    let mut expected =
+                        create_adts_header_for(object_type, channels, sample_rate, 38, false)
+                            .unwrap();
+                    expected.extend_from_slice(&data[2..]);
+                    assert_eq!(ensure_adts_header(data, channels, sample_rate), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ensure_adts() {
+        let raw = Bytes::from_static(&[0x21, 0x10, 0x05, 0x40, 0x5A, 0xA5, 0x00, 0x80, 0x1C]);
+        let lc = AdtsParams {
+            object_type: 2,
+            sample_rate: 44100,
+            channels: 2,
+        };
+
+        // The header ffmpeg's ADTS muxer writes for this frame
+        let expected = [&[0xFF, 0xF1, 0x50, 0x80, 0x02, 0x1F, 0xFC][..], &raw[..]].concat();
+        let framed = ensure_adts(raw.clone(), None, &lc).unwrap();
+        assert_eq!(framed, expected);
+
+        let asc = parse_audio_specific_config(&[0x12, 0x10]).unwrap();
+        assert_eq!(ensure_adts(raw.clone(), Some(&asc), &lc).unwrap(), expected);
+
+        // Already framed data comes back as is
+        assert_eq!(ensure_adts(framed.clone(), None, &lc).unwrap(), framed);
+
+        // HE-AAC is framed as its LC core
+        let he = parse_audio_specific_config(&[0x2B, 0x11, 0x88, 0x00]).unwrap();
+        let framed = ensure_adts(raw.clone(), Some(&he), &lc).unwrap();
+        let header = parse_adts_header(&framed).unwrap();
+        assert_eq!((header.profile, header.sample_rate), (1, 24000));
+
+        assert_eq!(
+            ensure_adts(Bytes::new(), None, &lc),
+            Err(AdtsError::Truncated)
+        );
+        assert_eq!(
+            ensure_adts(Bytes::from_static(&[0x12]), None, &lc),
+            Err(AdtsError::Truncated)
+        );
+        let odd_rate = AdtsParams {
+            sample_rate: 37800,
+            ..lc
+        };
+        assert_eq!(
+            ensure_adts(raw.clone(), None, &odd_rate),
+            Err(AdtsError::UnsupportedSampleRate(37800))
+        );
+        let seven = AdtsParams { channels: 7, ..lc };
+        assert_eq!(
+            ensure_adts(raw, None, &seven),
+            Err(AdtsError::UnsupportedChannelConfiguration(11))
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_adversarial_lengths_do_not_panic() {
+        // frame_length of 3 is smaller than the 7-byte header it belongs to
+        let mut header = create_adts_header_for(AacObjectType::Lc, 2, 44100, 0, false).unwrap();
+        header[3] &= 0xFC;
+        header[4] = 0x00;
+        header[5] = (3 << 5) | 0x1F;
+        assert_eq!(extract_aac_data(&Bytes::from(header.clone())), None);
+        assert_eq!(
+            extract_aac_data_checked(&Bytes::from(header)),
+            Err(AdtsError::FrameTooShort {
+                frame_length: 3,
+                header_length: 7,
+            })
+        );
+
+        // frame_length at the 13-bit maximum with almost no data behind it
+        let mut header = create_adts_header_for(AacObjectType::Lc, 2, 44100, 0, false).unwrap();
+        header[3] |= 0x03;
+        header[4] = 0xFF;
+        header[5] |= 0xE0;
+        assert_eq!(extract_aac_data(&Bytes::from(header.clone())), None);
+        assert_eq!(
+            extract_aac_data_checked(&Bytes::from(header)),
+            Err(AdtsError::FrameLengthExceedsInput {
+                frame_length: 8191,
+                available: 7,
+            })
+        );
+
+        assert!(ensure_adts_header(Bytes::new(), 2, 44100).is_empty());
+        assert_eq!(
+            ensure_adts_header(Bytes::from_static(&[0x12]), 2, 44100),
+            Bytes::from_static(&[0x12])
+        );
+    }
+
+    #[test]
+    fn test_extract_checked_on_every_truncation() {
+        let payload = protected_block(&[0x33; 16], &[]);
+        let with_crc = Bytes::from(
+            create_adts_header_with_crc(AacObjectType::Lc, 1, 44100, &payload).unwrap(),
+        );
+        let without_crc = Bytes::from(
+            [
+                &create_adts_header_for(AacObjectType::Lc, 1, 44100, payload.len(), false).unwrap()
+                    [..],
+                &payload,
+            ]
+            .concat(),
+        );
+
+        for frame in [with_crc.clone(), without_crc] {
+            let header_length = parse_adts_header(&frame).unwrap().header_length();
+            for len in 0..frame.len() {
+                let truncated = frame.slice(..len);
+                let expected = if len < header_length {
+                    AdtsError::Truncated
+                } else {
+                    AdtsError::FrameLengthExceedsInput {
+                        frame_length: frame.len(),
+                        available: len,
+                    }
+                };
+                assert_eq!(extract_aac_data_checked(&truncated), Err(expected));
+                assert!(extract_aac_blocks(&truncated).is_err());
+            }
+            assert_eq!(extract_aac_data_checked(&frame).unwrap(), &payload[..]);
+        }
+
+        let mut bad_sync = with_crc.to_vec();
+        bad_sync[1] = 0x0F;
+        assert_eq!(
+            extract_aac_data_checked(&Bytes::from(bad_sync.clone())),
+            Err(AdtsError::InvalidSyncWord)
+        );
+
+        // One flipped payload byte fails the CRC
+        bad_sync[1] = 0xF0;
+        bad_sync[9 + 6] ^= 0x10;
+        assert_eq!(
+            extract_aac_data_checked(&Bytes::from(bad_sync)),
+            Err(AdtsError::CrcMismatch)
+        );
     }
 }
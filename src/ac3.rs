@@ -0,0 +1,417 @@
+use crate::bits::{BitReader, EndOfInput};
+
+/// Fields of an AC-3 or E-AC-3 sync frame header and bit stream info.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SyncFrameHeader {
+    pub bsid: u8,
+    pub sample_rate: u32,
+    /// Audio coding mode, the arrangement of the full-bandwidth channels.
+    pub acmod: u8,
+    pub lfeon: bool,
+    pub channels: u8,
+    /// Bits per second.
+    pub bitrate: u32,
+    /// Frame length in bytes, header included.
+    pub frame_size: usize,
+    pub samples_per_frame: u32,
+}
+
+impl SyncFrameHeader {
+    pub fn is_eac3(&self) -> bool {
+        self.bsid > 10
+    }
+}
+
+const SYNC_WORD: [u8; 2] = [0x0B, 0x77];
+
+const SAMPLE_RATES: [u32; 3] = [48000, 44100, 32000];
+// fscod2 rates, used by E-AC-3 reduced sample rate streams.
+const REDUCED_SAMPLE_RATES: [u32; 3] = [24000, 22050, 16000];
+
+// Full-bandwidth channels per acmod; acmod 0 is dual mono.
+const ACMOD_CHANNELS: [u8; 8] = [2, 1, 2, 3, 3, 4, 4, 5];
+
+// Nominal bitrate in kbit/s per frmsizecod pair.
+const BITRATES: [u32; 19] = [
+    32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 448, 512, 576, 640,
+];
+
+/// AC-3 frame size in 16-bit words per frmsizecod, for 48, 44.1 and 32 kHz.
+const FRAME_SIZE_WORDS: [[u16; 3]; 38] = [
+    [64, 69, 96],
+    [64, 70, 96],
+    [80, 87, 120],
+    [80, 88, 120],
+    [96, 104, 144],
+    [96, 105, 144],
+    [112, 121, 168],
+    [112, 122, 168],
+    [128, 139, 192],
+    [128, 140, 192],
+    [160, 174, 240],
+    [160, 175, 240],
+    [192, 208, 288],
+    [192, 209, 288],
+    [224, 243, 336],
+    [224, 244, 336],
+    [256, 278, 384],
+    [256, 279, 384],
+    [320, 348, 480],
+    [320, 349, 480],
+    [384, 417, 576],
+    [384, 418, 576],
+    [448, 487, 672],
+    [448, 488, 672],
+    [512, 557, 768],
+    [512, 558, 768],
+    [640, 696, 960],
+    [640, 697, 960],
+    [768, 835, 1152],
+    [768, 836, 1152],
+    [896, 975, 1344],
+    [896, 976, 1344],
+    [1024, 1114, 1536],
+    [1024, 1115, 1536],
+    [1152, 1253, 1728],
+    [1152, 1254, 1728],
+    [1280, 1393, 1920],
+    [1280, 1394, 1920],
+];
+
+pub fn is_ac3(input: &[u8]) -> bool {
+    parse_syncframe_header(input).is_some_and(|header| !header.is_eac3())
+}
+
+pub fn is_eac3(input: &[u8]) -> bool {
+    parse_syncframe_header(input).is_some_and(|header| header.is_eac3())
+}
+
+/// Parses the sync frame header at the start of `input`, telling AC-3
+/// (bsid up to 10) and E-AC-3 (bsid 11 to 16) apart by the bsid field,
+/// which sits at the same position in both.
+pub fn parse_syncframe_header(input: &[u8]) -> Option<SyncFrameHeader> {
+    if input.len() < 8 || input[..2] != SYNC_WORD {
+        return None;
+    }
+
+    match input[5] >> 3 {
+        0..=10 => parse_ac3(input),
+        11..=16 => parse_eac3(input),
+        _ => None,
+    }
+}
+
+fn parse_ac3(input: &[u8]) -> Option<SyncFrameHeader> {
+    let fscod = (input[4] >> 6) as usize;
+    let frmsizecod = (input[4] & 0x3F) as usize;
+    if fscod == 3 || frmsizecod >= FRAME_SIZE_WORDS.len() {
+        return None;
+    }
+
+    // acmod is followed by mix levels that depend on it, then lfeon
+    let bsi = u16::from_be_bytes([input[6], input[7]]);
+    let acmod = (bsi >> 13) as u8;
+    let mut lfe_bit = 3;
+    if acmod & 0x01 != 0 && acmod != 1 {
+        lfe_bit += 2; // cmixlev
+    }
+    if acmod & 0x04 != 0 {
+        lfe_bit += 2; // surmixlev
+    }
+    if acmod == 2 {
+        lfe_bit += 2; // dsurmod
+    }
+    let lfeon = (bsi >> (15 - lfe_bit)) & 1 == 1;
+
+    Some(SyncFrameHeader {
+        bsid: input[5] >> 3,
+        sample_rate: SAMPLE_RATES[fscod],
+        acmod,
+        lfeon,
+        channels: ACMOD_CHANNELS[acmod as usize] + lfeon as u8,
+        bitrate: BITRATES[frmsizecod / 2] * 1000,
+        frame_size: FRAME_SIZE_WORDS[frmsizecod][fscod] as usize * 2,
+        samples_per_frame: 1536,
+    })
+}
+
+fn parse_eac3(input: &[u8]) -> Option<SyncFrameHeader> {
+    // strmtyp 3 is reserved
+    if input[2] >> 6 == 3 {
+        return None;
+    }
+    let frmsiz = (u16::from_be_bytes([input[2], input[3]]) & 0x07FF) as usize;
+    let frame_size = (frmsiz + 1) * 2;
+
+    let fscod = (input[4] >> 6) as usize;
+    let (sample_rate, blocks) = if fscod == 3 {
+        let fscod2 = ((input[4] >> 4) & 0x03) as usize;
+        (*REDUCED_SAMPLE_RATES.get(fscod2)?, 6)
+    } else {
+        let numblkscod = (input[4] >> 4) & 0x03;
+        (SAMPLE_RATES[fscod], [1, 2, 3, 6][numblkscod as usize])
+    };
+    let samples_per_frame = blocks * 256;
+
+    let acmod = (input[4] >> 1) & 0x07;
+    let lfeon = input[4] & 0x01 == 1;
+
+    Some(SyncFrameHeader {
+        bsid: input[5] >> 3,
+        sample_rate,
+        acmod,
+        lfeon,
+        channels: ACMOD_CHANNELS[acmod as usize] + lfeon as u8,
+        bitrate: (frame_size as u64 * 8 * sample_rate as u64 / samples_per_frame as u64) as u32,
+        frame_size,
+        samples_per_frame,
+    })
+}
+
+/// Length in bytes of the syncinfo and bitstream information at the
+/// start of `input`, rounded up to a whole byte, or `None` if the header
+/// does not parse or `input` ends inside it.
+pub fn header_len(input: &[u8]) -> Option<usize> {
+    let header = parse_syncframe_header(input)?;
+    let mut reader = BitReader::new(input);
+    let result = if header.is_eac3() {
+        skip_eac3_bsi(&mut reader, &header)
+    } else {
+        skip_ac3_bsi(&mut reader, &header)
+    };
+    result.ok()?;
+    Some(reader.bit_position().div_ceil(8))
+}
+
+// Skips an optional field behind its one-bit flag.
+fn skip_if_set(reader: &mut BitReader, bits: usize) -> Result<bool, EndOfInput> {
+    let set = reader.read_bit()?;
+    if set {
+        reader.skip(bits)?;
+    }
+    Ok(set)
+}
+
+fn skip_addbsi(reader: &mut BitReader) -> Result<(), EndOfInput> {
+    if reader.read_bit()? {
+        let addbsil = reader.read(6)? as usize;
+        reader.skip((addbsil + 1) * 8)?;
+    }
+    Ok(())
+}
+
+fn skip_ac3_bsi(reader: &mut BitReader, header: &SyncFrameHeader) -> Result<(), EndOfInput> {
+    let acmod = header.acmod;
+    // syncinfo, then bsid, bsmod and acmod
+    reader.skip(40 + 5 + 3 + 3)?;
+    if acmod & 0x01 != 0 && acmod != 1 {
+        reader.skip(2)?; // cmixlev
+    }
+    if acmod & 0x04 != 0 {
+        reader.skip(2)?; // surmixlev
+    }
+    if acmod == 2 {
+        reader.skip(2)?; // dsurmod
+    }
+    reader.skip(1)?; // lfeon
+
+    // Dual mono repeats dialnorm, compr, langcod and audprodi for the
+    // second channel
+    for _ in 0..if acmod == 0 { 2 } else { 1 } {
+        reader.skip(5)?; // dialnorm
+        skip_if_set(reader, 8)?; // compr
+        skip_if_set(reader, 8)?; // langcod
+        skip_if_set(reader, 5 + 2)?; // mixlevel, roomtyp
+    }
+    reader.skip(2)?; // copyrightb, origbs
+
+    // timecod1 and timecod2, or xbsi1 and xbsi2 in the bsid 6 syntax
+    skip_if_set(reader, 14)?;
+    skip_if_set(reader, 14)?;
+    skip_addbsi(reader)
+}
+
+fn skip_eac3_bsi(reader: &mut BitReader, header: &SyncFrameHeader) -> Result<(), EndOfInput> {
+    let acmod = header.acmod;
+    let blocks = header.samples_per_frame / 256;
+    // syncword, then strmtyp, substreamid and frmsiz
+    reader.skip(16)?;
+    let strmtyp = reader.read(2)?;
+    reader.skip(3 + 11)?;
+    let fscod = reader.read(2)?;
+    // fscod2 or numblkscod, acmod, lfeon, bsid and dialnorm
+    reader.skip(2 + 3 + 1 + 5 + 5)?;
+    skip_if_set(reader, 8)?; // compr
+    if acmod == 0 {
+        reader.skip(5)?; // dialnorm2
+        skip_if_set(reader, 8)?; // compr2
+    }
+    if strmtyp == 1 {
+        skip_if_set(reader, 16)?; // chanmap
+    }
+
+    if reader.read_bit()? {
+        // mixmdate
+        if acmod > 2 {
+            reader.skip(2)?; // dmixmod
+        }
+        if acmod & 0x01 != 0 && acmod > 2 {
+            reader.skip(6)?; // ltrtcmixlev, lorocmixlev
+        }
+        if acmod & 0x04 != 0 {
+            reader.skip(6)?; // ltrtsurmixlev, lorosurmixlev
+        }
+        if header.lfeon {
+            skip_if_set(reader, 5)?; // lfemixlevcod
+        }
+        if strmtyp == 0 {
+            skip_if_set(reader, 6)?; // pgmscl
+            if acmod == 0 {
+                skip_if_set(reader, 6)?; // pgmscl2
+            }
+            skip_if_set(reader, 6)?; // extpgmscl
+            match reader.read(2)? {
+                1 => reader.skip(5)?,
+                2 => reader.skip(12)?,
+                3 => {
+                    let mixdeflen = reader.read(5)? as usize;
+                    reader.skip((mixdeflen + 2) * 8)?;
+                }
+                _ => {}
+            }
+            if acmod < 2 {
+                skip_if_set(reader, 8 + 6)?; // panmean, paninfo
+                if acmod == 0 {
+                    skip_if_set(reader, 8 + 6)?; // panmean2, paninfo2
+                }
+            }
+            if reader.read_bit()? {
+                // frmmixcfginfoe
+                if blocks == 1 {
+                    reader.skip(5)?;
+                } else {
+                    for _ in 0..blocks {
+                        skip_if_set(reader, 5)?;
+                    }
+                }
+            }
+        }
+    }
+
+    if reader.read_bit()? {
+        // infomdate: bsmod, copyrightb, origbs
+        reader.skip(3 + 1 + 1)?;
+        if acmod == 2 {
+            reader.skip(4)?; // dsurmod, dheadphonmod
+        }
+        if acmod >= 6 {
+            reader.skip(2)?; // dsurexmod
+        }
+        skip_if_set(reader, 5 + 2 + 1)?; // mixlevel, roomtyp, adconvtyp
+        if acmod == 0 {
+            skip_if_set(reader, 5 + 2 + 1)?;
+        }
+        if fscod < 3 {
+            reader.skip(1)?; // sourcefscod
+        }
+    }
+    if strmtyp == 0 && blocks != 6 {
+        reader.skip(1)?; // convsync
+    }
+    if strmtyp == 2 {
+        // blkid is implied when there are six blocks
+        if blocks == 6 || reader.read_bit()? {
+            reader.skip(6)?; // frmsizecod
+        }
+    }
+    skip_addbsi(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ac3_header() {
+        // 48 kHz, 384 kbit/s, bsid 8, 3/2 with LFE
+        let header = [0x0B, 0x77, 0x00, 0x00, 0x1C, 0x40, 0xE1, 0x40];
+        let parsed = parse_syncframe_header(&header).unwrap();
+        assert_eq!(
+            parsed,
+            SyncFrameHeader {
+                bsid: 8,
+                sample_rate: 48000,
+                acmod: 7,
+                lfeon: true,
+                channels: 6,
+                bitrate: 384_000,
+                frame_size: 1536,
+                samples_per_frame: 1536,
+            }
+        );
+        assert!(is_ac3(&header));
+        assert!(!is_eac3(&header));
+
+        // 44.1 kHz, 192 kbit/s, odd frmsizecod, stereo without LFE
+        let header = [0x0B, 0x77, 0x00, 0x00, 0x55, 0x40, 0x40, 0x00];
+        let parsed = parse_syncframe_header(&header).unwrap();
+        assert_eq!(parsed.sample_rate, 44100);
+        assert_eq!(parsed.bitrate, 192_000);
+        assert_eq!(parsed.frame_size, 418 * 2);
+        assert_eq!(parsed.channels, 2);
+        assert!(!parsed.lfeon);
+    }
+
+    #[test]
+    fn test_parse_eac3_header() {
+        // Independent substream, 768 bytes, 48 kHz, 6 blocks, 3/2 + LFE
+        let header = [0x0B, 0x77, 0x01, 0x7F, 0x3F, 0x80, 0x00, 0x00];
+        let parsed = parse_syncframe_header(&header).unwrap();
+        assert!(parsed.is_eac3());
+        assert!(is_eac3(&header));
+        assert_eq!(parsed.frame_size, 768);
+        assert_eq!(parsed.sample_rate, 48000);
+        assert_eq!(parsed.samples_per_frame, 1536);
+        assert_eq!(parsed.channels, 6);
+        assert_eq!(parsed.bitrate, 192_000);
+    }
+
+    #[test]
+    fn test_header_len() {
+        // Stereo AC-3 with dsurmod and no optional fields
+        let mut frame = vec![0x0B, 0x77, 0x00, 0x00, 0x00, 0x40, 0x40, 0x00, 0x00];
+        assert_eq!(header_len(&frame), Some(9));
+        assert_eq!(header_len(&frame[..8]), None);
+        // compre and langcode add a byte each
+        frame[7] = 0x10;
+        frame[8] = 0x08;
+        frame.extend_from_slice(&[0; 2]);
+        assert_eq!(header_len(&frame), Some(11));
+
+        // 3/2 with LFE: cmixlev and surmixlev, but no dsurmod
+        let frame = [0x0B, 0x77, 0x00, 0x00, 0x1C, 0x40, 0xE1, 0x40, 0x00];
+        assert_eq!(header_len(&frame), Some(9));
+
+        // E-AC-3 with no mixing or info metadata
+        let mut frame = vec![0x0B, 0x77, 0x01, 0x7F, 0x3F, 0x80, 0x00, 0x00];
+        assert_eq!(header_len(&frame), Some(7));
+        // addbsie with a one-byte addbsi
+        frame[6] = 0x04;
+        assert_eq!(header_len(&frame), None);
+        frame.push(0x00);
+        assert_eq!(header_len(&frame), Some(9));
+    }
+
+    #[test]
+    fn test_rejects_invalid_headers() {
+        // Reserved fscod
+        assert!(parse_syncframe_header(&[0x0B, 0x77, 0, 0, 0xC0, 0x40, 0, 0]).is_none());
+        // frmsizecod out of range
+        assert!(parse_syncframe_header(&[0x0B, 0x77, 0, 0, 0x26, 0x40, 0, 0]).is_none());
+        // bsid 17
+        assert!(parse_syncframe_header(&[0x0B, 0x77, 0, 0, 0x10, 0x88, 0, 0]).is_none());
+        assert!(parse_syncframe_header(&[0x0B, 0x77, 0, 0]).is_none());
+        assert!(!is_ac3(&[0xFF, 0xF1, 0x50, 0x80, 0x08, 0x1F, 0xFC, 0x00]));
+    }
+}
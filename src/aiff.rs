@@ -0,0 +1,200 @@
+/// Sample encoding named by an AIFF-C COMM chunk. Plain AIFF is always
+/// big-endian integer PCM.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AiffCompression {
+    /// Big-endian integer PCM (`NONE`, or a plain AIFF file).
+    None,
+    /// Little-endian integer PCM (`sowt`).
+    Sowt,
+    /// Big-endian 32-bit float PCM (`fl32`).
+    Fl32,
+    Other([u8; 4]),
+}
+
+impl AiffCompression {
+    pub fn is_little_endian(&self) -> bool {
+        *self == AiffCompression::Sowt
+    }
+}
+
+/// Fields of the COMM chunk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AiffInfo {
+    pub channels: u16,
+    pub sample_frames: u32,
+    pub bits_per_sample: u16,
+    /// Decoded from the 80-bit extended float, fractions of a hertz dropped.
+    pub sample_rate: u32,
+    pub compression: AiffCompression,
+}
+
+pub(crate) const FORM_HEADER_LEN: usize = 12;
+const CHUNK_HEADER_LEN: usize = 8;
+const COMM_LEN: usize = 18;
+
+pub fn is_aiff(data: &[u8]) -> bool {
+    data.starts_with(b"FORM") && matches!(data.get(8..12), Some(b"AIFF" | b"AIFC"))
+}
+
+/// Finds the COMM chunk of an AIFF or AIFF-C file and parses it.
+pub fn parse_comm(data: &[u8]) -> Option<AiffInfo> {
+    if !is_aiff(data) {
+        return None;
+    }
+    let aifc = &data[8..12] == b"AIFC";
+
+    let mut pos = FORM_HEADER_LEN;
+    while let Some(header) = data.get(pos..pos + CHUNK_HEADER_LEN) {
+        let size = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+        let body = pos + CHUNK_HEADER_LEN;
+        if &header[..4] == b"COMM" {
+            return parse_comm_body(data.get(body..body + size)?, aifc);
+        }
+        // Chunks are padded to an even length
+        pos = body.checked_add(size + (size & 1))?;
+    }
+    None
+}
+
+fn parse_comm_body(comm: &[u8], aifc: bool) -> Option<AiffInfo> {
+    if comm.len() < COMM_LEN {
+        return None;
+    }
+
+    let compression = if aifc {
+        match comm.get(COMM_LEN..COMM_LEN + 4)? {
+            b"NONE" => AiffCompression::None,
+            b"sowt" => AiffCompression::Sowt,
+            b"fl32" | b"FL32" => AiffCompression::Fl32,
+            other => AiffCompression::Other(other.try_into().unwrap()),
+        }
+    } else {
+        AiffCompression::None
+    };
+
+    Some(AiffInfo {
+        channels: u16::from_be_bytes([comm[0], comm[1]]),
+        sample_frames: u32::from_be_bytes(comm[2..6].try_into().unwrap()),
+        bits_per_sample: u16::from_be_bytes([comm[6], comm[7]]),
+        sample_rate: extended_to_u32(comm[8..18].try_into().unwrap())?,
+        compression,
+    })
+}
+
+// IEEE 754 80-bit extended: sign, 15-bit exponent biased by 16383, and a
+// 64-bit mantissa with an explicit integer bit.
+fn extended_to_u32(bytes: [u8; 10]) -> Option<u32> {
+    let exponent = u16::from_be_bytes([bytes[0], bytes[1]]);
+    let mantissa = u64::from_be_bytes(bytes[2..].try_into().unwrap());
+    if exponent & 0x8000 != 0 {
+        return None;
+    }
+
+    let shift = 16383 + 63 - exponent as i32;
+    if shift >= 64 {
+        Some(0)
+    } else if shift > 0 {
+        u32::try_from(mantissa >> shift).ok()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn aiff_file(compression: Option<&[u8; 4]>, sample_rate: [u8; 10]) -> Vec<u8> {
+        let mut comm = vec![0x00, 0x02, 0x00, 0x00, 0xAC, 0x44, 0x00, 0x10];
+        comm.extend_from_slice(&sample_rate);
+        if let Some(compression) = compression {
+            // Compression type and an empty pascal string name
+            comm.extend_from_slice(compression);
+            comm.extend_from_slice(&[0x00, 0x00]);
+        }
+
+        let mut chunks = b"FVER\x00\x00\x00\x04\xA2\x80\x51\x40".to_vec();
+        chunks.extend_from_slice(b"COMM");
+        chunks.extend_from_slice(&(comm.len() as u32).to_be_bytes());
+        chunks.extend(comm);
+        chunks.extend_from_slice(b"SSND\x00\x00\x00\x08\x00\x00\x00\x00\x00\x00\x00\x00");
+
+        let mut file = b"FORM".to_vec();
+        file.extend_from_slice(&(chunks.len() as u32 + 4).to_be_bytes());
+        file.extend_from_slice(if compression.is_some() {
+            b"AIFC"
+        } else {
+            b"AIFF"
+        });
+        file.extend(chunks);
+        file
+    }
+
+    const RATE_44100: [u8; 10] = [0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0];
+
+    #[test]
+    fn test_parse_aiff_comm() {
+        let file = aiff_file(None, RATE_44100);
+        assert!(is_aiff(&file));
+        assert_eq!(
+            parse_comm(&file),
+            Some(AiffInfo {
+                channels: 2,
+                sample_frames: 44100,
+                bits_per_sample: 16,
+                sample_rate: 44100,
+                compression: AiffCompression::None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_aifc_compression() {
+        let file = aiff_file(Some(b"sowt"), RATE_44100);
+        let info = parse_comm(&file).unwrap();
+        assert_eq!(info.compression, AiffCompression::Sowt);
+        assert!(info.compression.is_little_endian());
+
+        let file = aiff_file(Some(b"fl32"), RATE_44100);
+        assert_eq!(
+            parse_comm(&file).unwrap().compression,
+            AiffCompression::Fl32
+        );
+
+        let file = aiff_file(Some(b"ima4"), RATE_44100);
+        assert_eq!(
+            parse_comm(&file).unwrap().compression,
+            AiffCompression::Other(*b"ima4")
+        );
+    }
+
+    #[test]
+    fn test_extended_sample_rates() {
+        // 48000 and 8000 Hz
+        assert_eq!(
+            extended_to_u32([0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0]),
+            Some(48000)
+        );
+        assert_eq!(
+            extended_to_u32([0x40, 0x0B, 0xFA, 0x00, 0, 0, 0, 0, 0, 0]),
+            Some(8000)
+        );
+        assert_eq!(
+            extended_to_u32([0xC0, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0]),
+            None
+        );
+        assert_eq!(
+            extended_to_u32([0x7F, 0xFF, 0x80, 0, 0, 0, 0, 0, 0, 0]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_rejects_other_forms() {
+        assert!(!is_aiff(b"RIFF\x00\x00\x00\x00WAVE"));
+        assert!(!is_aiff(b"FORM\x00\x00\x00\x04ILBM"));
+        assert!(parse_comm(b"FORM\x00\x00\x00\x04AIFF").is_none());
+    }
+}
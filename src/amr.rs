@@ -0,0 +1,118 @@
+use crate::AudioType;
+
+const AMR_NB_MAGIC: &[u8] = b"#!AMR\n";
+const AMR_WB_MAGIC: &[u8] = b"#!AMR-WB\n";
+
+/// Frames last 20 ms in both modes.
+pub const AMR_FRAME_MS: u32 = 20;
+
+// Frame size in bytes per frame type, TOC byte included. Zero marks the
+// reserved types.
+const AMR_NB_FRAME_SIZES: [usize; 16] = [13, 14, 16, 18, 20, 21, 27, 32, 6, 1, 1, 1, 0, 0, 0, 1];
+const AMR_WB_FRAME_SIZES: [usize; 16] = [18, 24, 33, 37, 41, 47, 51, 59, 61, 6, 0, 0, 0, 0, 1, 1];
+
+/// Checks for the single-channel AMR-NB or AMR-WB file magic.
+pub fn is_amr(data: &[u8]) -> Option<AudioType> {
+    if data.starts_with(AMR_NB_MAGIC) {
+        Some(AudioType::AmrNb)
+    } else if data.starts_with(AMR_WB_MAGIC) {
+        Some(AudioType::AmrWb)
+    } else {
+        None
+    }
+}
+
+/// Checks whether `data` could still grow into either AMR file magic.
+pub(crate) fn is_magic_prefix(data: &[u8]) -> bool {
+    magic_len(data).is_some()
+}
+
+/// Length of the shortest AMR file magic `data` could still grow into.
+pub(crate) fn magic_len(data: &[u8]) -> Option<usize> {
+    [AMR_NB_MAGIC, AMR_WB_MAGIC]
+        .into_iter()
+        .find(|magic| magic.starts_with(data))
+        .map(<[u8]>::len)
+}
+
+/// Iterates over the frames of an AMR file, yielding the frame type from
+/// each TOC byte and the frame with its TOC byte. Stops at a reserved
+/// frame type or a truncated frame, and yields nothing if `data` is not an
+/// AMR file.
+pub fn frames(data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    let (mut pos, sizes) = match is_amr(data) {
+        Some(AudioType::AmrNb) => (AMR_NB_MAGIC.len(), &AMR_NB_FRAME_SIZES),
+        Some(_) => (AMR_WB_MAGIC.len(), &AMR_WB_FRAME_SIZES),
+        None => (data.len(), &AMR_NB_FRAME_SIZES),
+    };
+
+    std::iter::from_fn(move || {
+        let toc = *data.get(pos)?;
+        // The padding bits around the frame type and quality bit are zero
+        if toc & 0x83 != 0 {
+            return None;
+        }
+        let frame_type = (toc >> 3) & 0x0F;
+        let size = sizes[frame_type as usize];
+        if size == 0 {
+            return None;
+        }
+
+        let frame = data.get(pos..pos + size)?;
+        pos += size;
+        Some((frame_type, frame))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_amr() {
+        assert_eq!(is_amr(b"#!AMR\n\x3C"), Some(AudioType::AmrNb));
+        assert_eq!(is_amr(b"#!AMR-WB\n\x24"), Some(AudioType::AmrWb));
+        assert_eq!(is_amr(b"#!AMR_MC1.0\n"), None);
+        assert_eq!(is_amr(b"ID3"), None);
+    }
+
+    #[test]
+    fn test_amr_nb_frames() {
+        let mut data = AMR_NB_MAGIC.to_vec();
+        // MR122, SID, then NO_DATA
+        data.push(0x3C);
+        data.extend_from_slice(&[0xAA; 31]);
+        data.push(0x44);
+        data.extend_from_slice(&[0x55; 5]);
+        data.push(0x7C);
+
+        let frames: Vec<_> = frames(&data).collect();
+        assert_eq!(
+            frames
+                .iter()
+                .map(|(t, f)| (*t, f.len()))
+                .collect::<Vec<_>>(),
+            vec![(7, 32), (8, 6), (15, 1)]
+        );
+        assert_eq!(frames[0].1[0], 0x3C);
+
+        // A truncated frame ends the iteration
+        data.push(0x3C);
+        data.extend_from_slice(&[0xAA; 10]);
+        assert_eq!(super::frames(&data).count(), 3);
+    }
+
+    #[test]
+    fn test_amr_wb_frames() {
+        let mut data = AMR_WB_MAGIC.to_vec();
+        // Mode 8 (23.85 kbit/s), then reserved type 12
+        data.push(0x44);
+        data.extend_from_slice(&[0; 60]);
+        data.push(0x64);
+        data.push(0x00);
+
+        let frames: Vec<_> = frames(&data).map(|(t, f)| (t, f.len())).collect();
+        assert_eq!(frames, vec![(8, 61)]);
+        assert_eq!(super::frames(b"RIFF").count(), 0);
+    }
+}
@@ -0,0 +1,183 @@
+//! MSB-first bit reading and writing, shared by the header parsers.
+
+use std::fmt;
+
+/// Returned when a read runs past the end of the data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EndOfInput;
+
+impl fmt::Display for EndOfInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unexpected end of input")
+    }
+}
+
+impl std::error::Error for EndOfInput {}
+
+/// MSB-first bit reader. A read or skip that would run past the end of
+/// the data fails without moving the reader.
+#[derive(Debug, Clone)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    bit_position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            bit_position: 0,
+        }
+    }
+
+    pub fn bit_position(&self) -> usize {
+        self.bit_position
+    }
+
+    pub fn remaining_bits(&self) -> usize {
+        (self.data.len() * 8).saturating_sub(self.bit_position)
+    }
+
+    /// Reads up to 32 bits.
+    pub fn read(&mut self, num_bits: usize) -> Result<u32, EndOfInput> {
+        let value = self.peek(num_bits)?;
+        self.bit_position += num_bits;
+        Ok(value)
+    }
+
+    /// Like [`BitReader::read`], without moving the reader.
+    pub fn peek(&self, num_bits: usize) -> Result<u32, EndOfInput> {
+        assert!(num_bits <= 32, "cannot read {} bits at once", num_bits);
+        if num_bits > self.remaining_bits() {
+            return Err(EndOfInput);
+        }
+        if num_bits == 0 {
+            return Ok(0);
+        }
+
+        // At most five bytes hold the field: up to seven bits of the first
+        // one are not part of it
+        let first = self.bit_position / 8;
+        let last = (self.bit_position + num_bits - 1) / 8;
+        let bytes = self.data[first..=last]
+            .iter()
+            .fold(0u64, |acc, &byte| (acc << 8) | byte as u64);
+        let shift = (last + 1) * 8 - self.bit_position - num_bits;
+        Ok(((bytes >> shift) & ((1 << num_bits) - 1)) as u32)
+    }
+
+    pub fn read_bit(&mut self) -> Result<bool, EndOfInput> {
+        self.read(1).map(|bit| bit == 1)
+    }
+
+    pub fn skip(&mut self, num_bits: usize) -> Result<(), EndOfInput> {
+        if num_bits > self.remaining_bits() {
+            return Err(EndOfInput);
+        }
+        self.bit_position += num_bits;
+        Ok(())
+    }
+
+    /// Skips to the next byte boundary.
+    pub fn align_to_byte(&mut self) {
+        self.bit_position = self.bit_position.div_ceil(8) * 8;
+    }
+}
+
+/// MSB-first bit writer.
+#[derive(Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    pub fn write(&mut self, value: u64, bits: u32) {
+        for i in (0..bits).rev() {
+            self.acc = (self.acc << 1) | ((value >> i) & 1);
+            self.bits += 1;
+            if self.bits == 8 {
+                self.bytes.push(self.acc as u8);
+                self.acc = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    /// The written bytes, zero-padded to a byte boundary.
+    pub fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push((self.acc << (8 - self.bits)) as u8);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_across_bytes() {
+        let mut reader = BitReader::new(&[0b1010_1100, 0b0101_0000]);
+        assert!(reader.read_bit().unwrap());
+        assert_eq!(reader.read(3).unwrap(), 0b010);
+        assert_eq!(reader.read(8).unwrap(), 0b1100_0101);
+        assert_eq!(reader.bit_position(), 12);
+
+        reader.align_to_byte();
+        assert_eq!(reader.bit_position(), 16);
+        assert_eq!(reader.read_bit(), Err(EndOfInput));
+    }
+
+    #[test]
+    fn test_wide_reads_and_peeks() {
+        let data = [0x81, 0x23, 0x45, 0x67, 0x89];
+        let mut reader = BitReader::new(&data);
+        reader.skip(7).unwrap();
+        assert_eq!(reader.peek(32).unwrap(), 0x91A2_B3C4);
+        assert_eq!(reader.bit_position(), 7);
+        assert_eq!(reader.read(32).unwrap(), 0x91A2_B3C4);
+        assert_eq!(reader.remaining_bits(), 1);
+        assert_eq!(reader.read(0).unwrap(), 0);
+
+        // A failed read leaves the reader where it was
+        assert_eq!(reader.read(2), Err(EndOfInput));
+        assert_eq!(reader.remaining_bits(), 1);
+        assert!(reader.read_bit().unwrap());
+    }
+
+    #[test]
+    fn test_skip_to_the_end() {
+        let mut reader = BitReader::new(&[0xFF, 0x00]);
+        reader.skip(16).unwrap();
+        assert_eq!(reader.remaining_bits(), 0);
+        assert_eq!(reader.skip(0), Ok(()));
+        assert_eq!(reader.skip(1), Err(EndOfInput));
+
+        // Skipping into the last byte is fine, past it is not
+        let mut reader = BitReader::new(&[0xFF, 0x00]);
+        reader.skip(12).unwrap();
+        assert_eq!(reader.skip(5), Err(EndOfInput));
+        assert_eq!(reader.bit_position(), 12);
+        reader.align_to_byte();
+        assert_eq!(reader.remaining_bits(), 0);
+    }
+
+    #[test]
+    fn test_write_then_read() {
+        let mut writer = BitWriter::default();
+        writer.write(0b101, 3);
+        writer.write(0x1FF, 9);
+        writer.write(1, 1);
+        let bytes = writer.finish();
+        assert_eq!(bytes, [0b1011_1111, 0b1111_1000]);
+
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(reader.read(3).unwrap(), 0b101);
+        assert_eq!(reader.read(9).unwrap(), 0x1FF);
+        assert!(reader.read_bit().unwrap());
+        assert_eq!(reader.bit_position(), 13);
+    }
+}
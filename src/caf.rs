@@ -0,0 +1,155 @@
+use crate::AudioType;
+
+/// The `desc` chunk of a Core Audio Format file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AudioDescription {
+    pub sample_rate: f64,
+    pub format_id: [u8; 4],
+    pub format_flags: u32,
+    /// Zero when packets vary in size.
+    pub bytes_per_packet: u32,
+    /// Zero when packets vary in duration.
+    pub frames_per_packet: u32,
+    pub channels: u32,
+    /// Zero for compressed formats.
+    pub bits_per_channel: u32,
+}
+
+impl AudioDescription {
+    /// Maps the format ID onto an [`AudioType`]. Formats without one,
+    /// including `lpcm` and `.mp3`, give `None`.
+    pub fn codec(&self) -> Option<AudioType> {
+        match &self.format_id {
+            b"aac " => Some(AudioType::AAC),
+            b"opus" => Some(AudioType::Opus),
+            b"flac" => Some(AudioType::FLAC),
+            b"ac-3" => Some(AudioType::AC3),
+            b"ec-3" => Some(AudioType::EAC3),
+            _ => None,
+        }
+    }
+}
+
+const CAF_FILE_HEADER_LEN: usize = 8;
+const CHUNK_HEADER_LEN: usize = 12;
+const DESC_LEN: usize = 32;
+
+/// Checks for the `caff` magic followed by file version 1 and zero flags.
+pub fn is_caf(data: &[u8]) -> bool {
+    data.starts_with(b"caff") && data.get(4..8) == Some(&[0x00, 0x01, 0x00, 0x00])
+}
+
+/// Finds and parses the `desc` chunk, which the format requires to be the
+/// first chunk.
+pub fn parse_audio_description(data: &[u8]) -> Option<AudioDescription> {
+    if !is_caf(data) {
+        return None;
+    }
+
+    let desc = chunks(data)
+        .find(|(chunk_type, _)| chunk_type == b"desc")?
+        .1;
+    if desc.len() < DESC_LEN {
+        return None;
+    }
+
+    let u32_at = |pos: usize| u32::from_be_bytes(desc[pos..pos + 4].try_into().unwrap());
+    Some(AudioDescription {
+        sample_rate: f64::from_be_bytes(desc[..8].try_into().unwrap()),
+        format_id: desc[8..12].try_into().unwrap(),
+        format_flags: u32_at(12),
+        bytes_per_packet: u32_at(16),
+        frames_per_packet: u32_at(20),
+        channels: u32_at(24),
+        bits_per_channel: u32_at(28),
+    })
+}
+
+// Chunk sizes are signed 64-bit; only the data chunk may use -1, meaning
+// it runs to the end of the file.
+fn chunks(data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    let mut pos = CAF_FILE_HEADER_LEN;
+    std::iter::from_fn(move || {
+        let header = data.get(pos..pos + CHUNK_HEADER_LEN)?;
+        let chunk_type: [u8; 4] = header[..4].try_into().unwrap();
+        let size = i64::from_be_bytes(header[4..12].try_into().unwrap());
+        let body = pos + CHUNK_HEADER_LEN;
+
+        let end = match size {
+            -1 if &chunk_type == b"data" => data.len(),
+            size => body.checked_add(usize::try_from(size).ok()?)?,
+        };
+        let chunk = data.get(body..end)?;
+        pos = end;
+        Some((chunk_type, chunk))
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn caf_file(format_id: &[u8; 4], data_size: i64) -> Vec<u8> {
+        let mut file = b"caff\x00\x01\x00\x00desc".to_vec();
+        file.extend_from_slice(&32i64.to_be_bytes());
+        file.extend_from_slice(&44100f64.to_be_bytes());
+        file.extend_from_slice(format_id);
+        for field in [0u32, 0, 1024, 2, 0] {
+            file.extend_from_slice(&field.to_be_bytes());
+        }
+        file.extend_from_slice(b"data");
+        file.extend_from_slice(&data_size.to_be_bytes());
+        file.extend_from_slice(&[0; 16]);
+        file
+    }
+
+    #[test]
+    fn test_parse_audio_description() {
+        let file = caf_file(b"aac ", 16);
+        assert!(is_caf(&file));
+
+        let desc = parse_audio_description(&file).unwrap();
+        assert_eq!(
+            desc,
+            AudioDescription {
+                sample_rate: 44100.0,
+                format_id: *b"aac ",
+                format_flags: 0,
+                bytes_per_packet: 0,
+                frames_per_packet: 1024,
+                channels: 2,
+                bits_per_channel: 0,
+            }
+        );
+        assert_eq!(desc.codec(), Some(AudioType::AAC));
+    }
+
+    #[test]
+    fn test_chunk_sizes() {
+        // The data chunk runs to the end of the file
+        let file = caf_file(b"opus", -1);
+        let chunks: Vec<_> = chunks(&file).map(|(t, c)| (t, c.len())).collect();
+        assert_eq!(chunks, vec![(*b"desc", 32), (*b"data", 16)]);
+
+        // Only the data chunk may leave its size unset
+        let mut file = caf_file(b"opus", 16);
+        file[12..20].copy_from_slice(&(-1i64).to_be_bytes());
+        assert!(parse_audio_description(&file).is_none());
+    }
+
+    #[test]
+    fn test_format_ids() {
+        for (format_id, codec) in [
+            (b"opus", Some(AudioType::Opus)),
+            (b"flac", Some(AudioType::FLAC)),
+            (b"ec-3", Some(AudioType::EAC3)),
+            (b"lpcm", None),
+            (b".mp3", None),
+        ] {
+            let desc = parse_audio_description(&caf_file(format_id, 16)).unwrap();
+            assert_eq!(desc.codec(), codec);
+        }
+        assert!(!is_caf(b"caff\x00\x02\x00\x00"));
+    }
+}
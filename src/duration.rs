@@ -0,0 +1,196 @@
+use crate::{aac, detect_audio, flac, AudioType};
+use std::time::Duration;
+
+// Longest possible FLAC frame header, kept around when no frame is found yet.
+const FLAC_MAX_HEADER_SIZE: usize = 16;
+
+/// Progress of a [`duration_hint`] scan. Passing it back in on the next call
+/// resumes parsing at the last fully-parsed frame boundary.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DurationState {
+    audio_type: AudioType,
+    offset: usize,
+    sample_rate: u32,
+    samples: u64,
+    elapsed: Duration,
+}
+
+impl DurationState {
+    fn new(audio_type: AudioType) -> Self {
+        Self {
+            audio_type,
+            offset: 0,
+            sample_rate: 0,
+            samples: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Byte offset up to which the input has been fully parsed.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn audio_type(&self) -> AudioType {
+        self.audio_type
+    }
+
+    fn add_samples(&mut self, samples: u64, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.elapsed = self.duration();
+            self.samples = 0;
+            self.sample_rate = sample_rate;
+        }
+        self.samples += samples;
+    }
+
+    fn duration(&self) -> Duration {
+        if self.sample_rate == 0 {
+            return self.elapsed;
+        }
+        let rate = self.sample_rate as u64;
+        self.elapsed
+            + Duration::from_secs(self.samples / rate)
+            + Duration::from_nanos((self.samples % rate) * 1_000_000_000 / rate)
+    }
+}
+
+/// Best-effort duration of a file that may still be growing.
+///
+/// Only complete frames are counted, so a truncated final frame is left for
+/// the next call. Passing the returned state back in skips everything parsed
+/// so far; if `data` has shrunk below the saved offset the scan starts over.
+/// Supports ADTS and raw FLAC frame streams.
+pub fn duration_hint(data: &[u8], previous: Option<&DurationState>) -> (Duration, DurationState) {
+    let mut state = match previous {
+        Some(state) if state.audio_type != AudioType::Unknown && state.offset <= data.len() => {
+            state.clone()
+        }
+        _ => DurationState::new(detect_audio(data)),
+    };
+
+    match state.audio_type {
+        AudioType::AAC => scan_adts(data, &mut state),
+        AudioType::FLAC => scan_flac(data, &mut state),
+        _ => {}
+    }
+
+    (state.duration(), state)
+}
+
+fn scan_adts(data: &[u8], state: &mut DurationState) {
+    let mut pos = state.offset;
+
+    while pos + 7 <= data.len() {
+        let Ok(header) = aac::parse_adts_header(&data[pos..]) else {
+            pos += 1;
+            continue;
+        };
+        if pos + header.frame_length > data.len() {
+            // Frame is still being written
+            break;
+        }
+
+        state.add_samples(header.samples_per_frame() as u64, header.sample_rate);
+        pos += header.frame_length;
+    }
+
+    state.offset = pos;
+}
+
+fn scan_flac(data: &[u8], state: &mut DurationState) {
+    let mut current = next_flac_frame(data, state.offset);
+
+    if current.is_none() {
+        state.offset = state
+            .offset
+            .max(data.len().saturating_sub(FLAC_MAX_HEADER_SIZE));
+        return;
+    }
+
+    // A frame only counts once the next frame header proves where it ends
+    while let Some((start, info)) = current {
+        current = next_flac_frame(data, start + 1);
+        if current.is_some() {
+            state.add_samples(info.block_size as u64, info.sample_rate);
+        }
+        state.offset = start;
+    }
+}
+
+fn next_flac_frame(data: &[u8], from: usize) -> Option<(usize, flac::FLACFrameInfo)> {
+    (from..data.len().saturating_sub(1)).find_map(|i| {
+        if data[i] == 0xFF && (data[i + 1] & 0xFE) == 0xF8 {
+            flac::decode_frame_header_checked(&data[i..])
+                .ok()
+                .map(|info| (i, info))
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn adts_stream(frames: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        for _ in 0..frames {
+            data.extend(
+                aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 100, false).unwrap(),
+            );
+            data.extend_from_slice(&[0u8; 100]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_duration_hint_adts_growing() {
+        let data = adts_stream(10);
+
+        let (full, _) = duration_hint(&data, None);
+        assert_eq!(
+            full,
+            Duration::from_nanos(10 * 1024 * 1_000_000_000 / 44100)
+        );
+
+        // Three and a half frames written so far
+        let (partial, state) = duration_hint(&data[..350], None);
+        assert_eq!(state.offset(), 321);
+        assert_eq!(
+            partial,
+            Duration::from_nanos(3 * 1024 * 1_000_000_000 / 44100)
+        );
+
+        let (resumed, state) = duration_hint(&data, Some(&state));
+        assert_eq!(resumed, full);
+        assert_eq!(state.offset(), data.len());
+    }
+
+    #[test]
+    fn test_duration_hint_flac_incremental() {
+        let data = fs::read("testdata/s24le.wav.flac").unwrap();
+        let (full, full_state) = duration_hint(&data, None);
+
+        // The final frame stays pending until a following frame arrives
+        assert_eq!(
+            full,
+            Duration::from_nanos(85 * 4096 * 1_000_000_000 / 44100)
+        );
+
+        let mut state = None;
+        let mut duration = Duration::ZERO;
+        for end in (0..data.len()).step_by(50_000).chain([data.len()]) {
+            let (d, s) = duration_hint(&data[..end], state.as_ref());
+            assert!(d >= duration);
+            duration = d;
+            state = Some(s);
+        }
+
+        assert_eq!(duration, full);
+        assert_eq!(state.unwrap(), full_state);
+    }
+}
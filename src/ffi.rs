@@ -0,0 +1,274 @@
+//! C interface to detection and frame header parsing, enabled by the `ffi`
+//! feature. `include/access_unit.h` declares these functions.
+//!
+//! Every function returns an error code instead of unwinding: a panic is
+//! caught at the boundary and reported through [`au_last_error_message`].
+
+use crate::{aac, detect_audio, flac, AudioType};
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+pub const AU_OK: i32 = 0;
+pub const AU_ERROR: i32 = -1;
+
+/// Stable codes for [`au_detect_audio`]. New formats get new numbers;
+/// existing numbers never change.
+pub const AU_AUDIO_UNKNOWN: i32 = 0;
+pub const AU_AUDIO_AAC: i32 = 1;
+pub const AU_AUDIO_FLAC: i32 = 2;
+pub const AU_AUDIO_OPUS: i32 = 3;
+pub const AU_AUDIO_AC3: i32 = 4;
+pub const AU_AUDIO_EAC3: i32 = 5;
+pub const AU_AUDIO_VORBIS: i32 = 6;
+pub const AU_AUDIO_AIFF: i32 = 7;
+pub const AU_AUDIO_AMR_NB: i32 = 8;
+pub const AU_AUDIO_AMR_WB: i32 = 9;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AuAdtsHeader {
+    /// ADTS profile: 0 Main, 1 LC, 2 SSR, 3 LTP.
+    pub profile: u8,
+    pub sample_rate: u32,
+    /// Channel configuration; 0 means it is signalled in the payload.
+    pub channels: u8,
+    /// 1 when the header carries a CRC.
+    pub has_crc: u8,
+    pub header_length: u32,
+    /// Frame length, header included.
+    pub frame_length: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AuFlacFrameInfo {
+    pub is_var_size: u8,
+    pub block_size: u16,
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub ch_mode: u8,
+    pub bps: u8,
+    pub frame_or_sample_num: u64,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: &str) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+// Runs `f` with the input as a slice, turning null pointers and panics
+// into AU_ERROR.
+unsafe fn guarded(data: *const u8, len: usize, f: impl FnOnce(&[u8]) -> i32) -> i32 {
+    let input = if len == 0 {
+        &[][..]
+    } else if data.is_null() {
+        set_last_error("null data pointer");
+        return AU_ERROR;
+    } else {
+        std::slice::from_raw_parts(data, len)
+    };
+
+    match catch_unwind(AssertUnwindSafe(|| f(input))) {
+        Ok(code) => code,
+        Err(_) => {
+            set_last_error("panic while parsing input");
+            AU_ERROR
+        }
+    }
+}
+
+/// Detects the audio format of `len` bytes at `data`, returning one of the
+/// `AU_AUDIO_*` codes, or `AU_ERROR`.
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes, or `len` must be zero.
+#[no_mangle]
+pub unsafe extern "C" fn au_detect_audio(data: *const u8, len: usize) -> i32 {
+    guarded(data, len, |input| match detect_audio(input) {
+        AudioType::Unknown => AU_AUDIO_UNKNOWN,
+        AudioType::AAC => AU_AUDIO_AAC,
+        AudioType::FLAC => AU_AUDIO_FLAC,
+        AudioType::Opus => AU_AUDIO_OPUS,
+        AudioType::AC3 => AU_AUDIO_AC3,
+        AudioType::EAC3 => AU_AUDIO_EAC3,
+        AudioType::Vorbis => AU_AUDIO_VORBIS,
+        AudioType::AIFF => AU_AUDIO_AIFF,
+        AudioType::AmrNb => AU_AUDIO_AMR_NB,
+        AudioType::AmrWb => AU_AUDIO_AMR_WB,
+    })
+}
+
+/// Parses the ADTS header at `data` into `out`.
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes, or `len` must be zero,
+/// and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn au_aac_parse_adts(
+    data: *const u8,
+    len: usize,
+    out: *mut AuAdtsHeader,
+) -> i32 {
+    if out.is_null() {
+        set_last_error("null output pointer");
+        return AU_ERROR;
+    }
+    guarded(data, len, |input| match aac::parse_adts_header(input) {
+        Ok(header) => {
+            out.write(AuAdtsHeader {
+                profile: header.profile,
+                sample_rate: header.sample_rate,
+                channels: header.channel_configuration,
+                has_crc: !header.protection_absent as u8,
+                header_length: header.header_length() as u32,
+                frame_length: header.frame_length as u32,
+            });
+            AU_OK
+        }
+        Err(err) => {
+            set_last_error(&err.to_string());
+            AU_ERROR
+        }
+    })
+}
+
+/// Decodes the FLAC frame header at `data` into `out`.
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes, or `len` must be zero,
+/// and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn au_flac_decode_header(
+    data: *const u8,
+    len: usize,
+    out: *mut AuFlacFrameInfo,
+) -> i32 {
+    if out.is_null() {
+        set_last_error("null output pointer");
+        return AU_ERROR;
+    }
+    guarded(data, len, |input| match flac::decode_frame_header(input) {
+        Ok(fi) => {
+            out.write(AuFlacFrameInfo {
+                is_var_size: fi.is_var_size as u8,
+                block_size: fi.block_size,
+                sample_rate: fi.sample_rate,
+                channels: fi.channels,
+                ch_mode: fi.ch_mode,
+                bps: fi.bps,
+                frame_or_sample_num: fi.frame_or_sample_num,
+            });
+            AU_OK
+        }
+        Err(err) => {
+            set_last_error(&err.to_string());
+            AU_ERROR
+        }
+    })
+}
+
+/// The message for the last error on this thread, or null if there was
+/// none. The string stays valid until the next failing call on the thread.
+#[no_mangle]
+pub extern "C" fn au_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn last_error() -> String {
+        let message = au_last_error_message();
+        assert!(!message.is_null());
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_detect_audio() {
+        let mut adts =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 0, false).unwrap();
+        unsafe {
+            assert_eq!(au_detect_audio(adts.as_ptr(), adts.len()), AU_AUDIO_AAC);
+            assert_eq!(au_detect_audio(std::ptr::null(), 0), AU_AUDIO_UNKNOWN);
+            assert_eq!(au_detect_audio(std::ptr::null(), 4), AU_ERROR);
+        }
+        assert_eq!(last_error(), "null data pointer");
+
+        // Only the given length is read
+        adts.truncate(7);
+        unsafe {
+            assert_eq!(au_detect_audio(adts.as_ptr(), 1), AU_AUDIO_UNKNOWN);
+        }
+    }
+
+    #[test]
+    fn test_parse_adts() {
+        let adts =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 48000, 100, true).unwrap();
+        let mut header = AuAdtsHeader::default();
+        unsafe {
+            assert_eq!(
+                au_aac_parse_adts(adts.as_ptr(), adts.len(), &mut header),
+                AU_OK
+            );
+        }
+        assert_eq!(
+            header,
+            AuAdtsHeader {
+                profile: 1,
+                sample_rate: 48000,
+                channels: 2,
+                has_crc: 1,
+                header_length: 9,
+                frame_length: 109,
+            }
+        );
+
+        unsafe {
+            assert_eq!(au_aac_parse_adts(adts.as_ptr(), 6, &mut header), AU_ERROR);
+            assert_eq!(
+                au_aac_parse_adts(adts.as_ptr(), adts.len(), std::ptr::null_mut()),
+                AU_ERROR
+            );
+        }
+    }
+
+    #[test]
+    fn test_flac_decode_header() {
+        let header = [0xFF, 0xF8, 0xC9, 0xA8, 0x00, 0x8D, 0x4E];
+        let mut info = AuFlacFrameInfo::default();
+        unsafe {
+            assert_eq!(
+                au_flac_decode_header(header.as_ptr(), header.len(), &mut info),
+                AU_OK
+            );
+        }
+        assert_eq!(info.block_size, 4096);
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.channels, 2);
+
+        unsafe {
+            assert_eq!(
+                au_flac_decode_header(header.as_ptr(), 3, &mut info),
+                AU_ERROR
+            );
+        }
+        assert_eq!(last_error(), "Unexpected end of input");
+    }
+}
@@ -0,0 +1,356 @@
+//! Segment-level helpers for [`Fmp4`]: telling init segments from media
+//! segments, cutting a CMAF byte stream into segments, and joining them
+//! back up.
+
+use crate::timing::{box_spans, top_level_boxes};
+use crate::Fmp4;
+use bytes::Bytes;
+
+// Sample flags, ISO/IEC 14496-12 8.8.3.1
+const SAMPLE_IS_NON_SYNC: u32 = 0x0001_0000;
+const DOES_NOT_DEPEND_ON_OTHERS: u32 = 2;
+
+// trun flags
+const DATA_OFFSET_PRESENT: u32 = 0x001;
+const FIRST_SAMPLE_FLAGS_PRESENT: u32 = 0x004;
+const SAMPLE_DURATION_PRESENT: u32 = 0x100;
+const SAMPLE_SIZE_PRESENT: u32 = 0x200;
+const SAMPLE_FLAGS_PRESENT: u32 = 0x400;
+const SAMPLE_CTO_PRESENT: u32 = 0x800;
+
+// tfhd flags
+const BASE_DATA_OFFSET_PRESENT: u32 = 0x01;
+const SAMPLE_DESCRIPTION_INDEX_PRESENT: u32 = 0x02;
+const DEFAULT_SAMPLE_DURATION_PRESENT: u32 = 0x08;
+const DEFAULT_SAMPLE_SIZE_PRESENT: u32 = 0x10;
+const DEFAULT_SAMPLE_FLAGS_PRESENT: u32 = 0x20;
+
+impl Fmp4 {
+    /// True for an init segment: `ftyp` and `moov` with no `moof`.
+    pub fn is_init_segment(data: &[u8]) -> bool {
+        let mut ftyp = false;
+        let mut moov = false;
+        for (box_type, _) in box_spans(data) {
+            match &box_type {
+                b"ftyp" => ftyp = true,
+                b"moov" => moov = true,
+                b"moof" => return false,
+                _ => {}
+            }
+        }
+        ftyp && moov
+    }
+
+    /// Cuts a CMAF byte stream into media segments. A segment starts at
+    /// each `styp`, or at a `moof` not directly preceded by one, and runs to
+    /// the next. Whatever comes before the first segment is the init
+    /// segment, shared by every result.
+    ///
+    /// `duration` is the sum of the first track fragment's sample
+    /// durations, in that track's timescale, and `key` is set when its
+    /// first sample does not depend on others. Both fall back to the
+    /// `tfhd` and then `trex` defaults. Parsing stops at a malformed box.
+    pub fn split_segments(data: &[u8]) -> Vec<Fmp4> {
+        let data = Bytes::copy_from_slice(data);
+        let mut starts = Vec::new();
+        let mut previous = None;
+        for (box_type, span) in box_spans(&data) {
+            let starts_segment = match &box_type {
+                b"styp" => true,
+                b"moof" => previous != Some(*b"styp"),
+                _ => false,
+            };
+            if starts_segment {
+                starts.push(span.start);
+            }
+            previous = Some(box_type);
+        }
+        let Some(&first) = starts.first() else {
+            return Vec::new();
+        };
+        let end = box_spans(&data).last().map_or(0, |(_, span)| span.end);
+
+        let init = (first > 0).then(|| data.slice(..first));
+        let defaults = init
+            .as_deref()
+            .and_then(|init| find(init, b"moov"))
+            .map(track_defaults)
+            .unwrap_or_default();
+
+        starts
+            .iter()
+            .zip(starts.iter().skip(1).copied().chain([end]))
+            .map(|(&start, end)| {
+                let segment = data.slice(start..end);
+                let (duration, key) = find(&segment, b"moof")
+                    .and_then(|moof| fragment_timing(moof, &defaults))
+                    .unwrap_or((0, true));
+                Fmp4 {
+                    init: init.clone(),
+                    key,
+                    data: segment,
+                    duration,
+                }
+            })
+            .collect()
+    }
+
+    /// `self` followed by `others` as one playable stream, led by the
+    /// first init segment any of them carries.
+    pub fn concat(&self, others: &[Fmp4]) -> Bytes {
+        let segments = || std::iter::once(self).chain(others);
+        let init = segments().find_map(|segment| segment.init.as_ref());
+
+        let len = init.map_or(0, |init| init.len())
+            + segments().map(|segment| segment.data.len()).sum::<usize>();
+        let mut out = Vec::with_capacity(len);
+        if let Some(init) = init {
+            out.extend_from_slice(init);
+        }
+        for segment in segments() {
+            out.extend_from_slice(&segment.data);
+        }
+        Bytes::from(out)
+    }
+}
+
+fn find<'a>(data: &'a [u8], box_type: &[u8; 4]) -> Option<&'a [u8]> {
+    top_level_boxes(data)
+        .find(|(found, _)| found == box_type)
+        .map(|(_, body)| body)
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SampleDefaults {
+    duration: u32,
+    flags: u32,
+}
+
+// trex defaults by track ID.
+fn track_defaults(moov: &[u8]) -> Vec<(u32, SampleDefaults)> {
+    let Some(mvex) = find(moov, b"mvex") else {
+        return Vec::new();
+    };
+    top_level_boxes(mvex)
+        .filter(|(box_type, _)| box_type == b"trex")
+        .filter_map(|(_, trex)| {
+            let defaults = SampleDefaults {
+                duration: u32_at(trex, 12)?,
+                flags: u32_at(trex, 20)?,
+            };
+            Some((u32_at(trex, 4)?, defaults))
+        })
+        .collect()
+}
+
+// Duration and key flag of the first track fragment in a moof.
+fn fragment_timing(moof: &[u8], trex: &[(u32, SampleDefaults)]) -> Option<(u32, bool)> {
+    let traf = find(moof, b"traf")?;
+    let tfhd = find(traf, b"tfhd")?;
+    let tfhd_flags = u32_at(tfhd, 0)? & 0x00FF_FFFF;
+    let track_id = u32_at(tfhd, 4)?;
+
+    let mut defaults = trex
+        .iter()
+        .find(|(id, _)| *id == track_id)
+        .map(|&(_, defaults)| defaults)
+        .unwrap_or_default();
+    let mut pos = 8;
+    for (flag, len) in [
+        (BASE_DATA_OFFSET_PRESENT, 8),
+        (SAMPLE_DESCRIPTION_INDEX_PRESENT, 4),
+    ] {
+        if tfhd_flags & flag != 0 {
+            pos += len;
+        }
+    }
+    if tfhd_flags & DEFAULT_SAMPLE_DURATION_PRESENT != 0 {
+        defaults.duration = u32_at(tfhd, pos)?;
+        pos += 4;
+    }
+    if tfhd_flags & DEFAULT_SAMPLE_SIZE_PRESENT != 0 {
+        pos += 4;
+    }
+    if tfhd_flags & DEFAULT_SAMPLE_FLAGS_PRESENT != 0 {
+        defaults.flags = u32_at(tfhd, pos)?;
+    }
+
+    let mut duration = 0u32;
+    let mut first_flags = None;
+    for (_, trun) in top_level_boxes(traf).filter(|(box_type, _)| box_type == b"trun") {
+        let flags = u32_at(trun, 0)? & 0x00FF_FFFF;
+        let sample_count = u32_at(trun, 4)?;
+        let mut pos = 8;
+        if flags & DATA_OFFSET_PRESENT != 0 {
+            pos += 4;
+        }
+        if flags & FIRST_SAMPLE_FLAGS_PRESENT != 0 {
+            first_flags = first_flags.or(Some(u32_at(trun, pos)?));
+            pos += 4;
+        }
+
+        let per_sample = SAMPLE_DURATION_PRESENT
+            | SAMPLE_SIZE_PRESENT
+            | SAMPLE_FLAGS_PRESENT
+            | SAMPLE_CTO_PRESENT;
+        if flags & per_sample == 0 {
+            // Nothing to read per sample, so the count alone may be huge
+            duration = duration.saturating_add(sample_count.saturating_mul(defaults.duration));
+            first_flags = first_flags.or(Some(defaults.flags));
+            continue;
+        }
+
+        for _ in 0..sample_count {
+            let mut field = |flag: u32| -> Option<Option<u32>> {
+                if flags & flag == 0 {
+                    return Some(None);
+                }
+                let value = u32_at(trun, pos)?;
+                pos += 4;
+                Some(Some(value))
+            };
+            let sample_duration = field(SAMPLE_DURATION_PRESENT)?;
+            field(SAMPLE_SIZE_PRESENT)?;
+            let sample_flags = field(SAMPLE_FLAGS_PRESENT)?;
+            field(SAMPLE_CTO_PRESENT)?;
+
+            duration = duration.saturating_add(sample_duration.unwrap_or(defaults.duration));
+            first_flags = first_flags.or(Some(sample_flags.unwrap_or(defaults.flags)));
+        }
+    }
+
+    let first_flags = first_flags.unwrap_or(defaults.flags);
+    let depends_on = (first_flags >> 24) & 0x03;
+    let key = depends_on == DOES_NOT_DEPEND_ON_OTHERS
+        || (depends_on == 0 && first_flags & SAMPLE_IS_NON_SYNC == 0);
+    Some((duration, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(box_type: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut b = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(box_type);
+        b.extend_from_slice(body);
+        b
+    }
+
+    fn words(flags: u32, fields: &[u32]) -> Vec<u8> {
+        std::iter::once(flags)
+            .chain(fields.iter().copied())
+            .flat_map(u32::to_be_bytes)
+            .collect()
+    }
+
+    fn init_segment() -> Vec<u8> {
+        // trex: track 1, description 1, 960 ticks, size 0, flags 0
+        let trex = mp4_box(b"trex", &words(0, &[1, 1, 960, 0, 0]));
+        let mut init = mp4_box(b"ftyp", b"iso6\x00\x00\x00\x00cmfc");
+        init.extend(mp4_box(b"moov", &mp4_box(b"mvex", &trex)));
+        init
+    }
+
+    fn media_segment(styp: bool, tfhd: &[u8], trun: &[u8]) -> Vec<u8> {
+        let mut traf = mp4_box(b"tfhd", tfhd);
+        traf.extend(mp4_box(b"trun", trun));
+        let mut moof = mp4_box(b"mfhd", &words(0, &[1]));
+        moof.extend(mp4_box(b"traf", &traf));
+
+        let mut segment = if styp {
+            mp4_box(b"styp", b"msdh\x00\x00\x00\x00msdhmsix")
+        } else {
+            Vec::new()
+        };
+        segment.extend(mp4_box(b"moof", &moof));
+        segment.extend(mp4_box(b"mdat", &[0xAA; 12]));
+        segment
+    }
+
+    // Three segments: tfhd default duration with a sync first sample,
+    // per-sample durations starting on a non-sync sample, and trex
+    // defaults only.
+    fn cmaf_stream() -> Vec<u8> {
+        let mut stream = init_segment();
+        stream.extend(media_segment(
+            true,
+            &words(DEFAULT_SAMPLE_DURATION_PRESENT, &[1, 1024]),
+            &words(
+                DATA_OFFSET_PRESENT | FIRST_SAMPLE_FLAGS_PRESENT,
+                &[3, 0, 0x0200_0000],
+            ),
+        ));
+        stream.extend(media_segment(
+            false,
+            &words(0, &[1]),
+            &words(
+                SAMPLE_DURATION_PRESENT | SAMPLE_SIZE_PRESENT | SAMPLE_FLAGS_PRESENT,
+                &[2, 1000, 4, 0x0101_0000, 1001, 8, 0x0200_0000],
+            ),
+        ));
+        stream.extend(media_segment(true, &words(0, &[1]), &words(0, &[2])));
+        stream
+    }
+
+    #[test]
+    fn test_is_init_segment() {
+        assert!(Fmp4::is_init_segment(&init_segment()));
+        assert!(!Fmp4::is_init_segment(&cmaf_stream()));
+        assert!(!Fmp4::is_init_segment(&mp4_box(b"ftyp", b"iso6")));
+        assert!(!Fmp4::is_init_segment(&[]));
+    }
+
+    #[test]
+    fn test_split_segments() {
+        let stream = cmaf_stream();
+        let init = init_segment();
+        let segments = Fmp4::split_segments(&stream);
+        assert_eq!(segments.len(), 3);
+
+        let summary: Vec<(u32, bool)> = segments
+            .iter()
+            .map(|segment| (segment.duration, segment.key))
+            .collect();
+        assert_eq!(summary, [(3072, true), (2001, false), (1920, true)]);
+
+        assert!(segments[0]
+            .data
+            .starts_with(&stream[init.len()..init.len() + 8]));
+        assert_eq!(&segments[1].data[4..8], b"moof");
+        for segment in &segments {
+            assert_eq!(segment.init.as_deref(), Some(&init[..]));
+        }
+
+        assert!(Fmp4::split_segments(&init).is_empty());
+    }
+
+    #[test]
+    fn test_concat_restores_stream() {
+        let stream = cmaf_stream();
+        let segments = Fmp4::split_segments(&stream);
+        assert_eq!(segments[0].concat(&segments[1..]), stream);
+
+        // The init segment appears once even when only a later one has it
+        let mut bare = segments[0].clone();
+        bare.init = None;
+        let joined = bare.concat(&segments[1..2]);
+        assert!(joined.starts_with(&init_segment()));
+        assert_eq!(
+            joined.len(),
+            init_segment().len() + segments[0].data.len() + segments[1].data.len()
+        );
+
+        // Without any init segment the media is passed through
+        let media_only = Fmp4::split_segments(&stream[init_segment().len()..]);
+        assert!(media_only[0].init.is_none());
+        assert_eq!(
+            media_only[0].concat(&media_only[1..]),
+            &stream[init_segment().len()..]
+        );
+    }
+}
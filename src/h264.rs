@@ -1,3 +1,5 @@
+use bytes::Bytes;
+
 pub fn is_nalu(data: &[u8]) -> bool {
     if data.len() < 3 {
         return false;
@@ -10,6 +12,156 @@ pub fn is_nalu(data: &[u8]) -> bool {
     })
 }
 
+/// One `cc_data` triplet from an ATSC A/53 caption payload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CcTriplet {
+    pub cc_valid: bool,
+    /// 0 and 1 are CEA-608 fields 1 and 2, 2 and 3 are CEA-708 DTVCC packets.
+    pub cc_type: u8,
+    pub data: [u8; 2],
+}
+
+impl CcTriplet {
+    pub fn is_cea608(&self) -> bool {
+        self.cc_type < 2
+    }
+}
+
+pub(crate) const SEI_NAL_TYPE: u8 = 6;
+const SEI_USER_DATA_REGISTERED_ITU_T_T35: u32 = 4;
+
+/// Splits an Annex B byte stream into NAL units, start codes excluded.
+pub fn split_nalus(data: &[u8]) -> Vec<&[u8]> {
+    let mut starts = Vec::new();
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i] == 0x00 && data[i + 1] == 0x00 && data[i + 2] == 0x01 {
+            starts.push((i, i + 3));
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+
+    let mut nalus = Vec::with_capacity(starts.len());
+    for (n, &(_, start)) in starts.iter().enumerate() {
+        let mut end = starts.get(n + 1).map_or(data.len(), |&(next, _)| next);
+        // The leading zero of a four-byte start code belongs to the next one
+        while end > start && data[end - 1] == 0x00 {
+            end -= 1;
+        }
+        if end > start {
+            nalus.push(&data[start..end]);
+        }
+    }
+    nalus
+}
+
+/// Extracts CEA-608/708 caption data carried in SEI
+/// user_data_registered_itu_t_t35 messages of an Annex B access unit.
+pub fn extract_captions(data: &[u8]) -> Vec<CcTriplet> {
+    split_nalus(data)
+        .into_iter()
+        .filter(|nalu| nalu[0] & 0x1F == SEI_NAL_TYPE)
+        .flat_map(|nalu| caption_triplets(&remove_emulation_prevention(&nalu[1..])))
+        .collect()
+}
+
+/// Returns the SEI NAL units of an access unit that carry captions,
+/// unchanged and without copying, so they can be passed through when
+/// repackaging.
+pub fn caption_sei_nalus(data: &Bytes) -> Vec<Bytes> {
+    split_nalus(data)
+        .into_iter()
+        .filter(|nalu| {
+            nalu[0] & 0x1F == SEI_NAL_TYPE
+                && !caption_triplets(&remove_emulation_prevention(&nalu[1..])).is_empty()
+        })
+        .map(|nalu| data.slice_ref(nalu))
+        .collect()
+}
+
+fn remove_emulation_prevention(data: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(data.len());
+    let mut zeros = 0;
+    for &byte in data {
+        if zeros >= 2 && byte == 0x03 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0x00 { zeros + 1 } else { 0 };
+        rbsp.push(byte);
+    }
+    rbsp
+}
+
+fn caption_triplets(sei: &[u8]) -> Vec<CcTriplet> {
+    let mut triplets = Vec::new();
+    let mut pos = 0;
+
+    // Stop at the rbsp trailing bits
+    while pos < sei.len() && sei[pos] != 0x80 {
+        let Some(payload_type) = read_sei_value(sei, &mut pos) else {
+            break;
+        };
+        let Some(payload_size) = read_sei_value(sei, &mut pos) else {
+            break;
+        };
+        let Some(end) = pos.checked_add(payload_size as usize) else {
+            break;
+        };
+        let Some(payload) = sei.get(pos..end) else {
+            break;
+        };
+        pos = end;
+
+        if payload_type == SEI_USER_DATA_REGISTERED_ITU_T_T35 {
+            triplets.extend(atsc_cc_data(payload));
+        }
+    }
+
+    triplets
+}
+
+fn read_sei_value(data: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut value = 0u32;
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value = value.saturating_add(byte as u32);
+        if byte != 0xFF {
+            return Some(value);
+        }
+    }
+}
+
+// ATSC A/53 cc_data() inside a GA94 itu_t_t35 payload.
+fn atsc_cc_data(payload: &[u8]) -> Vec<CcTriplet> {
+    const ATSC_HEADER: [u8; 8] = [0xB5, 0x00, 0x31, b'G', b'A', b'9', b'4', 0x03];
+
+    if payload.len() < ATSC_HEADER.len() + 2 || payload[..ATSC_HEADER.len()] != ATSC_HEADER {
+        return Vec::new();
+    }
+
+    let flags = payload[ATSC_HEADER.len()];
+    let process_cc_data = flags & 0x40 != 0;
+    if !process_cc_data {
+        return Vec::new();
+    }
+
+    let cc_count = (flags & 0x1F) as usize;
+    payload[ATSC_HEADER.len() + 2..]
+        .chunks_exact(3)
+        .take(cc_count)
+        .map(|triplet| CcTriplet {
+            cc_valid: triplet[0] & 0x04 != 0,
+            cc_type: triplet[0] & 0x03,
+            data: [triplet[1], triplet[2]],
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -45,4 +197,53 @@ mod tests {
         assert!(!is_nalu(&[0xFF, 0x00, 0x00]));
         assert!(!is_nalu(&[0xFF, 0x00, 0x00, 0x00]));
     }
+
+    #[test]
+    fn test_extract_captions() {
+        let sei = [
+            0x06, // SEI
+            0x04, 0x11, // user_data_registered_itu_t_t35, 17 bytes
+            0xB5, 0x00, 0x31, b'G', b'A', b'9', b'4', 0x03, // ATSC GA94 cc_data
+            0xC2, 0xFF, // process_cc_data_flag, cc_count = 2, em_data
+            0xFC, 0x94, 0x20, // 608 field 1: resume caption loading
+            0xFE, 0x41, 0x42, // 708 DTVCC
+            0xFF, // marker bits
+            0x80, // rbsp trailing bits
+        ];
+        let mut au = vec![0x00, 0x00, 0x00, 0x01, 0x09, 0xF0];
+        au.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+        au.extend_from_slice(&sei);
+        au.extend_from_slice(&[0x00, 0x00, 0x01, 0x65, 0x88, 0x84]);
+
+        let captions = extract_captions(&au);
+        assert_eq!(
+            captions,
+            vec![
+                CcTriplet {
+                    cc_valid: true,
+                    cc_type: 0,
+                    data: [0x94, 0x20],
+                },
+                CcTriplet {
+                    cc_valid: true,
+                    cc_type: 2,
+                    data: [0x41, 0x42],
+                },
+            ]
+        );
+        assert!(captions[0].is_cea608());
+        assert!(!captions[1].is_cea608());
+
+        let au = Bytes::from(au);
+        let passthrough = caption_sei_nalus(&au);
+        assert_eq!(passthrough, vec![Bytes::copy_from_slice(&sei)]);
+    }
+
+    #[test]
+    fn test_remove_emulation_prevention() {
+        assert_eq!(
+            remove_emulation_prevention(&[0x00, 0x00, 0x03, 0x01, 0x00, 0x00, 0x03, 0x03]),
+            vec![0x00, 0x00, 0x01, 0x00, 0x00, 0x03]
+        );
+    }
 }
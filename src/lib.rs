@@ -1,41 +1,2626 @@
+use crate::time::Ticks90k;
 use bytes::Bytes;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
 
 pub mod aac;
+pub mod ac3;
+pub mod aiff;
+pub mod amr;
+pub mod bits;
+#[cfg(feature = "serde")]
+mod bytes_serde;
+pub mod caf;
+pub mod duration;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod flac;
+mod fmp4;
 pub mod h264;
+pub mod ogg;
+pub mod opus;
+pub mod package;
+pub mod probe;
+pub mod reorder;
+pub mod sanitize;
+pub mod stream;
+pub mod synth;
+pub mod time;
+pub mod timing;
+pub mod video;
+pub mod vorbis;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(test)]
+mod testutil;
+
+pub use probe::{probe, AudioProbe};
+
+/// MPEG-TS PSI stream types known to this crate. Any other value is kept
+/// in [`StreamType::Other`], so converting a `u8` never loses information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StreamType {
+    MP3,
+    PrivateData,
+    /// AAC in ADTS framing.
+    AAC,
+    H264,
+    /// AAC in LATM framing.
+    Mpeg4AAC,
+    Opus,
+    /// ATSC A/52 AC-3.
+    AC3,
+    /// ATSC A/52 E-AC-3.
+    EAC3,
+    /// A stream type without a variant. [`From<u8>`] never produces this
+    /// for a value that has one.
+    Other(u8),
+}
+
+impl StreamType {
+    pub const fn as_u8(self) -> u8 {
+        match self {
+            StreamType::MP3 => 0x04,
+            StreamType::PrivateData => 0x06,
+            StreamType::AAC => 0x0f,
+            StreamType::H264 => 0x1b,
+            StreamType::Mpeg4AAC => 0x1c,
+            StreamType::Opus => 0x9c,
+            StreamType::AC3 => 0x81,
+            StreamType::EAC3 => 0x87,
+            StreamType::Other(stream_type) => stream_type,
+        }
+    }
+}
+
+impl From<StreamType> for u8 {
+    fn from(stream_type: StreamType) -> u8 {
+        stream_type.as_u8()
+    }
+}
+
+impl From<u8> for StreamType {
+    fn from(stream_type: u8) -> StreamType {
+        match stream_type {
+            0x04 => StreamType::MP3,
+            0x06 => StreamType::PrivateData,
+            0x0f => StreamType::AAC,
+            0x1b => StreamType::H264,
+            0x1c => StreamType::Mpeg4AAC,
+            0x9c => StreamType::Opus,
+            0x81 => StreamType::AC3,
+            0x87 => StreamType::EAC3,
+            other => StreamType::Other(other),
+        }
+    }
+}
+
+#[deprecated(note = "use StreamType::MP3")]
+pub const PSI_STREAM_MP3: u8 = StreamType::MP3.as_u8();
+#[deprecated(note = "use StreamType::PrivateData")]
+pub const PSI_STREAM_PRIVATE_DATA: u8 = StreamType::PrivateData.as_u8();
+#[deprecated(note = "use StreamType::AAC")]
+pub const PSI_STREAM_AAC: u8 = StreamType::AAC.as_u8();
+#[deprecated(note = "use StreamType::H264")]
+pub const PSI_STREAM_H264: u8 = StreamType::H264.as_u8();
+#[deprecated(note = "use StreamType::Mpeg4AAC")]
+pub const PSI_STREAM_MPEG4_AAC: u8 = StreamType::Mpeg4AAC.as_u8();
+#[deprecated(note = "use StreamType::Opus")]
+pub const PSI_STREAM_AUDIO_OPUS: u8 = StreamType::Opus.as_u8();
+#[deprecated(note = "use StreamType::AC3")]
+pub const PSI_STREAM_AC3: u8 = StreamType::AC3.as_u8();
+#[deprecated(note = "use StreamType::EAC3")]
+pub const PSI_STREAM_EAC3: u8 = StreamType::EAC3.as_u8();
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AudioType {
     Unknown,
     AAC,
     FLAC,
     Opus,
+    AC3,
+    EAC3,
+    Vorbis,
+    /// PCM in an AIFF or AIFF-C file.
+    AIFF,
+    AmrNb,
+    AmrWb,
+}
+
+/// Stream parameters that refine [`AudioType::mime_with_codecs`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CodecParams {
+    /// MPEG-4 audio object type: 2 for AAC-LC, 5 for HE-AAC, 29 for
+    /// HE-AAC v2. AAC-LC is assumed when unset.
+    pub aac_object_type: Option<u8>,
+}
+
+impl AudioType {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            AudioType::Unknown => "application/octet-stream",
+            AudioType::AAC => "audio/aac",
+            AudioType::FLAC => "audio/flac",
+            AudioType::Opus | AudioType::Vorbis => "audio/ogg",
+            AudioType::AC3 => "audio/ac3",
+            AudioType::EAC3 => "audio/eac3",
+            AudioType::AIFF => "audio/aiff",
+            AudioType::AmrNb => "audio/amr",
+            AudioType::AmrWb => "audio/amr-wb",
+        }
+    }
+
+    /// RFC 6381 codec string, as used in MSE and HLS `CODECS` attributes.
+    /// AAC is reported as AAC-LC.
+    pub fn codec_string(&self) -> Option<&'static str> {
+        match self {
+            AudioType::Unknown | AudioType::AIFF => None,
+            AudioType::AAC => Some("mp4a.40.2"),
+            AudioType::FLAC => Some("flac"),
+            AudioType::Opus => Some("opus"),
+            AudioType::AC3 => Some("ac-3"),
+            AudioType::EAC3 => Some("ec-3"),
+            AudioType::Vorbis => Some("vorbis"),
+            AudioType::AmrNb => Some("samr"),
+            AudioType::AmrWb => Some("sawb"),
+        }
+    }
+
+    /// The PSI stream type that carries this codec in MPEG-TS. AAC maps to
+    /// the ADTS type 0x0f, since that is the framing this crate produces.
+    pub fn to_psi_stream_type(&self) -> Option<u8> {
+        match self {
+            AudioType::AAC => Some(StreamType::AAC.into()),
+            AudioType::Opus => Some(StreamType::Opus.into()),
+            AudioType::AC3 => Some(StreamType::AC3.into()),
+            AudioType::EAC3 => Some(StreamType::EAC3.into()),
+            _ => None,
+        }
+    }
+
+    /// The audio codec of a PSI stream type. Both ADTS (0x0f) and LATM
+    /// (0x1c) AAC give [`AudioType::AAC`], so the mapping does not round
+    /// trip for 0x1c.
+    pub fn from_psi_stream_type(stream_type: u8) -> Option<AudioType> {
+        match StreamType::from(stream_type) {
+            StreamType::AAC | StreamType::Mpeg4AAC => Some(AudioType::AAC),
+            StreamType::Opus => Some(AudioType::Opus),
+            StreamType::AC3 => Some(AudioType::AC3),
+            StreamType::EAC3 => Some(AudioType::EAC3),
+            _ => None,
+        }
+    }
+
+    /// The MIME type with a `codecs` parameter when there is a codec
+    /// string, e.g. `audio/aac; codecs="mp4a.40.5"`.
+    pub fn mime_with_codecs(&self, params: Option<&CodecParams>) -> String {
+        let aac_object_type = params.and_then(|params| params.aac_object_type);
+        let codec = match (self, aac_object_type) {
+            (AudioType::AAC, Some(object_type)) => format!("mp4a.40.{}", object_type),
+            _ => match self.codec_string() {
+                Some(codec) => codec.to_string(),
+                None => return self.mime_type().to_string(),
+            },
+        };
+        format!("{}; codecs=\"{}\"", self.mime_type(), codec)
+    }
+}
+
+/// The codec named by a PSI stream type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Codec {
+    Audio(AudioType),
+    Video(video::VideoType),
+}
+
+impl Codec {
+    pub fn from_psi_stream_type(stream_type: u8) -> Option<Codec> {
+        AudioType::from_psi_stream_type(stream_type)
+            .map(Codec::Audio)
+            .or_else(|| video::VideoType::from_psi_stream_type(stream_type).map(Codec::Video))
+    }
+
+    pub fn to_psi_stream_type(&self) -> Option<u8> {
+        match self {
+            Codec::Audio(audio_type) => audio_type.to_psi_stream_type(),
+            Codec::Video(video_type) => video_type.to_psi_stream_type(),
+        }
+    }
+}
+
+impl From<AudioType> for Codec {
+    fn from(audio_type: AudioType) -> Self {
+        Codec::Audio(audio_type)
+    }
+}
+
+impl From<video::VideoType> for Codec {
+    fn from(video_type: video::VideoType) -> Self {
+        Codec::Video(video_type)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ContainerType {
+    Ogg,
+    WebM,
+    Mp4,
+    Wav,
+    MpegTs,
+    Caf,
+}
+
+/// Returned when parsing an [`AudioType`], [`ContainerType`] or
+/// [`video::VideoType`] from an unrecognised name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseTypeError(pub String);
+
+impl fmt::Display for ParseTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown type name: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseTypeError {}
+
+impl fmt::Display for AudioType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AudioType::Unknown => "unknown",
+            AudioType::AAC => "aac",
+            AudioType::FLAC => "flac",
+            AudioType::Opus => "opus",
+            AudioType::AC3 => "ac3",
+            AudioType::EAC3 => "eac3",
+            AudioType::Vorbis => "vorbis",
+            AudioType::AIFF => "aiff",
+            AudioType::AmrNb => "amr-nb",
+            AudioType::AmrWb => "amr-wb",
+        })
+    }
 }
 
-#[derive(Debug, Clone)]
+/// Accepts the [`Display`](fmt::Display) names in any case, along with
+/// common aliases and file extensions.
+impl FromStr for AudioType {
+    type Err = ParseTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "unknown" => Ok(AudioType::Unknown),
+            "aac" | "adts" | "m4a" => Ok(AudioType::AAC),
+            "flac" | "fla" => Ok(AudioType::FLAC),
+            "opus" => Ok(AudioType::Opus),
+            "ac3" | "ac-3" => Ok(AudioType::AC3),
+            "eac3" | "ec-3" | "e-ac-3" | "ec3" => Ok(AudioType::EAC3),
+            "vorbis" | "oga" => Ok(AudioType::Vorbis),
+            "aiff" | "aif" | "aifc" => Ok(AudioType::AIFF),
+            "amr-nb" | "amr" => Ok(AudioType::AmrNb),
+            "amr-wb" | "awb" => Ok(AudioType::AmrWb),
+            _ => Err(ParseTypeError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ContainerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ContainerType::Ogg => "ogg",
+            ContainerType::WebM => "webm",
+            ContainerType::Mp4 => "mp4",
+            ContainerType::Wav => "wav",
+            ContainerType::MpegTs => "mpegts",
+            ContainerType::Caf => "caf",
+        })
+    }
+}
+
+impl FromStr for ContainerType {
+    type Err = ParseTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ogg" | "oga" | "ogx" => Ok(ContainerType::Ogg),
+            "webm" | "mka" | "mkv" | "matroska" => Ok(ContainerType::WebM),
+            "mp4" | "m4a" | "m4v" | "mov" => Ok(ContainerType::Mp4),
+            "wav" | "wave" => Ok(ContainerType::Wav),
+            "mpegts" | "ts" | "m2ts" => Ok(ContainerType::MpegTs),
+            "caf" => Ok(ContainerType::Caf),
+            _ => Err(ParseTypeError(s.to_string())),
+        }
+    }
+}
+
+/// What [`detect_media`] found: the container, if any, and the audio codec
+/// when it could be identified.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MediaFormat {
+    pub container: Option<ContainerType>,
+    pub codec: Option<AudioType>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Fmp4 {
+    #[cfg_attr(feature = "serde", serde(with = "bytes_serde::option"))]
     pub init: Option<Bytes>,
     pub key: bool,
+    #[cfg_attr(feature = "serde", serde(with = "bytes_serde"))]
     pub data: Bytes,
     pub duration: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AccessUnit {
     pub key: bool,
     pub pts: u64,
     pub dts: u64,
+    #[cfg_attr(feature = "serde", serde(with = "bytes_serde"))]
     pub data: Bytes,
     pub stream_type: u8,
     pub id: u64,
 }
 
+// Payloads are summarised in Debug output so that logging a unit stays
+// short whatever its size.
+const DEBUG_PREVIEW_LEN: usize = 16;
+
+struct PayloadSummary<'a>(&'a [u8]);
+
+impl fmt::Debug for PayloadSummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes [", self.0.len())?;
+        for (i, byte) in self.0.iter().take(DEBUG_PREVIEW_LEN).enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        if self.0.len() > DEBUG_PREVIEW_LEN {
+            f.write_str(" ..")?;
+        }
+        f.write_str("]")
+    }
+}
+
+impl fmt::Debug for Fmp4 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fmp4")
+            .field("init", &self.init.as_ref().map(|init| init.len()))
+            .field("key", &self.key)
+            .field("data", &PayloadSummary(&self.data))
+            .field("duration", &self.duration)
+            .finish()
+    }
+}
+
+impl fmt::Debug for AccessUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessUnit")
+            .field("key", &self.key)
+            .field("pts", &self.pts)
+            .field("dts", &self.dts)
+            .field("stream_type", &self.stream_type)
+            .field("id", &self.id)
+            .field("data", &PayloadSummary(&self.data))
+            .finish()
+    }
+}
+
+/// Debug output of an [`AccessUnit`] with the whole payload, returned by
+/// [`AccessUnit::full_debug`].
+pub struct FullDebug<'a>(&'a AccessUnit);
+
+impl fmt::Debug for FullDebug<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let au = self.0;
+        f.debug_struct("AccessUnit")
+            .field("key", &au.key)
+            .field("pts", &au.pts)
+            .field("dts", &au.dts)
+            .field("stream_type", &au.stream_type)
+            .field("id", &au.id)
+            .field("data", &au.data)
+            .finish()
+    }
+}
+
+impl AccessUnit {
+    /// Formats with `{:?}` including every payload byte, where the plain
+    /// `Debug` impl shows only the length and the first 16 bytes.
+    pub fn full_debug(&self) -> FullDebug<'_> {
+        FullDebug(self)
+    }
+
+    /// The codec named by `stream_type`, or `None` for stream types without
+    /// one, such as private data.
+    pub fn stream_type_enum(&self) -> Option<Codec> {
+        Codec::from_psi_stream_type(self.stream_type)
+    }
+
+    pub fn stream_type(&self) -> StreamType {
+        StreamType::from(self.stream_type)
+    }
+
+    pub fn builder() -> AccessUnitBuilder {
+        AccessUnitBuilder::default()
+    }
+
+    /// Ticks from this unit's DTS to `next`'s, in the 90 kHz clock, taking
+    /// a 33-bit wrap between the two into account.
+    pub fn duration_90k(&self, next: &AccessUnit) -> u64 {
+        Ticks90k(next.dts).wrapping_sub(Ticks90k(self.dts)).0
+    }
+
+    /// The PTS as time since zero on the 90 kHz clock.
+    pub fn pts_duration(&self) -> std::time::Duration {
+        Ticks90k(self.pts).to_duration()
+    }
+
+    /// The DTS as time since zero on the 90 kHz clock.
+    pub fn dts_duration(&self) -> std::time::Duration {
+        Ticks90k(self.dts).to_duration()
+    }
+
+    /// Encodes the unit for transport between processes: a fixed
+    /// [`ACCESS_UNIT_HEADER_LEN`]-byte header followed by the payload.
+    ///
+    /// The header holds a version byte, a flags byte (bit 0 is `key`),
+    /// the stream type, then `pts`, `dts` and `id` as big-endian `u64`s,
+    /// and the payload length as a big-endian `u32`. Fails for a payload
+    /// too long for that field.
+    pub fn to_bytes(&self) -> Result<Bytes, AccessUnitError> {
+        let len = u32::try_from(self.data.len())
+            .map_err(|_| AccessUnitError::PayloadTooLong(self.data.len()))?;
+        let mut out = Vec::with_capacity(ACCESS_UNIT_HEADER_LEN + self.data.len());
+        out.push(ACCESS_UNIT_VERSION);
+        out.push(self.key as u8);
+        out.push(self.stream_type);
+        out.extend_from_slice(&self.pts.to_be_bytes());
+        out.extend_from_slice(&self.dts.to_be_bytes());
+        out.extend_from_slice(&self.id.to_be_bytes());
+        out.extend_from_slice(&len.to_be_bytes());
+        out.extend_from_slice(&self.data);
+        Ok(Bytes::from(out))
+    }
+
+    /// Decodes a unit written by [`AccessUnit::to_bytes`], copying the
+    /// payload. `data` must hold exactly one unit.
+    pub fn from_bytes(data: &[u8]) -> Result<AccessUnit, AccessUnitError> {
+        let (mut au, payload) = Self::decode_header(data)?;
+        au.data = Bytes::copy_from_slice(&data[payload]);
+        Ok(au)
+    }
+
+    /// Like [`AccessUnit::from_bytes`], but the payload is a slice of
+    /// `data` rather than a copy.
+    pub fn from_shared(data: &Bytes) -> Result<AccessUnit, AccessUnitError> {
+        let (mut au, payload) = Self::decode_header(data)?;
+        au.data = data.slice(payload);
+        Ok(au)
+    }
+
+    fn decode_header(data: &[u8]) -> Result<(AccessUnit, std::ops::Range<usize>), AccessUnitError> {
+        let header = data
+            .get(..ACCESS_UNIT_HEADER_LEN)
+            .ok_or(AccessUnitError::Truncated)?;
+        if header[0] != ACCESS_UNIT_VERSION {
+            return Err(AccessUnitError::UnsupportedVersion(header[0]));
+        }
+        if header[1] & !0x01 != 0 {
+            return Err(AccessUnitError::InvalidFlags(header[1]));
+        }
+
+        let u64_at = |pos: usize| u64::from_be_bytes(header[pos..pos + 8].try_into().unwrap());
+        let len = u32::from_be_bytes(header[27..31].try_into().unwrap()) as usize;
+        let available = data.len() - ACCESS_UNIT_HEADER_LEN;
+        if len != available {
+            return Err(AccessUnitError::LengthMismatch {
+                declared: len,
+                available,
+            });
+        }
+
+        let au = AccessUnit {
+            key: header[1] & 0x01 != 0,
+            pts: u64_at(3),
+            dts: u64_at(11),
+            data: Bytes::new(),
+            stream_type: header[2],
+            id: u64_at(19),
+        };
+        Ok((au, ACCESS_UNIT_HEADER_LEN..data.len()))
+    }
+}
+
+const ACCESS_UNIT_VERSION: u8 = 1;
+
+/// Builds an [`AccessUnit`], checking what a struct literal cannot.
+#[derive(Debug, Clone, Default)]
+pub struct AccessUnitBuilder {
+    key: bool,
+    pts: Option<u64>,
+    dts: Option<u64>,
+    id: u64,
+    stream_type: Option<u8>,
+    codec: Option<Codec>,
+    data: Bytes,
+    allow_empty: bool,
+}
+
+impl AccessUnitBuilder {
+    pub fn key(mut self, key: bool) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn pts(mut self, pts: u64) -> Self {
+        self.pts = Some(pts);
+        self
+    }
+
+    /// Defaults to the PTS.
+    pub fn dts(mut self, dts: u64) -> Self {
+        self.dts = Some(dts);
+        self
+    }
+
+    pub fn id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn stream_type(mut self, stream_type: impl Into<StreamType>) -> Self {
+        self.stream_type = Some(stream_type.into().as_u8());
+        self.codec = None;
+        self
+    }
+
+    /// Sets the stream type from a codec; `build` fails if the codec has
+    /// no PSI stream type.
+    pub fn codec(mut self, codec: impl Into<Codec>) -> Self {
+        self.codec = Some(codec.into());
+        self.stream_type = None;
+        self
+    }
+
+    pub fn data(mut self, data: impl Into<Bytes>) -> Self {
+        self.data = data.into();
+        self
+    }
+
+    /// Accepts a unit without payload, which `build` otherwise rejects.
+    pub fn allow_empty(mut self, allow_empty: bool) -> Self {
+        self.allow_empty = allow_empty;
+        self
+    }
+
+    pub fn build(self) -> Result<AccessUnit, AccessUnitError> {
+        let pts = self.pts.ok_or(AccessUnitError::MissingPts)?;
+        let dts = self.dts.unwrap_or(pts);
+        if dts > pts {
+            return Err(AccessUnitError::DtsAfterPts { pts, dts });
+        }
+
+        let stream_type = match self.codec {
+            Some(codec) => codec
+                .to_psi_stream_type()
+                .ok_or(AccessUnitError::UnmappedCodec(codec))?,
+            None => self.stream_type.ok_or(AccessUnitError::MissingStreamType)?,
+        };
+        if stream_type == 0 {
+            return Err(AccessUnitError::MissingStreamType);
+        }
+        if self.data.is_empty() && !self.allow_empty {
+            return Err(AccessUnitError::EmptyData);
+        }
+
+        Ok(AccessUnit {
+            key: self.key,
+            pts,
+            dts,
+            data: self.data,
+            stream_type,
+            id: self.id,
+        })
+    }
+}
+
+/// Size of the header [`AccessUnit::to_bytes`] writes before the payload.
+pub const ACCESS_UNIT_HEADER_LEN: usize = 31;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccessUnitError {
+    /// Shorter than the fixed header.
+    Truncated,
+    UnsupportedVersion(u8),
+    InvalidFlags(u8),
+    /// The header's payload length disagrees with the bytes after it.
+    LengthMismatch {
+        declared: usize,
+        available: usize,
+    },
+    /// The payload does not fit the header's 32-bit length field.
+    PayloadTooLong(usize),
+    MissingPts,
+    DtsAfterPts {
+        pts: u64,
+        dts: u64,
+    },
+    /// No stream type was set, or it was zero.
+    MissingStreamType,
+    UnmappedCodec(Codec),
+    EmptyData,
+}
+
+impl fmt::Display for AccessUnitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessUnitError::Truncated => write!(f, "Access unit header truncated"),
+            AccessUnitError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported access unit version: {}", version)
+            }
+            AccessUnitError::InvalidFlags(flags) => {
+                write!(f, "Invalid access unit flags: {:#04x}", flags)
+            }
+            AccessUnitError::LengthMismatch {
+                declared,
+                available,
+            } => write!(
+                f,
+                "Payload length {} does not match the {} bytes available",
+                declared, available
+            ),
+            AccessUnitError::PayloadTooLong(len) => {
+                write!(f, "Payload of {} bytes is too long to encode", len)
+            }
+            AccessUnitError::MissingPts => write!(f, "Access unit has no PTS"),
+            AccessUnitError::DtsAfterPts { pts, dts } => {
+                write!(f, "DTS {} is later than PTS {}", dts, pts)
+            }
+            AccessUnitError::MissingStreamType => write!(f, "Access unit has no stream type"),
+            AccessUnitError::UnmappedCodec(codec) => {
+                write!(f, "No PSI stream type for codec: {:?}", codec)
+            }
+            AccessUnitError::EmptyData => write!(f, "Access unit has no data"),
+        }
+    }
+}
+
+impl std::error::Error for AccessUnitError {}
+
+const ID3V2_HEADER_LEN: usize = 10;
+
+/// Total size of an ID3v2 tag at the start of `data`, header and footer
+/// included, or `None` if there is no valid tag header.
+pub fn id3v2_size(data: &[u8]) -> Option<usize> {
+    if data.len() < ID3V2_HEADER_LEN || &data[..3] != b"ID3" {
+        return None;
+    }
+
+    // Version bytes are never 0xFF and the size is four 7-bit syncsafe bytes
+    let (major, revision, flags) = (data[3], data[4], data[5]);
+    if major == 0xFF || revision == 0xFF || data[6..10].iter().any(|&b| b & 0x80 != 0) {
+        return None;
+    }
+    let size = data[6..10]
+        .iter()
+        .fold(0usize, |size, &b| (size << 7) | b as usize);
+
+    let footer = if flags & 0x10 != 0 {
+        ID3V2_HEADER_LEN
+    } else {
+        0
+    };
+    Some(ID3V2_HEADER_LEN + size + footer)
+}
+
+/// Detects the audio format at the start of `data`, after any ID3v2 tag.
+/// Bare elementary streams are reported, and so is the codec of a CAF
+/// file, which holds a single audio stream; see [`detect_media`] for other
+/// containers.
 pub fn detect_audio(data: &[u8]) -> AudioType {
-    if flac::is_flac(data) {
-        AudioType::FLAC
-    } else if aac::is_aac(data) {
-        AudioType::AAC
+    match detect_media(data) {
+        MediaFormat {
+            container: None | Some(ContainerType::Caf),
+            codec: Some(codec),
+        } => codec,
+        _ => AudioType::Unknown,
+    }
+}
+
+/// Identifies the container from its magic bytes and, where the first
+/// bytes say so, the audio codec inside it. Anything else is checked for
+/// a bare ADTS or FLAC stream.
+pub fn detect_media(data: &[u8]) -> MediaFormat {
+    // AIFF carries only PCM and AMR files only AMR, so the file format is
+    // the codec. ADIF is a single header in front of raw AAC.
+    let codec = if aiff::is_aiff(data) {
+        Some(AudioType::AIFF)
+    } else if aac::parse_adif_header(data).is_some() {
+        Some(AudioType::AAC)
+    } else {
+        amr::is_amr(data)
+    };
+    if codec.is_some() {
+        return MediaFormat {
+            container: None,
+            codec,
+        };
+    }
+
+    let container = if ogg::is_ogg(data) {
+        Some(ContainerType::Ogg)
+    } else if data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        Some(ContainerType::WebM)
+    } else if data.get(4..8) == Some(b"ftyp") {
+        Some(ContainerType::Mp4)
+    } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WAVE") {
+        Some(ContainerType::Wav)
+    } else if caf::is_caf(data) {
+        Some(ContainerType::Caf)
+    } else if is_mpegts(data) {
+        Some(ContainerType::MpegTs)
     } else {
-        AudioType::Unknown
+        None
+    };
+
+    let codec = match container {
+        Some(ContainerType::Ogg) => ogg::codec(data),
+        Some(ContainerType::Caf) => {
+            caf::parse_audio_description(data).and_then(|desc| desc.codec())
+        }
+        Some(_) => None,
+        None => match detect_audio_with(data, &DetectOptions::default()) {
+            AudioType::Unknown => None,
+            codec => Some(codec),
+        },
+    };
+
+    MediaFormat { container, codec }
+}
+
+const MPEGTS_SYNC_BYTE: u8 = 0x47;
+const MPEGTS_MIN_PACKETS: usize = 3;
+
+pub fn is_mpegts(data: &[u8]) -> bool {
+    mpegts_packet_size(data).is_some()
+}
+
+/// Packet size of an MPEG-TS stream starting at `data`: 188, or 192 for
+/// M2TS, whose packets carry a four-byte timestamp before the sync byte.
+/// The sync byte must repeat at that stride across three packets.
+pub fn mpegts_packet_size(data: &[u8]) -> Option<usize> {
+    [(188, 0), (192, 4)]
+        .into_iter()
+        .find(|&(size, sync_offset)| {
+            (0..MPEGTS_MIN_PACKETS)
+                .all(|n| data.get(n * size + sync_offset) == Some(&MPEGTS_SYNC_BYTE))
+        })
+        .map(|(size, _)| size)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetectOptions {
+    /// How far past any ID3v2 tag the first frame may start. Zero only
+    /// looks at the very start.
+    pub max_probe_bytes: usize,
+    pub aac: bool,
+    pub flac: bool,
+    /// AC-3 and E-AC-3.
+    pub ac3: bool,
+    /// Only accept a FLAC header whose CRC-8 checks, or an ADTS or AC-3
+    /// frame followed by another sync. Always applied past offset zero.
+    pub require_validated: bool,
+}
+
+impl Default for DetectOptions {
+    fn default() -> Self {
+        Self {
+            max_probe_bytes: 0,
+            aac: true,
+            flac: true,
+            ac3: true,
+            require_validated: false,
+        }
+    }
+}
+
+/// [`detect_audio`] with control over probe depth, the formats tried and
+/// how much evidence a match needs. The defaults behave like `detect_audio`.
+pub fn detect_audio_with(data: &[u8], options: &DetectOptions) -> AudioType {
+    let data = match id3v2_size(data) {
+        Some(size) => data.get(size..).unwrap_or_default(),
+        None => data,
+    };
+    if options.flac && data.starts_with(b"fLaC") {
+        return AudioType::FLAC;
+    }
+
+    let last = options.max_probe_bytes.min(data.len().saturating_sub(1));
+    // Every format starts with one of three bytes, so a single pass can
+    // skip all other offsets before trying any parser.
+    data.iter()
+        .take(last + 1)
+        .enumerate()
+        .filter(|(_, &byte)| matches!(byte, 0xFF | 0x0B | 0x56))
+        .find_map(|(offset, &byte)| {
+            let frame = &data[offset..];
+            let validated = options.require_validated || offset > 0;
+            match byte {
+                0xFF => {
+                    let is_flac = if validated {
+                        flac::checked_header_len(frame).is_some()
+                    } else {
+                        flac::is_flac(frame)
+                    };
+                    let is_aac = if validated {
+                        is_confirmed_adts(frame)
+                    } else {
+                        aac::is_aac(frame)
+                    };
+                    if options.flac && is_flac {
+                        Some(AudioType::FLAC)
+                    } else if options.aac && is_aac {
+                        Some(AudioType::AAC)
+                    } else {
+                        None
+                    }
+                }
+                0x0B if options.ac3 => ac3::parse_syncframe_header(frame)
+                    .filter(|header| {
+                        !validated
+                            || frame.get(header.frame_size..header.frame_size + 2)
+                                == Some(&[0x0B, 0x77])
+                    })
+                    .map(|header| {
+                        if header.is_eac3() {
+                            AudioType::EAC3
+                        } else {
+                            AudioType::AC3
+                        }
+                    }),
+                0x56 if options.aac && aac::is_loas(frame) => Some(AudioType::AAC),
+                _ => None,
+            }
+        })
+        .unwrap_or(AudioType::Unknown)
+}
+
+pub const DEFAULT_PROBE_WINDOW: usize = 256 * 1024;
+
+/// Detects the audio codec from a reader, reading at most
+/// [`DEFAULT_PROBE_WINDOW`] bytes. Codecs inside Ogg, CAF and MP4 are
+/// identified too, as by [`detect_from_path`]. The reader is left where it
+/// started.
+pub fn detect_audio_from_reader<R: Read + Seek>(reader: &mut R) -> io::Result<AudioType> {
+    detect_audio_from_reader_with(reader, DEFAULT_PROBE_WINDOW)
+}
+
+/// Like [`detect_audio_from_reader`] with a caller-chosen probe window. A
+/// leading ID3v2 tag is seeked over rather than read, so it does not count
+/// against the window, and so are the top-level boxes of an MP4 file on
+/// the way to `moov`.
+pub fn detect_audio_from_reader_with<R: Read + Seek>(
+    reader: &mut R,
+    window: usize,
+) -> io::Result<AudioType> {
+    detect_with_hint(reader, window, None)
+}
+
+// Shared by the reader and path functions so they agree. `hint` only picks
+// between formats that all match the content equally well.
+fn detect_with_hint<R: Read + Seek>(
+    reader: &mut R,
+    window: usize,
+    hint: Option<AudioType>,
+) -> io::Result<AudioType> {
+    let start = reader.stream_position()?;
+    let probe = read_probe(reader, window)?;
+
+    let media = detect_media(&probe);
+    let audio_type = match media.container {
+        Some(ContainerType::Mp4) => {
+            let codec = mp4_audio_codec(reader, start);
+            reader.seek(SeekFrom::Start(start))?;
+            codec?
+        }
+        Some(_) => media.codec,
+        None => match detect_audio_strict(&probe) {
+            StrictDetection::Ambiguous(candidates) => hint
+                .filter(|hint| candidates.contains(hint))
+                .or(media.codec),
+            _ => media.codec,
+        },
+    };
+
+    Ok(audio_type.unwrap_or(AudioType::Unknown))
+}
+
+// Reads up to `window` bytes after any ID3v2 tag and seeks back to where
+// the reader started.
+fn read_probe<R: Read + Seek>(reader: &mut R, window: usize) -> io::Result<Vec<u8>> {
+    let start = reader.stream_position()?;
+
+    let mut header = Vec::with_capacity(ID3V2_HEADER_LEN);
+    reader
+        .by_ref()
+        .take(ID3V2_HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    let skip = id3v2_size(&header).unwrap_or(0) as u64;
+
+    reader.seek(SeekFrom::Start(start + skip))?;
+    let mut probe = Vec::with_capacity(window.min(DEFAULT_PROBE_WINDOW));
+    reader
+        .by_ref()
+        .take(window as u64)
+        .read_to_end(&mut probe)?;
+    reader.seek(SeekFrom::Start(start))?;
+
+    Ok(probe)
+}
+
+/// Detects the audio codec of a file from its first
+/// [`DEFAULT_PROBE_WINDOW`] bytes. Codecs inside Ogg, CAF and MP4 are
+/// identified too; for MP4 the top-level boxes are seeked over to find
+/// `moov`, wherever it is, without reading `mdat`.
+///
+/// The extension never decides the result on its own. It only picks
+/// between formats that all match the content equally well.
+pub fn detect_from_path<P: AsRef<Path>>(path: P) -> io::Result<AudioType> {
+    let path = path.as_ref();
+    let hint = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| ext.parse::<AudioType>().ok());
+    detect_with_hint(&mut File::open(path)?, DEFAULT_PROBE_WINDOW, hint)
+}
+
+// A moov this large is not worth reading just to name the codec.
+const MAX_MOOV_LEN: u64 = 64 * 1024 * 1024;
+
+// Reads only the eight or sixteen byte header of each top-level box from
+// `start` until it reaches moov.
+fn mp4_audio_codec<R: Read + Seek>(reader: &mut R, start: u64) -> io::Result<Option<AudioType>> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    let mut pos = start;
+
+    while end - pos >= 8 {
+        let mut header = [0u8; 16];
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut header[..8])?;
+        let (header_len, size) = match u32::from_be_bytes(header[..4].try_into().unwrap()) {
+            0 => (8, end - pos),
+            1 => {
+                reader.read_exact(&mut header[8..])?;
+                (16, u64::from_be_bytes(header[8..].try_into().unwrap()))
+            }
+            size => (8, size as u64),
+        };
+        if size < header_len || size > end - pos {
+            return Ok(None);
+        }
+
+        if &header[4..8] == b"moov" {
+            if size - header_len > MAX_MOOV_LEN {
+                return Ok(None);
+            }
+            let mut moov = vec![0; (size - header_len) as usize];
+            reader.read_exact(&mut moov)?;
+            return Ok(probe::mp4_audio_track(&moov).map(|probe| probe.audio_type));
+        }
+        pos += size;
+    }
+
+    Ok(None)
+}
+
+/// [`detect_audio`] over an async reader, driving a [`Detector`] until it
+/// reaches a verdict, the input ends, or [`DEFAULT_PROBE_WINDOW`] bytes,
+/// ID3v2 tags included, have been read without one.
+///
+/// All state lives in the future, so dropping it mid-read is safe; bytes
+/// already read from `reader` are not given back.
+#[cfg(feature = "tokio")]
+pub async fn detect_audio_async<R: tokio::io::AsyncRead + Unpin>(
+    reader: &mut R,
+) -> io::Result<AudioType> {
+    use tokio::io::AsyncReadExt;
+
+    let mut detector = Detector::new();
+    let mut buf = [0u8; 4096];
+    let mut total = 0;
+
+    let status = loop {
+        let n = reader.read(&mut buf).await?;
+        total += n;
+        let status = if n == 0 {
+            detector.finish()
+        } else {
+            detector.push(&buf[..n])
+        };
+        match status {
+            DetectStatus::NeedMoreData if total < DEFAULT_PROBE_WINDOW => continue,
+            DetectStatus::NeedMoreData => break detector.finish(),
+            status => break status,
+        }
+    };
+
+    Ok(match status {
+        DetectStatus::Detected(audio_type) => audio_type,
+        _ => AudioType::Unknown,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DetectionReport {
+    pub audio_type: AudioType,
+    /// Offset of the first frame header.
+    pub offset: usize,
+    /// Length of that header, when it could be parsed.
+    pub header_len: Option<usize>,
+}
+
+/// Like [`detect_audio`], but also finds streams that begin after leading
+/// garbage and reports where the first frame starts. Offsets are relative
+/// to `data`, so they include any ID3v2 tag. Past the tag a
+/// FLAC header must pass its CRC-8 and an ADTS frame must be followed by
+/// another sync, so stray 0xFF bytes are not reported. A container is not
+/// scanned: its codec, if [`detect_media`] finds one, is reported at the
+/// start with no header length.
+pub fn detect_audio_report(data: &[u8]) -> Option<DetectionReport> {
+    let report = |audio_type, offset: usize| {
+        let frame = &data[offset..];
+        let header_len = match audio_type {
+            AudioType::FLAC => flac::checked_header_len(frame),
+            AudioType::AAC if aac::parse_adts_header(frame).is_ok() => Some(adts_header_len(frame)),
+            AudioType::AAC if aac::is_loas(frame) => Some(aac::LOAS_HEADER_LEN),
+            AudioType::AC3 | AudioType::EAC3 => ac3::header_len(frame),
+            // ADIF and the magic-number formats have no per-frame header
+            _ => None,
+        };
+        Some(DetectionReport {
+            audio_type,
+            offset,
+            header_len,
+        })
+    };
+
+    let start = id3v2_size(data).unwrap_or(0);
+    if start >= data.len() {
+        return None;
+    }
+
+    // Frame syncs inside a container's pages or boxes are not frames
+    let media = detect_media(&data[start..]);
+    if media.container.is_some() {
+        return media.codec.and_then(|audio_type| report(audio_type, start));
+    }
+
+    match detect_audio(data) {
+        AudioType::Unknown => {}
+        audio_type => return report(audio_type, start),
+    }
+
+    (start + 1..data.len())
+        .filter(|&offset| data[offset] == 0xFF)
+        .find_map(|offset| {
+            let frame = &data[offset..];
+            if flac::checked_header_len(frame).is_some() {
+                report(AudioType::FLAC, offset)
+            } else if is_confirmed_adts(frame) {
+                report(AudioType::AAC, offset)
+            } else {
+                None
+            }
+        })
+}
+
+/// Result of [`detect_audio_strict`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrictDetection {
+    /// Exactly one format matched on a validated header.
+    Confident(AudioType),
+    /// Several formats matched, or the candidates could not be validated.
+    Ambiguous(Vec<AudioType>),
+    /// A candidate needs `need` bytes in total before it can be validated.
+    InsufficientData {
+        need: usize,
+    },
+    Unknown,
+}
+
+// Sync, two code bytes, a seven-byte coded number, 16-bit block size and
+// sample rate, and the CRC-8.
+const FLAC_MAX_HEADER_LEN: usize = 16;
+
+/// Stricter [`detect_audio`] for short sniff buffers. The AIFF, AMR and
+/// ADIF magic numbers are strong matches once complete. A FLAC header only
+/// counts as a strong match once its CRC-8 checks, and an ADTS, AC-3,
+/// E-AC-3 or LOAS frame once the following frame's header lines up.
+/// Containers are not looked into: use [`detect_media`] for those.
+pub fn detect_audio_strict(data: &[u8]) -> StrictDetection {
+    let start = id3v2_size(data).unwrap_or(0);
+    let frame = data.get(start..).unwrap_or_default();
+    if frame.is_empty() {
+        return StrictDetection::InsufficientData {
+            need: start + DETECT_PROBE_LEN,
+        };
+    }
+
+    let mut strong = Vec::new();
+    let mut weak = Vec::new();
+    let mut need = None;
+
+    if aiff::is_aiff(frame) {
+        strong.push(AudioType::AIFF);
+    } else if frame.len() < aiff::FORM_HEADER_LEN
+        && b"FORM".starts_with(&frame[..frame.len().min(4)])
+    {
+        need = Some(start + aiff::FORM_HEADER_LEN);
+    }
+
+    if let Some(amr) = amr::is_amr(frame) {
+        strong.push(amr);
+    } else if let Some(len) = amr::magic_len(frame) {
+        need = need.max(Some(start + len));
+    }
+
+    if aac::parse_adif_header(frame).is_some() {
+        strong.push(AudioType::AAC);
+    } else if aac::is_truncated_adif(frame) {
+        // The program config element has no length field, so the header
+        // can only be known to need at least one more byte
+        need = need.max(Some(start + frame.len() + 1));
+    }
+
+    if frame.starts_with(b"fLaC") {
+        strong.push(AudioType::FLAC);
+    } else if flac::has_frame_sync(frame) {
+        if flac::checked_header_len(frame).is_some() {
+            strong.push(AudioType::FLAC);
+        } else {
+            weak.push(AudioType::FLAC);
+            if matches!(
+                flac::decode_frame_header(frame),
+                Err(flac::FLACError::UnexpectedEndOfInput)
+            ) {
+                need = Some(start + FLAC_MAX_HEADER_LEN);
+            }
+        }
+    }
+
+    if frame.len() < DETECT_PROBE_LEN {
+        if frame[0] == 0xFF && frame.get(1).map_or(0xF0, |&b| b & 0xF0) == 0xF0 {
+            need = need.max(Some(start + DETECT_PROBE_LEN));
+        }
+    } else if aac::is_aac_single_frame(frame) {
+        let frame_length = aac::adts_frame_length(frame);
+        match frame.get(frame_length..) {
+            Some(next) if next.len() >= DETECT_PROBE_LEN => {
+                if frame_length >= adts_header_len(frame) && aac::is_aac_single_frame(next) {
+                    strong.push(AudioType::AAC);
+                } else {
+                    weak.push(AudioType::AAC);
+                }
+            }
+            _ => {
+                weak.push(AudioType::AAC);
+                need = need.max(Some(start + frame_length + DETECT_PROBE_LEN));
+            }
+        }
+    }
+
+    if let Some(header) = ac3::parse_syncframe_header(frame) {
+        let audio_type = if header.is_eac3() {
+            AudioType::EAC3
+        } else {
+            AudioType::AC3
+        };
+        match frame.get(header.frame_size..header.frame_size + 2) {
+            Some(next) if next == [0x0B, 0x77] => strong.push(audio_type),
+            Some(_) => weak.push(audio_type),
+            None => {
+                weak.push(audio_type);
+                need = need.max(Some(start + header.frame_size + 2));
+            }
+        }
+    } else if frame.len() < AC3_PROBE_LEN && [0x0B, 0x77].starts_with(&frame[..frame.len().min(2)])
+    {
+        need = need.max(Some(start + AC3_PROBE_LEN));
+    }
+
+    if frame[0] == 0x56 {
+        match aac::loas_frame_length(frame) {
+            Some(_) if aac::is_loas(frame) => strong.push(AudioType::AAC),
+            Some(len) if frame.len() < len + LOAS_PROBE_LEN => {
+                weak.push(AudioType::AAC);
+                need = need.max(Some(start + len + LOAS_PROBE_LEN));
+            }
+            Some(_) => weak.push(AudioType::AAC),
+            None if frame.len() < LOAS_PROBE_LEN
+                && !matches!(frame.get(1), Some(&b) if b & 0xE0 != 0xE0) =>
+            {
+                need = need.max(Some(start + LOAS_PROBE_LEN));
+            }
+            None => {}
+        }
+    }
+
+    match (strong.len(), need) {
+        (1, _) => StrictDetection::Confident(strong[0]),
+        (0, Some(need)) => StrictDetection::InsufficientData { need },
+        (0, None) if weak.is_empty() => StrictDetection::Unknown,
+        (0, None) => StrictDetection::Ambiguous(weak),
+        _ => StrictDetection::Ambiguous(strong),
+    }
+}
+
+fn adts_header_len(frame: &[u8]) -> usize {
+    if frame[1] & 0x01 == 0x01 {
+        7
+    } else {
+        9
+    }
+}
+
+fn is_confirmed_adts(frame: &[u8]) -> bool {
+    // is_aac also accepts a frame ending the buffer, which confirms nothing
+    aac::is_aac(frame) && frame.len() > aac::adts_frame_length(frame)
+}
+
+/// Longest prefix either detector needs before it can decide: a full ADTS
+/// header, which is also enough for a FLAC sync.
+const DETECT_PROBE_LEN: usize = 7;
+/// AC-3 needs one more byte to reach lfeon.
+const AC3_PROBE_LEN: usize = 8;
+/// LOAS needs the three-byte header of the frame after the first one.
+const LOAS_PROBE_LEN: usize = 3;
+const DETECTOR_MAX_BUFFER: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DetectStatus {
+    NeedMoreData,
+    Detected(AudioType),
+    Unknown,
+}
+
+/// Incremental [`detect_audio`] for data that arrives in pieces. Bytes are
+/// buffered, at most 64 KiB, until a verdict can be reached; after that
+/// further pushes return the same verdict. ID3v2 tags are skipped without
+/// being buffered, whatever their size.
+#[derive(Debug, Clone, Default)]
+pub struct Detector {
+    buffer: Vec<u8>,
+    skip: usize,
+    status: Option<DetectStatus>,
+}
+
+impl Detector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, data: &[u8]) -> DetectStatus {
+        if let Some(status) = self.status {
+            return status;
+        }
+
+        let mut data = data;
+        loop {
+            let skipped = self.skip.min(data.len());
+            self.skip -= skipped;
+            data = &data[skipped..];
+
+            let take = data.len().min(DETECTOR_MAX_BUFFER - self.buffer.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+
+            let Some(size) = id3v2_size(&self.buffer) else {
+                break;
+            };
+            let dropped = size.min(self.buffer.len());
+            self.buffer.drain(..dropped);
+            self.skip = size - dropped;
+        }
+
+        let status = match detect_audio(&self.buffer) {
+            AudioType::Unknown if self.could_still_match() => return DetectStatus::NeedMoreData,
+            AudioType::Unknown => DetectStatus::Unknown,
+            audio_type => DetectStatus::Detected(audio_type),
+        };
+        self.status = Some(status);
+        self.buffer = Vec::new();
+        status
+    }
+
+    /// Signals the end of input: a detector still waiting for data gives up
+    /// with [`DetectStatus::Unknown`].
+    pub fn finish(&mut self) -> DetectStatus {
+        let status = match self.push(&[]) {
+            DetectStatus::NeedMoreData => DetectStatus::Unknown,
+            status => status,
+        };
+        self.status = Some(status);
+        self.buffer = Vec::new();
+        status
+    }
+
+    // ADTS and FLAC start with a 12-bit sync, AC-3 with 0x0B77 and LOAS
+    // with 0x56E, so anything other than those or the start of an ID3v2
+    // header, fLaC marker, AMR magic, AIFF FORM header or ADIF header is
+    // final. LOAS waits for the header of its second frame, FLAC for the
+    // rest of its frame header, FORM for the form type after its size and
+    // ADIF for its first program config element.
+    fn could_still_match(&self) -> bool {
+        let buffer = &self.buffer;
+        if buffer.is_empty() {
+            return true;
+        }
+        if buffer.len() < ID3V2_HEADER_LEN && b"ID3".starts_with(&buffer[..buffer.len().min(3)]) {
+            return true;
+        }
+        if buffer.len() < AC3_PROBE_LEN && [0x0B, 0x77].starts_with(&buffer[..buffer.len().min(2)])
+        {
+            return true;
+        }
+        if buffer.len() < 4 && b"fLaC".starts_with(buffer) {
+            return true;
+        }
+        if amr::is_magic_prefix(buffer) {
+            return true;
+        }
+        if aac::is_truncated_adif(buffer) {
+            return true;
+        }
+        if buffer.len() < aiff::FORM_HEADER_LEN
+            && b"FORM".starts_with(&buffer[..buffer.len().min(4)])
+        {
+            return true;
+        }
+        if flac::has_frame_sync(buffer)
+            && buffer.len() < FLAC_MAX_HEADER_LEN
+            && matches!(
+                flac::decode_frame_header(buffer),
+                Err(flac::FLACError::UnexpectedEndOfInput)
+            )
+        {
+            return true;
+        }
+        if buffer[0] == 0x56 {
+            return match aac::loas_frame_length(buffer) {
+                Some(len) => buffer.len() < len + LOAS_PROBE_LEN,
+                None => {
+                    buffer.len() < LOAS_PROBE_LEN
+                        && !matches!(buffer.get(1), Some(&b) if b & 0xE0 != 0xE0)
+                }
+            };
+        }
+
+        buffer.len() < DETECT_PROBE_LEN
+            && !matches!(buffer.first(), Some(&b) if b != 0xFF)
+            && !matches!(buffer.get(1), Some(&b) if b & 0xF0 != 0xF0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUDIO_TYPES: [AudioType; 10] = [
+        AudioType::Unknown,
+        AudioType::AAC,
+        AudioType::FLAC,
+        AudioType::Opus,
+        AudioType::AC3,
+        AudioType::EAC3,
+        AudioType::Vorbis,
+        AudioType::AIFF,
+        AudioType::AmrNb,
+        AudioType::AmrWb,
+    ];
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let au = AccessUnit {
+            key: true,
+            pts: 3_600,
+            dts: 1_800,
+            data: Bytes::from_static(&[0xFF, 0xF1, 0x50, 0x80]),
+            stream_type: StreamType::AAC.into(),
+            id: 7,
+        };
+
+        let json = serde_json::to_value(&au).unwrap();
+        assert_eq!(json["data"], "//FQgA==");
+        let decoded: AccessUnit = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.data, au.data);
+        assert_eq!((decoded.pts, decoded.dts, decoded.id), (3_600, 1_800, 7));
+
+        let encoded = bincode::serialize(&au).unwrap();
+        let decoded: AccessUnit = bincode::deserialize(&encoded).unwrap();
+        assert_eq!(decoded.data, au.data);
+        assert!(decoded.key);
+
+        let fmp4 = Fmp4 {
+            init: None,
+            key: false,
+            data: Bytes::from_static(b"moof"),
+            duration: 1_024,
+        };
+        let decoded: Fmp4 = serde_json::from_str(&serde_json::to_string(&fmp4).unwrap()).unwrap();
+        assert_eq!(decoded.init, None);
+        assert_eq!(decoded.data, fmp4.data);
+        let with_init = Fmp4 {
+            init: Some(Bytes::from_static(b"ftyp")),
+            ..fmp4
+        };
+        let decoded: Fmp4 = bincode::deserialize(&bincode::serialize(&with_init).unwrap()).unwrap();
+        assert_eq!(decoded.init, with_init.init);
+
+        let media = detect_media(&aiff::tests::aiff_file(
+            None,
+            [0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0],
+        ));
+        let json = serde_json::to_string(&media).unwrap();
+        assert_eq!(serde_json::from_str::<MediaFormat>(&json).unwrap(), media);
+
+        // A persisted scan state resumes exactly where the original left off
+        let flac = std::fs::read("testdata/s24le.wav.flac").unwrap();
+        let (_, state) = duration::duration_hint(&flac[..flac.len() / 2], None);
+        let json = serde_json::to_string(&state).unwrap();
+        let from_json: duration::DurationState = serde_json::from_str(&json).unwrap();
+        assert_eq!(from_json, state);
+        let from_bincode: duration::DurationState =
+            bincode::deserialize(&bincode::serialize(&state).unwrap()).unwrap();
+        assert_eq!(from_bincode, state);
+        assert_eq!(
+            duration::duration_hint(&flac, Some(&from_json)),
+            duration::duration_hint(&flac, None)
+        );
+    }
+
+    #[test]
+    fn test_type_names_round_trip() {
+        for audio_type in AUDIO_TYPES {
+            assert_eq!(audio_type.to_string().parse(), Ok(audio_type));
+        }
+        for container in [
+            ContainerType::Ogg,
+            ContainerType::WebM,
+            ContainerType::Mp4,
+            ContainerType::Wav,
+            ContainerType::MpegTs,
+            ContainerType::Caf,
+        ] {
+            assert_eq!(container.to_string().parse(), Ok(container));
+        }
+
+        assert_eq!(AudioType::AmrWb.to_string(), "amr-wb");
+        assert_eq!("M4A".parse(), Ok(AudioType::AAC));
+        assert_eq!("ec-3".parse(), Ok(AudioType::EAC3));
+        assert_eq!("mka".parse(), Ok(ContainerType::WebM));
+        assert_eq!("oga".parse(), Ok(ContainerType::Ogg));
+        assert_eq!(
+            "mp3".parse::<AudioType>(),
+            Err(ParseTypeError("mp3".to_string()))
+        );
+        assert_eq!(
+            ParseTypeError("mp3".to_string()).to_string(),
+            "Unknown type name: mp3"
+        );
+    }
+
+    #[test]
+    fn test_access_unit_bytes_round_trip() {
+        let au = AccessUnit {
+            key: true,
+            pts: (1 << 33) + 3003,
+            dts: 3000,
+            data: Bytes::from_static(b"\x00\x00\x00\x01\x65payload"),
+            stream_type: StreamType::H264.into(),
+            id: u64::MAX,
+        };
+        let encoded = au.to_bytes().unwrap();
+        assert_eq!(encoded.len(), ACCESS_UNIT_HEADER_LEN + au.data.len());
+
+        for decoded in [
+            AccessUnit::from_bytes(&encoded).unwrap(),
+            AccessUnit::from_shared(&encoded).unwrap(),
+        ] {
+            assert!(decoded.key);
+            assert_eq!((decoded.pts, decoded.dts), (au.pts, au.dts));
+            assert_eq!(decoded.id, au.id);
+            assert_eq!(decoded.stream_type(), StreamType::H264);
+            assert_eq!(decoded.data, au.data);
+        }
+
+        let shared = AccessUnit::from_shared(&encoded).unwrap();
+        assert_eq!(
+            shared.data.as_ptr(),
+            encoded[ACCESS_UNIT_HEADER_LEN..].as_ptr()
+        );
+
+        let empty = AccessUnit {
+            data: Bytes::new(),
+            key: false,
+            ..au
+        };
+        let decoded = AccessUnit::from_bytes(&empty.to_bytes().unwrap()).unwrap();
+        assert!(!decoded.key);
+        assert!(decoded.data.is_empty());
+    }
+
+    #[test]
+    fn test_access_unit_bytes_rejects_corruption() {
+        let au = AccessUnit {
+            key: false,
+            pts: 90000,
+            dts: 90000,
+            data: Bytes::from_static(&[0x11; 16]),
+            stream_type: StreamType::AAC.into(),
+            id: 7,
+        };
+        let encoded = au.to_bytes().unwrap().to_vec();
+
+        // Every truncation fails cleanly
+        for len in 0..encoded.len() {
+            assert!(AccessUnit::from_bytes(&encoded[..len]).is_err());
+        }
+        assert_eq!(
+            AccessUnit::from_bytes(&encoded[..10]).unwrap_err(),
+            AccessUnitError::Truncated
+        );
+        assert_eq!(
+            AccessUnit::from_bytes(&encoded[..encoded.len() - 1]).unwrap_err(),
+            AccessUnitError::LengthMismatch {
+                declared: 16,
+                available: 15
+            }
+        );
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(matches!(
+            AccessUnit::from_bytes(&trailing),
+            Err(AccessUnitError::LengthMismatch { .. })
+        ));
+
+        let mut bad_length = encoded.clone();
+        bad_length[27..31].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(AccessUnit::from_bytes(&bad_length).is_err());
+
+        let mut bad_version = encoded.clone();
+        bad_version[0] = 2;
+        assert_eq!(
+            AccessUnit::from_bytes(&bad_version).unwrap_err(),
+            AccessUnitError::UnsupportedVersion(2)
+        );
+
+        let mut bad_flags = encoded;
+        bad_flags[1] = 0x80;
+        assert_eq!(
+            AccessUnit::from_shared(&Bytes::from(bad_flags)).unwrap_err(),
+            AccessUnitError::InvalidFlags(0x80)
+        );
+    }
+
+    #[test]
+    fn test_access_unit_builder() {
+        let au = AccessUnit::builder()
+            .pts(3003)
+            .key(true)
+            .id(4)
+            .codec(AudioType::AAC)
+            .data(Bytes::from_static(&[0xFF, 0xF1]))
+            .build()
+            .unwrap();
+        assert_eq!(au.dts, 3003);
+        assert_eq!(au.stream_type(), StreamType::AAC);
+        assert!(au.key);
+        assert_eq!(au.id, 4);
+
+        let video = AccessUnit::builder()
+            .pts(6006)
+            .dts(3003)
+            .codec(video::VideoType::H264)
+            .data(vec![0, 0, 1, 0x65])
+            .build()
+            .unwrap();
+        assert_eq!(video.stream_type, 0x1b);
+
+        let builder = AccessUnit::builder().stream_type(StreamType::Opus).pts(0);
+        assert_eq!(
+            builder.clone().build().unwrap_err(),
+            AccessUnitError::EmptyData
+        );
+        assert!(builder.clone().allow_empty(true).build().is_ok());
+        assert_eq!(
+            builder.clone().dts(1).data(vec![1]).build().unwrap_err(),
+            AccessUnitError::DtsAfterPts { pts: 0, dts: 1 }
+        );
+        assert_eq!(
+            AccessUnit::builder().data(vec![1]).build().unwrap_err(),
+            AccessUnitError::MissingPts
+        );
+        assert_eq!(
+            builder.stream_type(0).data(vec![1]).build().unwrap_err(),
+            AccessUnitError::MissingStreamType
+        );
+        assert_eq!(
+            AccessUnit::builder()
+                .pts(0)
+                .codec(AudioType::Vorbis)
+                .data(vec![1])
+                .build()
+                .unwrap_err(),
+            AccessUnitError::UnmappedCodec(Codec::Audio(AudioType::Vorbis))
+        );
+    }
+
+    #[test]
+    fn test_debug_output_is_bounded() {
+        let au = |len: usize| AccessUnit {
+            key: true,
+            pts: 3003,
+            dts: 0,
+            data: Bytes::from(vec![0xAB; len]),
+            stream_type: StreamType::H264.into(),
+            id: 9,
+        };
+
+        assert_eq!(
+            format!("{:?}", au(3)),
+            "AccessUnit { key: true, pts: 3003, dts: 0, stream_type: 27, id: 9, \
+             data: 3 bytes [ab ab ab] }"
+        );
+        let small = format!("{:?}", au(64));
+        assert!(small
+            .ends_with("data: 64 bytes [ab ab ab ab ab ab ab ab ab ab ab ab ab ab ab ab ..] }"));
+        for len in [1 << 10, 1 << 20] {
+            assert_eq!(
+                format!("{:?}", au(len)).len(),
+                small.len() + len.to_string().len() - 2
+            );
+            assert!(format!("{:#?}", au(len)).len() < 256);
+        }
+        assert!(format!("{:?}", au(1 << 10).full_debug()).len() > 1 << 10);
+
+        let fmp4 = Fmp4 {
+            init: Some(Bytes::from(vec![0; 700])),
+            key: true,
+            data: Bytes::from(vec![0; 1 << 20]),
+            duration: 2000,
+        };
+        let formatted = format!("{:?}", fmp4);
+        assert!(formatted.starts_with("Fmp4 { init: Some(700), key: true, data: 1048576 bytes ["));
+        assert!(formatted.len() < 256);
+    }
+
+    #[test]
+    fn test_duration_90k() {
+        let at = |dts| AccessUnit {
+            key: false,
+            pts: dts,
+            dts,
+            data: Bytes::new(),
+            stream_type: StreamType::AAC.into(),
+            id: 0,
+        };
+        assert_eq!(at(90000).duration_90k(&at(93003)), 3003);
+        // Across the 33-bit wrap
+        assert_eq!(at((1 << 33) - 1000).duration_90k(&at(2003)), 3003);
+        assert_eq!(at(90000).pts_duration(), std::time::Duration::from_secs(1));
+        assert_eq!(
+            at(3600).dts_duration(),
+            std::time::Duration::from_millis(40)
+        );
+    }
+
+    #[test]
+    fn test_stream_type_conversions() {
+        let known = [
+            (StreamType::MP3, 0x04),
+            (StreamType::PrivateData, 0x06),
+            (StreamType::AAC, 0x0f),
+            (StreamType::H264, 0x1b),
+            (StreamType::Mpeg4AAC, 0x1c),
+            (StreamType::Opus, 0x9c),
+            (StreamType::AC3, 0x81),
+            (StreamType::EAC3, 0x87),
+        ];
+        for (stream_type, value) in known {
+            assert_eq!(u8::from(stream_type), value);
+            assert_eq!(StreamType::from(value), stream_type);
+        }
+
+        assert_eq!(StreamType::from(0x24), StreamType::Other(0x24));
+        assert_eq!(u8::from(StreamType::Other(0x24)), 0x24);
+
+        #[allow(deprecated)]
+        let constants = [PSI_STREAM_AAC, PSI_STREAM_H264, PSI_STREAM_AUDIO_OPUS];
+        assert_eq!(constants, [0x0f, 0x1b, 0x9c]);
+
+        let au = AccessUnit {
+            key: true,
+            pts: 0,
+            dts: 0,
+            data: Bytes::new(),
+            stream_type: 0x0f,
+            id: 0,
+        };
+        assert_eq!(au.stream_type(), StreamType::AAC);
+    }
+
+    #[test]
+    fn test_psi_stream_type_mapping() {
+        for audio_type in AUDIO_TYPES {
+            if let Some(stream_type) = audio_type.to_psi_stream_type() {
+                assert_eq!(
+                    AudioType::from_psi_stream_type(stream_type),
+                    Some(audio_type)
+                );
+            }
+        }
+        assert_eq!(AudioType::AAC.to_psi_stream_type(), Some(0x0f));
+        assert_eq!(AudioType::FLAC.to_psi_stream_type(), None);
+
+        // LATM AAC is still AAC, but AAC goes back to ADTS
+        assert_eq!(
+            AudioType::from_psi_stream_type(StreamType::Mpeg4AAC.into()),
+            Some(AudioType::AAC)
+        );
+        assert_eq!(
+            AudioType::from_psi_stream_type(StreamType::MP3.into()),
+            None
+        );
+
+        let au = AccessUnit {
+            key: true,
+            pts: 0,
+            dts: 0,
+            data: Bytes::new(),
+            stream_type: StreamType::H264.into(),
+            id: 0,
+        };
+        assert_eq!(
+            au.stream_type_enum(),
+            Some(Codec::Video(video::VideoType::H264))
+        );
+        assert_eq!(
+            Codec::Video(video::VideoType::H264).to_psi_stream_type(),
+            Some(StreamType::H264.into())
+        );
+        let au = AccessUnit {
+            stream_type: StreamType::EAC3.into(),
+            ..au
+        };
+        assert_eq!(au.stream_type_enum(), Some(Codec::Audio(AudioType::EAC3)));
+        let au = AccessUnit {
+            stream_type: StreamType::PrivateData.into(),
+            ..au
+        };
+        assert_eq!(au.stream_type_enum(), None);
+    }
+
+    #[test]
+    fn test_mime_and_codec_strings() {
+        for audio_type in AUDIO_TYPES {
+            // No wildcard, so a new variant has to be added here
+            let (mime, codecs) = match audio_type {
+                AudioType::Unknown => ("application/octet-stream", None),
+                AudioType::AAC => ("audio/aac", Some("mp4a.40.2")),
+                AudioType::FLAC => ("audio/flac", Some("flac")),
+                AudioType::Opus => ("audio/ogg", Some("opus")),
+                AudioType::AC3 => ("audio/ac3", Some("ac-3")),
+                AudioType::EAC3 => ("audio/eac3", Some("ec-3")),
+                AudioType::Vorbis => ("audio/ogg", Some("vorbis")),
+                AudioType::AIFF => ("audio/aiff", None),
+                AudioType::AmrNb => ("audio/amr", Some("samr")),
+                AudioType::AmrWb => ("audio/amr-wb", Some("sawb")),
+            };
+            assert_eq!(audio_type.mime_type(), mime);
+            assert_eq!(audio_type.codec_string(), codecs);
+
+            let expected = match codecs {
+                Some(codec) => format!("{}; codecs=\"{}\"", mime, codec),
+                None => mime.to_string(),
+            };
+            assert_eq!(audio_type.mime_with_codecs(None), expected);
+        }
+
+        let he_aac = CodecParams {
+            aac_object_type: Some(5),
+        };
+        assert_eq!(
+            AudioType::AAC.mime_with_codecs(Some(&he_aac)),
+            "audio/aac; codecs=\"mp4a.40.5\""
+        );
+        assert_eq!(
+            AudioType::FLAC.mime_with_codecs(Some(&he_aac)),
+            "audio/flac; codecs=\"flac\""
+        );
+    }
+
+    #[test]
+    fn test_detect_audio_report_skips_garbage() {
+        let mut frame =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 32, false).unwrap();
+        frame.extend_from_slice(&[0x11; 32]);
+
+        let mut data = vec![0x00, 0xFF, 0x12, 0xFF, 0xF1];
+        data.extend_from_slice(&frame);
+        data.extend_from_slice(&frame);
+        assert_eq!(
+            detect_audio_report(&data),
+            Some(DetectionReport {
+                audio_type: AudioType::AAC,
+                offset: 5,
+                header_len: Some(7),
+            })
+        );
+        assert_eq!(detect_audio_report(&data[5..]).unwrap().offset, 0);
+
+        // A lone frame after garbage is not enough to trust the sync
+        assert_eq!(detect_audio_report(&data[..5 + frame.len()]), None);
+
+        // A sync whose header does not decode is garbage too
+        let flac = std::fs::read("testdata/s24le.wav.flac").unwrap();
+        let mut data = vec![0xFF, 0xF8, 0x00];
+        data.extend_from_slice(&flac);
+        let report = detect_audio_report(&data).unwrap();
+        assert_eq!(report.audio_type, AudioType::FLAC);
+        assert_eq!(report.offset, 3);
+        assert_eq!(report.header_len, Some(6));
+
+        let report = detect_audio_report(&data[1..]).unwrap();
+        assert_eq!(report.audio_type, AudioType::FLAC);
+        assert_eq!(report.offset, 2);
+        assert_eq!(report.header_len, Some(6));
+
+        assert_eq!(detect_audio_report(&[0u8; 64]), None);
+    }
+
+    #[test]
+    fn test_detect_audio_report_header_len() {
+        let header_len = |data: &[u8]| detect_audio_report(data).unwrap().header_len;
+
+        let mut adts =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 4, true).unwrap();
+        adts.extend_from_slice(&[0x11; 4]);
+        assert_eq!(header_len(&[&adts[..], &adts].concat()), Some(9));
+
+        let loas = [
+            0x56, 0xE0, 0x04, 0x20, 0x00, 0x10, 0x30, 0x56, 0xE0, 0x02, 0x20, 0x00,
+        ];
+        assert_eq!(header_len(&loas), Some(3));
+
+        let mut ac3 = vec![0x0B, 0x77, 0x00, 0x00, 0x00, 0x40, 0x40, 0x00];
+        ac3.resize(128, 0);
+        assert_eq!(header_len(&ac3), Some(9));
+        let eac3 = [0x0B, 0x77, 0x01, 0x7F, 0x3F, 0x80, 0x00, 0x00];
+        assert_eq!(header_len(&eac3), Some(7));
+
+        let flac = std::fs::read("testdata/s24le.wav.flac").unwrap();
+        assert_eq!(header_len(&flac), Some(6));
+        let report = detect_audio_report(b"fLaC\x80\x00\x00\x22").unwrap();
+        assert_eq!(report.audio_type, AudioType::FLAC);
+        assert_eq!(report.header_len, None);
+
+        // Formats known by their magic have no frame header to report
+        let aiff =
+            aiff::tests::aiff_file(Some(b"sowt"), [0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(header_len(&aiff), None);
+        assert_eq!(header_len(b"#!AMR-WB\n\x44"), None);
+        let adif = b"ADIF\x10\x3E\x80\x00\x0A\x08\x02\x00\x40\x00\x00";
+        let report = detect_audio_report(adif).unwrap();
+        assert_eq!(report.audio_type, AudioType::AAC);
+        assert_eq!(report.header_len, None);
+
+        // Containers report their codec at the start, with no header
+        let ogg = ogg::tests::page(0x02, &[&vorbis::tests::id_header(1, 48000)]);
+        let report = detect_audio_report(&ogg).unwrap();
+        assert_eq!(report.audio_type, AudioType::Vorbis);
+        assert_eq!((report.offset, report.header_len), (0, None));
+
+        let frames: Vec<&[u8]> = flac::FlacFrameIter::new(&flac)
+            .map(|frame| frame.unwrap().data)
+            .collect();
+        let streaminfo = flac::build_streaminfo(&flac::StreamInfo {
+            min_block_size: 4096,
+            max_block_size: 4096,
+            min_frame_size: 0,
+            max_frame_size: 0,
+            sample_rate: 44100,
+            channels: 2,
+            bps: 16,
+            total_samples: 86 * 4096,
+            md5: [0; 16],
+        })
+        .unwrap();
+        let ogg_flac = flac::to_ogg(&streaminfo, frames).unwrap();
+        let report = detect_audio_report(&ogg_flac).unwrap();
+        assert_eq!(report.audio_type, AudioType::FLAC);
+        assert_eq!((report.offset, report.header_len), (0, None));
+
+        // A container without a known codec is not scanned for syncs
+        let mut webm = vec![0x1A, 0x45, 0xDF, 0xA3];
+        webm.extend_from_slice(&ogg_flac[ogg_flac.len() / 2..]);
+        assert_eq!(detect_audio_report(&webm), None);
+    }
+
+    fn id3v2_tag(size: usize) -> Vec<u8> {
+        let mut tag = b"ID3\x04\x00\x00".to_vec();
+        tag.extend((0..4).rev().map(|i| ((size >> (7 * i)) & 0x7F) as u8));
+        tag.resize(ID3V2_HEADER_LEN + size, 0xFF);
+        tag
+    }
+
+    #[test]
+    fn test_id3v2_size() {
+        assert_eq!(id3v2_size(&id3v2_tag(0)), Some(10));
+        assert_eq!(id3v2_size(&id3v2_tag(300_000)[..10]), Some(300_010));
+        assert_eq!(
+            id3v2_size(b"ID3\x04\x00\x10\x00\x00\x02\x01"),
+            Some(10 + 257 + 10)
+        );
+
+        assert_eq!(id3v2_size(b"ID3\x04\x00\x00\x00\x00\x80\x00"), None);
+        assert_eq!(id3v2_size(b"ID3\xFF\x00\x00\x00\x00\x00\x00"), None);
+        assert_eq!(id3v2_size(b"ID3\x04"), None);
+    }
+
+    #[test]
+    fn test_detect_audio_skips_id3v2() {
+        let mut frame =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 32, false).unwrap();
+        frame.extend_from_slice(&[0x11; 32]);
+
+        // A large tag whose padding is full of sync-like bytes
+        let mut data = id3v2_tag(100_000);
+        data.extend_from_slice(&frame);
+        data.extend_from_slice(&frame);
+
+        assert_eq!(detect_audio(&data), AudioType::AAC);
+        assert_eq!(detect_audio(&data[..100_010]), AudioType::Unknown);
+        assert_eq!(detect_audio_report(&data).unwrap().offset, 100_010);
+
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(&data[..4]), DetectStatus::NeedMoreData);
+        for chunk in data[4..100_012].chunks(4096) {
+            assert_eq!(detector.push(chunk), DetectStatus::NeedMoreData);
+        }
+        assert!(detector.buffer.len() < DETECT_PROBE_LEN);
+        assert_eq!(
+            detector.push(&data[100_012..]),
+            DetectStatus::Detected(AudioType::AAC)
+        );
+    }
+
+    #[test]
+    fn test_detect_audio_strict_testdata_prefixes() {
+        let flac = std::fs::read("testdata/s24le.wav.flac").unwrap();
+        for len in [64, 256, 512] {
+            assert_eq!(
+                detect_audio_strict(&flac[..len]),
+                StrictDetection::Confident(AudioType::FLAC),
+                "{} bytes",
+                len
+            );
+        }
+        assert_eq!(
+            detect_audio_strict(&flac[..4]),
+            StrictDetection::InsufficientData { need: 16 }
+        );
+    }
+
+    #[test]
+    fn test_detect_audio_strict() {
+        let mut frame =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 32, false).unwrap();
+        frame.extend_from_slice(&[0x11; 32]);
+        let mut stream = frame.clone();
+        stream.extend_from_slice(&frame);
+
+        assert_eq!(
+            detect_audio_strict(&stream),
+            StrictDetection::Confident(AudioType::AAC)
+        );
+        // One frame is only a weak match until the next header arrives
+        assert_eq!(
+            detect_audio_strict(&frame),
+            StrictDetection::InsufficientData { need: 39 + 7 }
+        );
+        assert_eq!(
+            detect_audio_strict(&stream[..3]),
+            StrictDetection::InsufficientData { need: 7 }
+        );
+
+        let mut junk = frame.clone();
+        junk.extend_from_slice(&[0u8; 16]);
+        assert_eq!(
+            detect_audio_strict(&junk),
+            StrictDetection::Ambiguous(vec![AudioType::AAC])
+        );
+
+        // 0xFFF8 is both a FLAC sync and a plausible ADTS header
+        let mut both = vec![0xFF, 0xF8, 0x50, 0x80, 0x08, 0x1F, 0xFC];
+        both.extend_from_slice(&[0u8; 64]);
+        assert_eq!(
+            detect_audio_strict(&both),
+            StrictDetection::Ambiguous(vec![AudioType::FLAC, AudioType::AAC])
+        );
+        assert_eq!(detect_audio(&both), AudioType::FLAC);
+
+        assert_eq!(detect_audio_strict(b"RIFF"), StrictDetection::Unknown);
+        assert_eq!(
+            detect_audio_strict(&[]),
+            StrictDetection::InsufficientData { need: 7 }
+        );
+    }
+
+    #[test]
+    fn test_detect_audio_strict_other_formats() {
+        let aiff =
+            aiff::tests::aiff_file(Some(b"sowt"), [0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            detect_audio_strict(&aiff),
+            StrictDetection::Confident(AudioType::AIFF)
+        );
+        let mut tagged = id3v2_tag(16);
+        tagged.extend_from_slice(&aiff[..6]);
+        assert_eq!(
+            detect_audio_strict(&tagged),
+            StrictDetection::InsufficientData { need: 26 + 12 }
+        );
+
+        assert_eq!(
+            detect_audio_strict(b"#!AMR-WB\n\x44"),
+            StrictDetection::Confident(AudioType::AmrWb)
+        );
+        assert_eq!(
+            detect_audio_strict(b"#!AMR"),
+            StrictDetection::InsufficientData { need: 6 }
+        );
+        assert_eq!(
+            detect_audio_strict(b"#!AMR-"),
+            StrictDetection::InsufficientData { need: 9 }
+        );
+
+        let adif = b"ADIF\x10\x3E\x80\x00\x0A\x08\x02\x00\x40\x00\x00";
+        assert_eq!(
+            detect_audio_strict(adif),
+            StrictDetection::Confident(AudioType::AAC)
+        );
+        assert_eq!(
+            detect_audio_strict(&adif[..8]),
+            StrictDetection::InsufficientData { need: 9 }
+        );
+
+        // 48 kHz 32 kbit/s stereo AC-3, and a 768-byte E-AC-3 frame
+        let mut ac3 = vec![0x0B, 0x77, 0x00, 0x00, 0x00, 0x40, 0x40, 0x00];
+        ac3.resize(128, 0);
+        assert_eq!(
+            detect_audio_strict(&[&ac3[..], &ac3].concat()),
+            StrictDetection::Confident(AudioType::AC3)
+        );
+        assert_eq!(
+            detect_audio_strict(&ac3),
+            StrictDetection::InsufficientData { need: 130 }
+        );
+        assert_eq!(
+            detect_audio_strict(&[&ac3[..], &[0u8; 2]].concat()),
+            StrictDetection::Ambiguous(vec![AudioType::AC3])
+        );
+        assert_eq!(
+            detect_audio_strict(&ac3[..4]),
+            StrictDetection::InsufficientData { need: 8 }
+        );
+        let mut eac3 = vec![0x0B, 0x77, 0x01, 0x7F, 0x3F, 0x80, 0x00, 0x00];
+        eac3.resize(768, 0);
+        assert_eq!(
+            detect_audio_strict(&[&eac3[..], &eac3].concat()),
+            StrictDetection::Confident(AudioType::EAC3)
+        );
+
+        let loas = [0x56, 0xE0, 0x02, 0x20, 0x00, 0x56, 0xE0, 0x02, 0x20, 0x00];
+        assert_eq!(
+            detect_audio_strict(&loas),
+            StrictDetection::Confident(AudioType::AAC)
+        );
+        assert_eq!(
+            detect_audio_strict(&loas[..6]),
+            StrictDetection::InsufficientData { need: 8 }
+        );
+        assert_eq!(
+            detect_audio_strict(&loas[..1]),
+            StrictDetection::InsufficientData { need: 3 }
+        );
+    }
+
+    #[test]
+    fn test_detect_audio_from_reader() {
+        let flac = std::fs::read("testdata/s24le.wav.flac").unwrap();
+        let mut reader = io::Cursor::new(&flac);
+        assert_eq!(
+            detect_audio_from_reader(&mut reader).unwrap(),
+            detect_audio(&flac)
+        );
+        assert_eq!(reader.position(), 0);
+
+        // A tag far larger than the window is seeked over
+        let mut data = id3v2_tag(1 << 20);
+        data.extend(
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 32, false).unwrap(),
+        );
+        data.extend_from_slice(&[0x11; 32]);
+        let mut reader = io::Cursor::new(&data);
+        assert_eq!(
+            detect_audio_from_reader_with(&mut reader, 1024).unwrap(),
+            AudioType::AAC
+        );
+
+        // Too small a window to see a whole ADTS header
+        let mut reader = io::Cursor::new(&data[ID3V2_HEADER_LEN + (1 << 20)..]);
+        assert_eq!(
+            detect_audio_from_reader_with(&mut reader, 4).unwrap(),
+            AudioType::Unknown
+        );
+    }
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("access-unit-{}-{}", std::process::id(), name))
+    }
+
+    fn mp4_box(box_type: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut b = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(box_type);
+        b.extend_from_slice(body);
+        b
+    }
+
+    #[test]
+    fn test_detect_from_path() {
+        assert_eq!(
+            detect_from_path("testdata/s24le.wav.flac").unwrap(),
+            AudioType::FLAC
+        );
+        assert!(detect_from_path("testdata/missing.flac").is_err());
+
+        // Matches both an unconfirmed FLAC header and an unconfirmed ADTS
+        // frame, so only here does the extension count
+        let mut both = vec![0xFF, 0xF8, 0x59, 0x18, 0x01, 0xE0];
+        both.resize(64, 0);
+        assert!(matches!(
+            detect_audio_strict(&both),
+            StrictDetection::Ambiguous(candidates) if candidates.len() == 2
+        ));
+        for (name, expected) in [
+            ("both.aac", AudioType::AAC),
+            ("both.flac", AudioType::FLAC),
+            ("both.opus", AudioType::FLAC),
+        ] {
+            let path = temp_file(name);
+            std::fs::write(&path, &both).unwrap();
+            assert_eq!(detect_from_path(&path).unwrap(), expected);
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        // Content wins over a misleading extension
+        let path = temp_file("frames.aac");
+        std::fs::copy("testdata/s24le.wav.flac", &path).unwrap();
+        assert_eq!(detect_from_path(&path).unwrap(), AudioType::FLAC);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reader_and_path_agree_on_containers() {
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(mp4_box(b"mp4a", &[0; 28]));
+        let stbl = mp4_box(b"stbl", &mp4_box(b"stsd", &stsd));
+        let trak = mp4_box(b"trak", &mp4_box(b"mdia", &mp4_box(b"minf", &stbl)));
+        let mut mp4 = mp4_box(b"ftyp", b"M4A \x00\x00\x00\x00");
+        mp4.extend(mp4_box(b"mdat", &[0; 64]));
+        mp4.extend(mp4_box(b"moov", &trak));
+
+        let ogg = ogg::tests::page(0x02, &[&vorbis::tests::id_header(1, 48000)]);
+        let mut caf = b"caff\x00\x01\x00\x00desc".to_vec();
+        caf.extend_from_slice(&32i64.to_be_bytes());
+        caf.extend_from_slice(&48000f64.to_be_bytes());
+        caf.extend_from_slice(b"opus");
+        caf.extend_from_slice(&[0; 20]);
+
+        for (name, data, expected) in [
+            ("agree.m4a", &mp4, AudioType::AAC),
+            ("agree.ogg", &ogg, AudioType::Vorbis),
+            ("agree.caf", &caf, AudioType::Opus),
+        ] {
+            // The reader starts part way into its data
+            let mut shifted = vec![0x00; 5];
+            shifted.extend_from_slice(data);
+            let mut reader = io::Cursor::new(&shifted);
+            reader.set_position(5);
+            assert_eq!(detect_audio_from_reader(&mut reader).unwrap(), expected);
+            assert_eq!(reader.position(), 5);
+
+            let path = temp_file(name);
+            std::fs::write(&path, data).unwrap();
+            assert_eq!(detect_from_path(&path).unwrap(), expected);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_detect_from_path_moov_at_end() {
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(mp4_box(b"mp4a", &[0; 28]));
+        let stbl = mp4_box(b"stbl", &mp4_box(b"stsd", &stsd));
+        let trak = mp4_box(b"trak", &mp4_box(b"mdia", &mp4_box(b"minf", &stbl)));
+        let moov = mp4_box(b"moov", &trak);
+
+        // A 4 GiB mdat with a 64-bit size, left as a hole in the file
+        let mdat_len: u64 = 4 << 30;
+        let mut head = mp4_box(b"ftyp", b"M4A \x00\x00\x00\x00");
+        let mdat_start = head.len() as u64;
+        head.extend(1u32.to_be_bytes());
+        head.extend_from_slice(b"mdat");
+        head.extend(mdat_len.to_be_bytes());
+
+        let path = temp_file("moov-at-end.m4a");
+        let mut file = File::create(&path).unwrap();
+        std::io::Write::write_all(&mut file, &head).unwrap();
+        file.set_len(mdat_start + mdat_len).unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        std::io::Write::write_all(&mut file, &moov).unwrap();
+        drop(file);
+
+        assert_eq!(detect_from_path(&path).unwrap(), AudioType::AAC);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_detect_audio_async() {
+        let flac = std::fs::read("testdata/s24le.wav.flac").unwrap();
+        assert_eq!(
+            detect_audio_async(&mut &flac[..]).await.unwrap(),
+            AudioType::FLAC
+        );
+
+        let mut data = id3v2_tag(100_000);
+        data.extend(
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 32, false).unwrap(),
+        );
+        data.extend_from_slice(&[0x11; 32]);
+        assert_eq!(
+            detect_audio_async(&mut &data[..]).await.unwrap(),
+            AudioType::AAC
+        );
+
+        // Input ends before a whole header arrives
+        assert_eq!(
+            detect_audio_async(&mut &data[..100_013]).await.unwrap(),
+            AudioType::Unknown
+        );
+
+        // A tag larger than the probe budget
+        let data = id3v2_tag(DEFAULT_PROBE_WINDOW);
+        assert_eq!(
+            detect_audio_async(&mut &data[..]).await.unwrap(),
+            AudioType::Unknown
+        );
+    }
+
+    #[test]
+    fn test_detect_audio_with_options() {
+        let mut frame =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 32, false).unwrap();
+        frame.extend_from_slice(&[0x11; 32]);
+        let mut data = vec![0u8; 300];
+        data.extend_from_slice(&frame);
+        data.extend_from_slice(&frame);
+
+        assert_eq!(detect_audio(&data), AudioType::Unknown);
+        let deep = DetectOptions {
+            max_probe_bytes: 512,
+            ..Default::default()
+        };
+        assert_eq!(detect_audio_with(&data, &deep), AudioType::AAC);
+        let shallow = DetectOptions {
+            max_probe_bytes: 299,
+            ..Default::default()
+        };
+        assert_eq!(detect_audio_with(&data, &shallow), AudioType::Unknown);
+
+        let no_aac = DetectOptions { aac: false, ..deep };
+        assert_eq!(detect_audio_with(&data, &no_aac), AudioType::Unknown);
+
+        // A lone frame passes the default sync check but not validation
+        let strict = DetectOptions {
+            require_validated: true,
+            ..Default::default()
+        };
+        assert_eq!(detect_audio_with(&frame, &strict), AudioType::Unknown);
+        assert_eq!(detect_audio_with(&data[300..], &strict), AudioType::AAC);
+
+        let flac = std::fs::read("testdata/s24le.wav.flac").unwrap();
+        assert_eq!(detect_audio_with(&flac, &strict), AudioType::FLAC);
+        let no_flac = DetectOptions {
+            flac: false,
+            ..Default::default()
+        };
+        assert_eq!(detect_audio_with(&flac, &no_flac), AudioType::Unknown);
+    }
+
+    #[test]
+    fn test_detect_media() {
+        let mut ogg = b"OggS\x00\x02".to_vec();
+        ogg.resize(26, 0);
+        ogg.extend_from_slice(&[1, 19]);
+        ogg.extend_from_slice(b"OpusHead\x01\x02\x38\x01\x80\xBB\x00\x00\x00\x00\x00");
+        assert_eq!(
+            detect_media(&ogg),
+            MediaFormat {
+                container: Some(ContainerType::Ogg),
+                codec: Some(AudioType::Opus),
+            }
+        );
+        assert_eq!(detect_audio(&ogg), AudioType::Unknown);
+
+        let vorbis = ogg::tests::page(0x02, &[&vorbis::tests::id_header(1, 48000)]);
+        assert_eq!(
+            detect_media(&vorbis),
+            MediaFormat {
+                container: Some(ContainerType::Ogg),
+                codec: Some(AudioType::Vorbis),
+            }
+        );
+
+        let aiff =
+            aiff::tests::aiff_file(Some(b"sowt"), [0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(detect_media(&aiff).container, None);
+        assert_eq!(detect_audio(&aiff), AudioType::AIFF);
+        assert_eq!(detect_audio(b"#!AMR-WB\n\x44"), AudioType::AmrWb);
+        assert_eq!(
+            detect_audio(b"ADIF\x10\x3E\x80\x00\x0A\x08\x02\x00\x40\x00\x00"),
+            AudioType::AAC
+        );
+
+        let wav = b"RIFF\x24\x00\x00\x00WAVEfmt ";
+        assert_eq!(detect_media(wav).container, Some(ContainerType::Wav));
+        assert_eq!(detect_media(wav).codec, None);
+
+        let mut caf = b"caff\x00\x01\x00\x00desc".to_vec();
+        caf.extend_from_slice(&32i64.to_be_bytes());
+        caf.extend_from_slice(&48000f64.to_be_bytes());
+        caf.extend_from_slice(b"opus");
+        caf.extend_from_slice(&[0; 20]);
+        assert_eq!(
+            detect_media(&caf),
+            MediaFormat {
+                container: Some(ContainerType::Caf),
+                codec: Some(AudioType::Opus),
+            }
+        );
+        assert_eq!(detect_audio(&caf), AudioType::Opus);
+        caf[28..32].copy_from_slice(b"aac ");
+        assert_eq!(detect_audio(&caf), AudioType::AAC);
+        caf[28..32].copy_from_slice(b"????");
+        assert_eq!(detect_audio(&caf), AudioType::Unknown);
+
+        let mp4 = b"\x00\x00\x00\x18ftypisom";
+        assert_eq!(detect_media(mp4).container, Some(ContainerType::Mp4));
+
+        let webm = [0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x86, 0x81, 0x01];
+        assert_eq!(detect_media(&webm).container, Some(ContainerType::WebM));
+
+        let mut adts =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 32, false).unwrap();
+        adts.extend_from_slice(&[0x11; 32]);
+        assert_eq!(
+            detect_media(&adts),
+            MediaFormat {
+                container: None,
+                codec: Some(AudioType::AAC),
+            }
+        );
+
+        assert_eq!(
+            detect_media(b"nothing here"),
+            MediaFormat {
+                container: None,
+                codec: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_mpegts_packet_size() {
+        let packets = |size: usize, sync_offset: usize, count: usize| {
+            let mut data = vec![0u8; size * count];
+            for n in 0..count {
+                data[n * size + sync_offset] = MPEGTS_SYNC_BYTE;
+            }
+            data
+        };
+
+        let ts = packets(188, 0, 3);
+        assert_eq!(mpegts_packet_size(&ts), Some(188));
+        assert_eq!(detect_media(&ts).container, Some(ContainerType::MpegTs));
+
+        let m2ts = packets(192, 4, 4);
+        assert_eq!(mpegts_packet_size(&m2ts), Some(192));
+        assert!(is_mpegts(&m2ts));
+
+        // Two packets, or a lone sync byte, are not enough
+        assert!(!is_mpegts(&ts[..2 * 188]));
+        let mut lone = vec![0u8; 1024];
+        lone[0] = MPEGTS_SYNC_BYTE;
+        assert!(!is_mpegts(&lone));
+    }
+
+    #[test]
+    fn test_detect_ac3() {
+        // Two 48 kHz 32 kbit/s stereo frames
+        let mut frame = vec![0x0B, 0x77, 0x00, 0x00, 0x00, 0x40, 0x40, 0x00];
+        frame.resize(128, 0);
+        let stream = [frame.clone(), frame.clone()].concat();
+
+        assert_eq!(detect_audio(&stream), AudioType::AC3);
+        assert_eq!(detect_media(&stream).codec, Some(AudioType::AC3));
+
+        let strict = DetectOptions {
+            require_validated: true,
+            ..Default::default()
+        };
+        assert_eq!(detect_audio_with(&stream, &strict), AudioType::AC3);
+        assert_eq!(detect_audio_with(&frame, &strict), AudioType::Unknown);
+        let no_ac3 = DetectOptions {
+            ac3: false,
+            ..Default::default()
+        };
+        assert_eq!(detect_audio_with(&stream, &no_ac3), AudioType::Unknown);
+
+        let eac3 = [0x0B, 0x77, 0x01, 0x7F, 0x3F, 0x80, 0x00, 0x00];
+        assert_eq!(detect_audio(&eac3), AudioType::EAC3);
+
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(&stream[..7]), DetectStatus::NeedMoreData);
+        assert_eq!(
+            detector.push(&stream[7..]),
+            DetectStatus::Detected(AudioType::AC3)
+        );
+    }
+
+    #[test]
+    fn test_detector_waits_for_full_header() {
+        let mut header =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 64, false).unwrap();
+        header.extend_from_slice(&[0u8; 64]);
+
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(&[]), DetectStatus::NeedMoreData);
+        assert_eq!(detector.push(&header[..3]), DetectStatus::NeedMoreData);
+        assert_eq!(detector.push(&header[3..6]), DetectStatus::NeedMoreData);
+        assert_eq!(
+            detector.push(&header[6..]),
+            DetectStatus::Detected(AudioType::AAC)
+        );
+        assert_eq!(
+            detector.push(&[0x00]),
+            DetectStatus::Detected(AudioType::AAC)
+        );
+    }
+
+    #[test]
+    fn test_detector_verdicts() {
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(&[0xFF]), DetectStatus::NeedMoreData);
+        assert_eq!(detector.clone().finish(), DetectStatus::Unknown);
+        assert_eq!(detector.push(&[0xF8, 0xC9]), DetectStatus::NeedMoreData);
+        assert_eq!(
+            detector.push(&[0xA8, 0x00, 0x8D, 0x4E]),
+            DetectStatus::Detected(AudioType::FLAC)
+        );
+
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(b"fL"), DetectStatus::NeedMoreData);
+        assert_eq!(
+            detector.push(b"aC\x80"),
+            DetectStatus::Detected(AudioType::FLAC)
+        );
+
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(b"RIFF"), DetectStatus::Unknown);
+        assert_eq!(detector.push(&[0xFF, 0xF8]), DetectStatus::Unknown);
+
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(&[0xFF, 0x00]), DetectStatus::Unknown);
+
+        // A sync followed by a header no detector accepts
+        let mut detector = Detector::new();
+        assert_eq!(
+            detector.push(&[0xFF, 0xF1, 0xFC]),
+            DetectStatus::NeedMoreData
+        );
+        assert_eq!(detector.push(&[0x00; 4]), DetectStatus::Unknown);
+    }
+
+    #[test]
+    fn test_detector_waits_for_magic() {
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(b"#!AM"), DetectStatus::NeedMoreData);
+        assert_eq!(detector.push(b"R-W"), DetectStatus::NeedMoreData);
+        assert_eq!(
+            detector.push(b"B\n\x44"),
+            DetectStatus::Detected(AudioType::AmrWb)
+        );
+
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(b"#!AMR"), DetectStatus::NeedMoreData);
+        assert_eq!(
+            detector.push(b"\n\x3C"),
+            DetectStatus::Detected(AudioType::AmrNb)
+        );
+
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(b"#!AMX"), DetectStatus::Unknown);
+
+        // The form type comes after the FORM size
+        let aiff =
+            aiff::tests::aiff_file(Some(b"sowt"), [0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0]);
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(&aiff[..4]), DetectStatus::NeedMoreData);
+        assert_eq!(detector.push(&aiff[4..11]), DetectStatus::NeedMoreData);
+        assert_eq!(
+            detector.push(&aiff[11..]),
+            DetectStatus::Detected(AudioType::AIFF)
+        );
+
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(b"FORM\0\0\0\x04"), DetectStatus::NeedMoreData);
+        assert_eq!(detector.push(b"8SVX"), DetectStatus::Unknown);
+
+        let adif = b"ADIF\x10\x3E\x80\x00\x0A\x08\x02\x00\x40\x00\x00";
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(&adif[..2]), DetectStatus::NeedMoreData);
+        assert_eq!(detector.push(&adif[2..9]), DetectStatus::NeedMoreData);
+        assert_eq!(
+            detector.push(&adif[9..]),
+            DetectStatus::Detected(AudioType::AAC)
+        );
+
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(b"ADIX"), DetectStatus::Unknown);
+    }
+
+    #[test]
+    fn test_detect_loas() {
+        let loas = [0x56, 0xE0, 0x02, 0x20, 0x00, 0x56, 0xE0, 0x02, 0x20, 0x00];
+        assert_eq!(detect_audio(&loas), AudioType::AAC);
+        assert_eq!(detect_audio(&loas[..6]), AudioType::Unknown);
+
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(&loas[..4]), DetectStatus::NeedMoreData);
+        assert_eq!(detector.push(&loas[4..7]), DetectStatus::NeedMoreData);
+        assert_eq!(
+            detector.push(&loas[7..]),
+            DetectStatus::Detected(AudioType::AAC)
+        );
+
+        let mut detector = Detector::new();
+        assert_eq!(detector.push(&[0x56, 0x00]), DetectStatus::Unknown);
     }
 }
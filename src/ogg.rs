@@ -0,0 +1,554 @@
+use crate::{flac, vorbis, AudioType};
+use bytes::{BufMut, Bytes, BytesMut};
+use std::fmt;
+use std::ops::Range;
+
+const OGG_PAGE_HEADER_LEN: usize = 27;
+const CRC_RANGE: Range<usize> = 22..26;
+const MAX_SEGMENTS: usize = 255;
+// libogg closes a page once its body passes about 4 KiB
+const PAGE_FILL: usize = 4096;
+
+pub const PAGE_CONTINUED: u8 = 0x01;
+pub const PAGE_BOS: u8 = 0x02;
+pub const PAGE_EOS: u8 = 0x04;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OggError {
+    MissingCapturePattern,
+    UnsupportedVersion(u8),
+    CrcMismatch,
+    UnexpectedEndOfInput,
+}
+
+impl fmt::Display for OggError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OggError::MissingCapturePattern => write!(f, "Missing OggS capture pattern"),
+            OggError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported Ogg version: {}", version)
+            }
+            OggError::CrcMismatch => write!(f, "Ogg page CRC mismatch"),
+            OggError::UnexpectedEndOfInput => write!(f, "Unexpected end of input"),
+        }
+    }
+}
+
+impl std::error::Error for OggError {}
+
+pub fn is_ogg(data: &[u8]) -> bool {
+    data.starts_with(b"OggS")
+}
+
+/// Returns the first packet of the first page, provided it ends on that
+/// page. Identification headers always do.
+pub fn first_packet(data: &[u8]) -> Option<&[u8]> {
+    if !is_ogg(data) {
+        return None;
+    }
+    let segments = *data.get(OGG_PAGE_HEADER_LEN - 1)? as usize;
+    let lacing = data.get(OGG_PAGE_HEADER_LEN..OGG_PAGE_HEADER_LEN + segments)?;
+
+    // A lacing value below 255 ends the packet
+    let end = lacing.iter().position(|&len| len < 255)?;
+    let len: usize = lacing[..=end].iter().map(|&len| len as usize).sum();
+    let body = OGG_PAGE_HEADER_LEN + segments;
+    data.get(body..body + len)
+}
+
+/// Identifies the codec of an Ogg stream from its first packet, which is
+/// the codec's identification header.
+pub fn codec(data: &[u8]) -> Option<AudioType> {
+    let packet = first_packet(data)?;
+    if packet.starts_with(b"OpusHead") {
+        Some(AudioType::Opus)
+    } else if flac::parse_ogg_mapping_header(packet).is_some() {
+        Some(AudioType::FLAC)
+    } else if vorbis::parse_vorbis_id_header(packet).is_some() {
+        Some(AudioType::Vorbis)
+    } else {
+        None
+    }
+}
+
+// CRC-32 with polynomial 0x04C11DB7, unreflected, with zero initial value
+// and no final xor.
+pub(crate) fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= (byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+// The checksum of a whole page, computed with its CRC field zeroed.
+fn page_crc(page: &[u8]) -> u32 {
+    let crc = crc32_update(0, &page[..CRC_RANGE.start]);
+    let crc = crc32_update(crc, &[0; 4]);
+    crc32_update(crc, &page[CRC_RANGE.end..])
+}
+
+/// An Ogg page, borrowing its lacing values and body from the input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OggPage<'a> {
+    pub header_type: u8,
+    /// `u64::MAX` when no packet ends on the page.
+    pub granule_position: u64,
+    pub serial: u32,
+    pub sequence: u32,
+    pub lacing: &'a [u8],
+    pub body: &'a [u8],
+}
+
+impl OggPage<'_> {
+    pub fn is_continued(&self) -> bool {
+        self.header_type & PAGE_CONTINUED != 0
+    }
+
+    pub fn is_bos(&self) -> bool {
+        self.header_type & PAGE_BOS != 0
+    }
+
+    pub fn is_eos(&self) -> bool {
+        self.header_type & PAGE_EOS != 0
+    }
+}
+
+/// Reads the page at the start of `data` and verifies its CRC, returning
+/// the page and its length.
+pub fn parse_page(data: &[u8]) -> Result<(OggPage<'_>, usize), OggError> {
+    let header = data
+        .get(..OGG_PAGE_HEADER_LEN)
+        .ok_or(OggError::UnexpectedEndOfInput)?;
+    if !is_ogg(header) {
+        return Err(OggError::MissingCapturePattern);
+    }
+    if header[4] != 0 {
+        return Err(OggError::UnsupportedVersion(header[4]));
+    }
+
+    let segments = header[OGG_PAGE_HEADER_LEN - 1] as usize;
+    let body_start = OGG_PAGE_HEADER_LEN + segments;
+    let lacing = data
+        .get(OGG_PAGE_HEADER_LEN..body_start)
+        .ok_or(OggError::UnexpectedEndOfInput)?;
+    let body_len: usize = lacing.iter().map(|&len| len as usize).sum();
+    let page = data
+        .get(..body_start + body_len)
+        .ok_or(OggError::UnexpectedEndOfInput)?;
+
+    let le_u32 = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+    if page_crc(page) != le_u32(CRC_RANGE.start) {
+        return Err(OggError::CrcMismatch);
+    }
+
+    Ok((
+        OggPage {
+            header_type: header[5],
+            granule_position: u64::from_le_bytes(header[6..14].try_into().unwrap()),
+            serial: le_u32(14),
+            sequence: le_u32(18),
+            lacing,
+            body: &page[body_start..],
+        },
+        page.len(),
+    ))
+}
+
+/// Reassembles the packets of the first logical stream in `data`. Pages of
+/// other streams are skipped, as is a packet continued from before the
+/// start of the data. The first bad page ends the iteration with an error.
+pub struct PacketIter<'a> {
+    data: &'a [u8],
+    pos: usize,
+    serial: Option<u32>,
+    page: Option<OggPage<'a>>,
+    segment: usize,
+    body_pos: usize,
+    packet: Vec<u8>,
+    in_packet: bool,
+    discard: bool,
+    done: bool,
+}
+
+impl<'a> PacketIter<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            serial: None,
+            page: None,
+            segment: 0,
+            body_pos: 0,
+            packet: Vec::new(),
+            in_packet: false,
+            discard: false,
+            done: false,
+        }
+    }
+
+    /// The serial number of the stream being read, once its first page
+    /// has been.
+    pub fn serial(&self) -> Option<u32> {
+        self.serial
+    }
+
+    fn next_page(&mut self) -> Option<Result<(), OggError>> {
+        loop {
+            if self.pos >= self.data.len() {
+                return None;
+            }
+            let (page, len) = match parse_page(&self.data[self.pos..]) {
+                Ok(page) => page,
+                Err(err) => return Some(Err(err)),
+            };
+            self.pos += len;
+            if *self.serial.get_or_insert(page.serial) != page.serial {
+                continue;
+            }
+
+            if page.is_continued() {
+                self.discard |= !self.in_packet;
+            } else {
+                // The rest of an unfinished packet was lost
+                self.packet.clear();
+                self.in_packet = false;
+            }
+            self.page = Some(page);
+            self.segment = 0;
+            self.body_pos = 0;
+            return Some(Ok(()));
+        }
+    }
+}
+
+impl Iterator for PacketIter<'_> {
+    type Item = Result<Vec<u8>, OggError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let Some(page) = self.page else {
+                match self.next_page() {
+                    Some(Ok(())) => continue,
+                    Some(Err(err)) => {
+                        self.done = true;
+                        return Some(Err(err));
+                    }
+                    None => {
+                        self.done = true;
+                        return None;
+                    }
+                }
+            };
+
+            let Some(&len) = page.lacing.get(self.segment) else {
+                self.page = None;
+                continue;
+            };
+            let len = len as usize;
+            self.segment += 1;
+            self.packet
+                .extend_from_slice(&page.body[self.body_pos..self.body_pos + len]);
+            self.body_pos += len;
+            self.in_packet = len == 255;
+
+            // A lacing value below 255 ends the packet
+            if len < 255 {
+                let packet = std::mem::take(&mut self.packet);
+                if !std::mem::take(&mut self.discard) {
+                    return Some(Ok(packet));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Lays packets of one logical stream out into pages, with CRCs.
+pub struct OggWriter {
+    serial: u32,
+    sequence: u32,
+    out: BytesMut,
+    lacing: Vec<u8>,
+    body: Vec<u8>,
+    // Granule position of the last packet ended on the pending page
+    granule_position: Option<u64>,
+    continued: bool,
+    last_page: Option<usize>,
+}
+
+impl OggWriter {
+    pub fn new(serial: u32) -> Self {
+        Self {
+            serial,
+            sequence: 0,
+            out: BytesMut::new(),
+            lacing: Vec::new(),
+            body: Vec::new(),
+            granule_position: None,
+            continued: false,
+            last_page: None,
+        }
+    }
+
+    /// Appends a packet, `granule_position` being the codec's position at
+    /// its end. A full page is written out once it passes about 4 KiB or
+    /// runs out of lacing values.
+    pub fn write_packet(&mut self, packet: &[u8], granule_position: u64) {
+        if self.body.len() >= PAGE_FILL {
+            self.write_page(0);
+        }
+
+        let mut last_len = 0;
+        for segment in packet.chunks(255) {
+            self.add_segment(segment);
+            last_len = segment.len();
+        }
+        // A packet filling its last segment is ended by an empty one
+        if matches!(last_len, 0 | 255) {
+            self.add_segment(&[]);
+        }
+        self.granule_position = Some(granule_position);
+    }
+
+    /// Writes out the pending page, so that the next packet starts a new
+    /// one. Header packets are laid out this way.
+    pub fn flush(&mut self) {
+        if !self.lacing.is_empty() {
+            self.write_page(0);
+        }
+    }
+
+    /// Writes out the pending page and marks the last page as the end of
+    /// the stream.
+    pub fn finish(mut self) -> Bytes {
+        match self.last_page {
+            Some(start) if self.lacing.is_empty() => {
+                let page = &mut self.out[start..];
+                page[5] |= PAGE_EOS;
+                let crc = page_crc(page);
+                page[CRC_RANGE].copy_from_slice(&crc.to_le_bytes());
+            }
+            _ => self.write_page(PAGE_EOS),
+        }
+        self.out.freeze()
+    }
+
+    fn add_segment(&mut self, segment: &[u8]) {
+        if self.lacing.len() == MAX_SEGMENTS {
+            let mid_packet = self.lacing.last() == Some(&255);
+            self.write_page(0);
+            self.continued = mid_packet;
+        }
+        self.lacing.push(segment.len() as u8);
+        self.body.extend_from_slice(segment);
+    }
+
+    fn write_page(&mut self, flags: u8) {
+        let mut header_type = flags;
+        if self.continued {
+            header_type |= PAGE_CONTINUED;
+        }
+        if self.sequence == 0 {
+            header_type |= PAGE_BOS;
+        }
+
+        let start = self.out.len();
+        self.out.put_slice(b"OggS\x00");
+        self.out.put_u8(header_type);
+        self.out
+            .put_u64_le(self.granule_position.take().unwrap_or(u64::MAX));
+        self.out.put_u32_le(self.serial);
+        self.out.put_u32_le(self.sequence);
+        self.out.put_u32_le(0);
+        self.out.put_u8(self.lacing.len() as u8);
+        self.out.put_slice(&self.lacing);
+        self.out.put_slice(&self.body);
+
+        let page = &mut self.out[start..];
+        let crc = page_crc(page);
+        page[CRC_RANGE].copy_from_slice(&crc.to_le_bytes());
+
+        self.sequence += 1;
+        self.last_page = Some(start);
+        self.continued = false;
+        self.lacing.clear();
+        self.body.clear();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn page(header_type: u8, packets: &[&[u8]]) -> Vec<u8> {
+        let mut lacing = Vec::new();
+        for packet in packets {
+            lacing.resize(lacing.len() + packet.len() / 255, 255);
+            lacing.push((packet.len() % 255) as u8);
+        }
+
+        let mut page = b"OggS\x00".to_vec();
+        page.push(header_type);
+        page.resize(OGG_PAGE_HEADER_LEN - 1, 0);
+        page.push(lacing.len() as u8);
+        page.extend(lacing);
+        for packet in packets {
+            page.extend_from_slice(packet);
+        }
+        page
+    }
+
+    #[test]
+    fn test_first_packet() {
+        let long = vec![0xAB; 600];
+        let data = page(0x02, &[&long, b"next"]);
+        assert_eq!(first_packet(&data), Some(&long[..]));
+        assert_eq!(first_packet(&data[..100]), None);
+        assert_eq!(first_packet(b"RIFF"), None);
+    }
+
+    #[test]
+    fn test_codec_distinguishes_opus_flac_vorbis() {
+        let opus = page(
+            0x02,
+            &[b"OpusHead\x01\x02\x38\x01\x80\xBB\x00\x00\x00\x00\x00"],
+        );
+        assert_eq!(codec(&opus), Some(AudioType::Opus));
+
+        let mut mapping = b"\x7FFLAC\x01\x00\x00\x01fLaC\x00\x00\x00\x22".to_vec();
+        mapping.extend(
+            flac::create_streaminfo(&flac::FLACFrameInfo {
+                block_size: 4096,
+                sample_rate: 48000,
+                channels: 2,
+                bps: 24,
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        assert_eq!(codec(&page(0x02, &[&mapping])), Some(AudioType::FLAC));
+        // The mapping signature alone is not enough
+        assert_eq!(codec(&page(0x02, &[&mapping[..13]])), None);
+
+        let vorbis = page(0x02, &[&vorbis::tests::id_header(2, 44100)]);
+        assert_eq!(codec(&vorbis), Some(AudioType::Vorbis));
+
+        let unknown = page(0x02, &[b"\x80theora"]);
+        assert_eq!(codec(&unknown), None);
+    }
+
+    fn read_pages(data: &[u8]) -> Vec<OggPage<'_>> {
+        let mut pages = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let (page, len) = parse_page(&data[pos..]).unwrap();
+            pages.push(page);
+            pos += len;
+        }
+        pages
+    }
+
+    #[test]
+    fn test_crc32_check_value() {
+        // CRC-32/CKSUM's check value without its final xor
+        assert_eq!(crc32_update(0, b"123456789"), 0x89A1_897F);
+        assert_eq!(crc32_update(0, b""), 0);
+    }
+
+    #[test]
+    fn test_writer_round_trip() {
+        let packets: Vec<Vec<u8>> = [30, 0, 255, 510, 140_000, 1, 4000, 4000]
+            .iter()
+            .enumerate()
+            .map(|(i, &len)| vec![i as u8; len])
+            .collect();
+
+        let mut writer = OggWriter::new(0x1234_5678);
+        writer.write_packet(&packets[0], 0);
+        writer.flush();
+        for (i, packet) in packets.iter().enumerate().skip(1) {
+            writer.write_packet(packet, i as u64 * 10);
+        }
+        let data = writer.finish();
+
+        let pages = read_pages(&data);
+        assert!(pages.len() > 3);
+        for (i, page) in pages.iter().enumerate() {
+            assert_eq!(page.serial, 0x1234_5678);
+            assert_eq!(page.sequence, i as u32);
+            assert_eq!(page.is_bos(), i == 0);
+            assert_eq!(page.is_eos(), i == pages.len() - 1);
+            assert!(page.lacing.len() <= MAX_SEGMENTS);
+        }
+        assert_eq!(pages[0].lacing, [30]);
+        assert_eq!(pages[0].granule_position, 0);
+
+        // The 140000-byte packet needs three pages of lacing values,
+        // and the middle one completes no packet
+        let spanned: Vec<_> = pages.iter().filter(|page| page.is_continued()).collect();
+        assert!(!spanned.is_empty());
+        assert!(pages.iter().any(|page| page.granule_position == u64::MAX));
+        assert_eq!(pages.last().unwrap().granule_position, 70);
+
+        let read: Vec<Vec<u8>> = PacketIter::new(&data).map(Result::unwrap).collect();
+        assert_eq!(read, packets);
+
+        // Finishing on a flushed page marks it rather than adding a page
+        let mut writer = OggWriter::new(1);
+        writer.write_packet(b"only", 4);
+        writer.flush();
+        let data = writer.finish();
+        let page = read_pages(&data);
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].header_type, PAGE_BOS | PAGE_EOS);
+    }
+
+    #[test]
+    fn test_packet_iter_errors_and_other_streams() {
+        let stream = |serial, packets: &[&[u8]]| {
+            let mut writer = OggWriter::new(serial);
+            for packet in packets {
+                writer.write_packet(packet, 0);
+                writer.flush();
+            }
+            writer.finish().to_vec()
+        };
+        let a = stream(1, &[b"a1", b"a2"]);
+        let b = stream(2, &[b"b1"]);
+
+        // Pages of a second stream are skipped
+        let mut data = a[..30].to_vec();
+        data.extend(&b);
+        data.extend(&a[30..]);
+        let mut packets = PacketIter::new(&data);
+        assert_eq!(packets.next(), Some(Ok(b"a1".to_vec())));
+        assert_eq!(packets.serial(), Some(1));
+        assert_eq!(packets.next(), Some(Ok(b"a2".to_vec())));
+        assert_eq!(packets.next(), None);
+
+        let mut corrupt = a.clone();
+        corrupt[29] ^= 0x01;
+        let mut packets = PacketIter::new(&corrupt);
+        assert_eq!(packets.next(), Some(Err(OggError::CrcMismatch)));
+        assert_eq!(packets.next(), None);
+
+        let mut packets = PacketIter::new(&a[..a.len() - 1]);
+        assert_eq!(packets.next(), Some(Ok(b"a1".to_vec())));
+        assert_eq!(packets.next(), Some(Err(OggError::UnexpectedEndOfInput)));
+
+        assert_eq!(
+            parse_page(&[b"RIFF".as_slice(), &[0; 40]].concat()),
+            Err(OggError::MissingCapturePattern)
+        );
+        let mut version = a.clone();
+        version[4] = 1;
+        assert_eq!(parse_page(&version), Err(OggError::UnsupportedVersion(1)));
+    }
+}
@@ -0,0 +1,232 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OpusMode {
+    Silk,
+    Hybrid,
+    Celt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Bandwidth {
+    Narrowband,
+    Mediumband,
+    Wideband,
+    SuperWideband,
+    Fullband,
+}
+
+/// Fields of the TOC byte that starts every Opus packet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TocInfo {
+    /// Configuration number, 0 to 31.
+    pub config: u8,
+    pub mode: OpusMode,
+    pub bandwidth: Bandwidth,
+    /// Duration of each frame in samples at 48 kHz.
+    pub frame_samples: u32,
+    pub stereo: bool,
+    /// 0: one frame, 1: two equal frames, 2: two frames of different
+    /// sizes, 3: an arbitrary number of frames.
+    pub frame_count_code: u8,
+}
+
+const MAX_FRAME_LEN: usize = 1275;
+// A packet holds at most 120 ms of audio.
+const MAX_PACKET_SAMPLES: u32 = 5760;
+const PROBE_PACKETS: usize = 4;
+
+pub fn parse_toc(toc: u8) -> TocInfo {
+    let config = toc >> 3;
+    let (mode, bandwidth, durations): (_, _, [u32; 4]) = match config {
+        0..=3 => (
+            OpusMode::Silk,
+            Bandwidth::Narrowband,
+            [480, 960, 1920, 2880],
+        ),
+        4..=7 => (
+            OpusMode::Silk,
+            Bandwidth::Mediumband,
+            [480, 960, 1920, 2880],
+        ),
+        8..=11 => (OpusMode::Silk, Bandwidth::Wideband, [480, 960, 1920, 2880]),
+        12..=13 => (
+            OpusMode::Hybrid,
+            Bandwidth::SuperWideband,
+            [480, 960, 480, 960],
+        ),
+        14..=15 => (OpusMode::Hybrid, Bandwidth::Fullband, [480, 960, 480, 960]),
+        16..=19 => (OpusMode::Celt, Bandwidth::Narrowband, [120, 240, 480, 960]),
+        20..=23 => (OpusMode::Celt, Bandwidth::Wideband, [120, 240, 480, 960]),
+        24..=27 => (
+            OpusMode::Celt,
+            Bandwidth::SuperWideband,
+            [120, 240, 480, 960],
+        ),
+        _ => (OpusMode::Celt, Bandwidth::Fullband, [120, 240, 480, 960]),
+    };
+
+    TocInfo {
+        config,
+        mode,
+        bandwidth,
+        frame_samples: durations[(config & 0x03) as usize],
+        stereo: toc & 0x04 != 0,
+        frame_count_code: toc & 0x03,
+    }
+}
+
+/// Number of frames in an Opus packet, or `None` if the packet breaks the
+/// framing rules of RFC 6716 section 3.4: frame lengths that do not add
+/// up, frames over 1275 bytes, or more than 120 ms of audio.
+pub fn frame_count(packet: &[u8]) -> Option<usize> {
+    let toc = parse_toc(*packet.first()?);
+    let payload = &packet[1..];
+
+    let frames = match toc.frame_count_code {
+        0 => (payload.len() <= MAX_FRAME_LEN).then_some(1)?,
+        1 => (payload.len() & 1 == 0 && payload.len() / 2 <= MAX_FRAME_LEN).then_some(2)?,
+        2 => {
+            let (first, header_len) = read_frame_length(payload)?;
+            let second = payload.len().checked_sub(header_len + first)?;
+            (first.max(second) <= MAX_FRAME_LEN).then_some(2)?
+        }
+        _ => {
+            let (&count_byte, mut rest) = payload.split_first()?;
+            let vbr = count_byte & 0x80 != 0;
+            let count = (count_byte & 0x3F) as usize;
+            if count == 0 || count as u32 * toc.frame_samples > MAX_PACKET_SAMPLES {
+                return None;
+            }
+
+            // Each 255 adds 254 bytes of padding and another length byte
+            let mut padding = 0;
+            if count_byte & 0x40 != 0 {
+                loop {
+                    let (&byte, tail) = rest.split_first()?;
+                    rest = tail;
+                    padding += if byte == 255 { 254 } else { byte as usize };
+                    if byte != 255 {
+                        break;
+                    }
+                }
+            }
+            let mut data_len = rest.len().checked_sub(padding)?;
+
+            if vbr {
+                for _ in 0..count - 1 {
+                    let (len, header_len) = read_frame_length(rest)?;
+                    if len > MAX_FRAME_LEN {
+                        return None;
+                    }
+                    rest = &rest[header_len..];
+                    data_len = data_len.checked_sub(header_len + len)?;
+                }
+                if data_len > MAX_FRAME_LEN {
+                    return None;
+                }
+            } else if data_len % count != 0 || data_len / count > MAX_FRAME_LEN {
+                return None;
+            }
+            count
+        }
+    };
+
+    (frames as u32 * toc.frame_samples <= MAX_PACKET_SAMPLES).then_some(frames)
+}
+
+pub fn is_opus_packet(packet: &[u8]) -> bool {
+    frame_count(packet).is_some()
+}
+
+/// Validates the first few packets of a raw Opus stream, such as the
+/// chunks of a length-prefixed recording: each must be a well-formed Opus
+/// packet and all must agree on the channel count.
+pub fn is_opus_stream<'a>(packets: impl IntoIterator<Item = &'a [u8]>) -> bool {
+    let mut stereo = None;
+    let mut checked = 0;
+    for packet in packets.into_iter().take(PROBE_PACKETS) {
+        if !is_opus_packet(packet) {
+            return false;
+        }
+        let packet_stereo = parse_toc(packet[0]).stereo;
+        if *stereo.get_or_insert(packet_stereo) != packet_stereo {
+            return false;
+        }
+        checked += 1;
+    }
+    checked > 0
+}
+
+// Frame lengths under 252 take one byte, longer ones a second byte
+// counting in fours.
+fn read_frame_length(data: &[u8]) -> Option<(usize, usize)> {
+    match *data.first()? {
+        len @ 0..=251 => Some((len as usize, 1)),
+        first => Some((first as usize + *data.get(1)? as usize * 4, 2)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_toc() {
+        assert_eq!(
+            parse_toc(0xFC),
+            TocInfo {
+                config: 31,
+                mode: OpusMode::Celt,
+                bandwidth: Bandwidth::Fullband,
+                frame_samples: 960,
+                stereo: true,
+                frame_count_code: 0,
+            }
+        );
+
+        let toc = parse_toc(0x53);
+        assert_eq!(toc.mode, OpusMode::Silk);
+        assert_eq!(toc.bandwidth, Bandwidth::Wideband);
+        assert_eq!(toc.frame_samples, 1920);
+        assert!(!toc.stereo);
+        assert_eq!(toc.frame_count_code, 3);
+
+        assert_eq!(parse_toc(0x68).mode, OpusMode::Hybrid);
+    }
+
+    #[test]
+    fn test_frame_count() {
+        assert_eq!(frame_count(&[0xF8, 0xFF, 0xFE]), Some(1));
+        assert_eq!(frame_count(&[0xF9, 0x01, 0x02]), Some(2));
+        assert_eq!(frame_count(&[0xF9, 0x01]), None);
+
+        // Code 2: a 2-byte first frame, then the rest
+        assert_eq!(frame_count(&[0xFA, 0x02, 0xAA, 0xBB, 0xCC]), Some(2));
+        assert_eq!(frame_count(&[0xFA, 0x05, 0xAA]), None);
+
+        // Code 3: three CBR frames of two bytes with two bytes of padding
+        assert_eq!(
+            frame_count(&[0xFB, 0x43, 0x02, 1, 2, 3, 4, 5, 6, 0, 0]),
+            Some(3)
+        );
+        assert_eq!(frame_count(&[0xFB, 0x03, 1, 2, 3, 4, 5]), None);
+        // VBR frames of one and three bytes
+        assert_eq!(frame_count(&[0xFB, 0x82, 0x01, 0xAA, 1, 2, 3]), Some(2));
+        // Three 60 ms frames exceed 120 ms
+        assert_eq!(frame_count(&[0x1B, 0x03, 1, 2, 3]), None);
+        assert_eq!(frame_count(&[0xFB, 0x00]), None);
+        assert_eq!(frame_count(&[]), None);
+    }
+
+    #[test]
+    fn test_is_opus_stream() {
+        let mono: &[u8] = &[0xF8, 0xFF, 0xFE];
+        let stereo: &[u8] = &[0xFC, 0xFF, 0xFE];
+        assert!(is_opus_stream([mono, mono, mono]));
+        assert!(!is_opus_stream([mono, stereo]));
+        assert!(!is_opus_stream([mono, &[0xF9, 0x01][..]]));
+        assert!(!is_opus_stream(std::iter::empty()));
+    }
+}
@@ -0,0 +1,426 @@
+use crate::{aac, flac, id3v2_size, AccessUnit, AudioType};
+use std::fmt::Write;
+use std::ops::Range;
+use std::time::Duration;
+
+/// Which keyframe to cut on when the target falls between two.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum KeyframePreference {
+    #[default]
+    Earlier,
+    Later,
+    Nearest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CutOptions {
+    pub prefer: KeyframePreference,
+}
+
+/// Achievable cut points for a requested time, in `timescale` ticks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CutPoints {
+    /// Last video keyframe at or before the target.
+    pub preceding_keyframe: Option<u64>,
+    /// First video keyframe at or after the target.
+    pub following_keyframe: Option<u64>,
+    /// The keyframe chosen according to the preference.
+    pub video: Option<u64>,
+    /// Audio frame boundary closest to the chosen video point, or to the
+    /// target when there is no video keyframe.
+    pub audio: Option<u64>,
+    /// `audio - video` when both cut points exist.
+    pub skew: Option<i64>,
+}
+
+pub fn find_cut_points(
+    video: &[AccessUnit],
+    audio: &[AccessUnit],
+    target: Duration,
+    timescale: u32,
+) -> CutPoints {
+    find_cut_points_with(video, audio, target, timescale, &CutOptions::default())
+}
+
+pub fn find_cut_points_with(
+    video: &[AccessUnit],
+    audio: &[AccessUnit],
+    target: Duration,
+    timescale: u32,
+    options: &CutOptions,
+) -> CutPoints {
+    let target = duration_to_ticks(target, timescale);
+    let keyframes = || video.iter().filter(|au| au.key).map(|au| au.pts);
+
+    let preceding_keyframe = keyframes().filter(|&pts| pts <= target).max();
+    let following_keyframe = keyframes().filter(|&pts| pts >= target).min();
+
+    let video_cut = match options.prefer {
+        KeyframePreference::Earlier => preceding_keyframe.or(following_keyframe),
+        KeyframePreference::Later => following_keyframe.or(preceding_keyframe),
+        KeyframePreference::Nearest => match (preceding_keyframe, following_keyframe) {
+            (Some(before), Some(after)) if after - target < target - before => Some(after),
+            (Some(before), _) => Some(before),
+            (None, after) => after,
+        },
+    };
+
+    let anchor = video_cut.unwrap_or(target);
+    let audio_cut = audio
+        .iter()
+        .map(|au| au.pts)
+        .min_by_key(|&pts| (pts.abs_diff(anchor), pts));
+
+    let skew = match (video_cut, audio_cut) {
+        (Some(video), Some(audio)) => Some(audio as i64 - video as i64),
+        _ => None,
+    };
+
+    CutPoints {
+        preceding_keyframe,
+        following_keyframe,
+        video: video_cut,
+        audio: audio_cut,
+        skew,
+    }
+}
+
+/// A run of whole frames addressed by byte range within the source file.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ByteRangeSegment {
+    pub range: Range<u64>,
+    pub duration: Duration,
+    pub frames: usize,
+}
+
+/// Plans segments of at least `target` duration on frame boundaries of an
+/// ADTS or raw FLAC stream, for `#EXT-X-BYTERANGE` playlists over the
+/// original file. The ranges tile the frames from the first one, after any
+/// ID3v2 tag or FLAC metadata, to the last complete one; the final segment
+/// may be shorter.
+pub fn byte_range_plan(
+    data: &[u8],
+    audio_type: AudioType,
+    target: Duration,
+) -> Vec<ByteRangeSegment> {
+    let start = id3v2_size(data).unwrap_or(0).min(data.len());
+    let frames = match audio_type {
+        AudioType::AAC => adts_frames(data, start),
+        AudioType::FLAC => flac_frames(data, start),
+        _ => Vec::new(),
+    };
+
+    let mut plan: Vec<ByteRangeSegment> = Vec::new();
+    let mut samples = 0;
+    for frame in frames {
+        let open = plan.last().filter(|segment| segment.duration < target);
+        if open.is_none() {
+            samples = 0;
+            plan.push(ByteRangeSegment {
+                range: frame.range.start..frame.range.start,
+                duration: Duration::ZERO,
+                frames: 0,
+            });
+        }
+
+        let segment = plan.last_mut().unwrap();
+        samples += frame.samples;
+        segment.range.end = frame.range.end;
+        segment.duration = samples_to_duration(samples, frame.sample_rate);
+        segment.frames += 1;
+    }
+
+    plan
+}
+
+/// Renders a plan as an HLS media playlist in which every segment is a
+/// byte range of `uri`.
+pub fn byte_range_playlist(plan: &[ByteRangeSegment], uri: &str) -> String {
+    let target = plan
+        .iter()
+        .map(|segment| segment.duration.as_secs_f64().ceil() as u64)
+        .max()
+        .unwrap_or(0);
+
+    let mut playlist = String::new();
+    writeln!(playlist, "#EXTM3U").unwrap();
+    writeln!(playlist, "#EXT-X-VERSION:4").unwrap();
+    writeln!(playlist, "#EXT-X-TARGETDURATION:{}", target).unwrap();
+    writeln!(playlist, "#EXT-X-MEDIA-SEQUENCE:0").unwrap();
+    for segment in plan {
+        writeln!(playlist, "#EXTINF:{:.3},", segment.duration.as_secs_f64()).unwrap();
+        writeln!(
+            playlist,
+            "#EXT-X-BYTERANGE:{}@{}",
+            segment.range.end - segment.range.start,
+            segment.range.start
+        )
+        .unwrap();
+        writeln!(playlist, "{}", uri).unwrap();
+    }
+    writeln!(playlist, "#EXT-X-ENDLIST").unwrap();
+    playlist
+}
+
+struct FrameSpan {
+    range: Range<u64>,
+    samples: u64,
+    sample_rate: u32,
+}
+
+// Stops at the first byte that does not start a complete frame, leaving
+// trailing tags out of the plan.
+fn adts_frames(data: &[u8], mut pos: usize) -> Vec<FrameSpan> {
+    let mut frames = Vec::new();
+    while let Ok(header) = aac::parse_adts_header(&data[pos..]) {
+        if pos + header.frame_length > data.len() {
+            break;
+        }
+        frames.push(FrameSpan {
+            range: pos as u64..(pos + header.frame_length) as u64,
+            samples: header.samples_per_frame() as u64,
+            sample_rate: header.sample_rate,
+        });
+        pos += header.frame_length;
+    }
+    frames
+}
+
+// Raw FLAC frames carry no length, so they are cut where
+// [`flac::FlacFrameIter`] finds their CRC-16 checks. Like the ADTS walk,
+// this stops at the first error or a frame cut short.
+fn flac_frames(data: &[u8], start: usize) -> Vec<FrameSpan> {
+    // A native file's frames follow its metadata blocks
+    let start = if data[start..].starts_with(b"fLaC") {
+        match flac::parse_stream(&data[start..]) {
+            Ok(stream) => start + stream.first_frame_offset,
+            Err(_) => return Vec::new(),
+        }
+    } else {
+        start
+    };
+    flac::FlacFrameIter::new(&data[start..])
+        .map_while(Result::ok)
+        .map(|frame| {
+            let offset = frame.data.as_ptr() as usize - data.as_ptr() as usize;
+            FrameSpan {
+                range: offset as u64..(offset + frame.data.len()) as u64,
+                samples: frame.info.block_size as u64,
+                sample_rate: frame.info.sample_rate,
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn samples_to_duration(samples: u64, sample_rate: u32) -> Duration {
+    Duration::from_nanos((samples as u128 * 1_000_000_000 / sample_rate.max(1) as u128) as u64)
+}
+
+// Rounds to the nearest tick.
+fn duration_to_ticks(duration: Duration, timescale: u32) -> u64 {
+    ((duration.as_nanos() * timescale as u128 + 500_000_000) / 1_000_000_000) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    const TIMESCALE: u32 = 90_000;
+
+    fn au(pts: u64, key: bool) -> AccessUnit {
+        AccessUnit {
+            key,
+            pts,
+            dts: pts,
+            data: Bytes::new(),
+            stream_type: 0,
+            id: 0,
+        }
+    }
+
+    // 25 fps video with a keyframe every two seconds, starting at 1 s
+    fn video() -> Vec<AccessUnit> {
+        (0..150)
+            .map(|i| au(90_000 + i * 3_600, i % 50 == 0))
+            .collect()
+    }
+
+    // 1024-sample AAC frames at 48 kHz
+    fn audio() -> Vec<AccessUnit> {
+        (0..300).map(|i| au(90_000 + i * 1_920, true)).collect()
+    }
+
+    #[test]
+    fn test_cut_between_keyframes() {
+        let cut = find_cut_points(&video(), &audio(), Duration::from_millis(3_500), TIMESCALE);
+        assert_eq!(cut.preceding_keyframe, Some(270_000));
+        assert_eq!(cut.following_keyframe, Some(450_000));
+        assert_eq!(cut.video, Some(270_000));
+        // 270_000 - 90_000 = 180_000 = 93.75 frames of 1920 ticks
+        assert_eq!(cut.audio, Some(90_000 + 94 * 1_920));
+        assert_eq!(cut.skew, Some(480));
+
+        let options = CutOptions {
+            prefer: KeyframePreference::Later,
+        };
+        let later = find_cut_points_with(
+            &video(),
+            &audio(),
+            Duration::from_millis(3_500),
+            TIMESCALE,
+            &options,
+        );
+        assert_eq!(later.video, Some(450_000));
+
+        let options = CutOptions {
+            prefer: KeyframePreference::Nearest,
+        };
+        let nearest = find_cut_points_with(
+            &video(),
+            &audio(),
+            Duration::from_millis(4_500),
+            TIMESCALE,
+            &options,
+        );
+        assert_eq!(nearest.video, Some(450_000));
+    }
+
+    #[test]
+    fn test_cut_exactly_on_keyframe() {
+        let cut = find_cut_points(&video(), &audio(), Duration::from_secs(3), TIMESCALE);
+        assert_eq!(cut.preceding_keyframe, Some(270_000));
+        assert_eq!(cut.following_keyframe, Some(270_000));
+        assert_eq!(cut.video, Some(270_000));
+    }
+
+    #[test]
+    fn test_cut_outside_keyframe_range() {
+        let before = find_cut_points(&video(), &audio(), Duration::from_millis(500), TIMESCALE);
+        assert_eq!(before.preceding_keyframe, None);
+        assert_eq!(before.following_keyframe, Some(90_000));
+        assert_eq!(before.video, Some(90_000));
+        assert_eq!(before.audio, Some(90_000));
+        assert_eq!(before.skew, Some(0));
+
+        let after = find_cut_points(&video(), &audio(), Duration::from_secs(60), TIMESCALE);
+        assert_eq!(after.preceding_keyframe, Some(450_000));
+        assert_eq!(after.following_keyframe, None);
+        assert_eq!(after.video, Some(450_000));
+    }
+
+    #[test]
+    fn test_byte_range_plan_adts() {
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x05".to_vec();
+        data.extend_from_slice(&[0u8; 5]);
+        for i in 0..100 {
+            data.extend(
+                aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 48000, 50 + i, false)
+                    .unwrap(),
+            );
+            data.extend(vec![0x11; 50 + i]);
+        }
+        let payload_end = data.len() as u64;
+        data.extend_from_slice(b"TAG");
+        data.extend_from_slice(&[0u8; 125]);
+
+        // 1024 samples at 48 kHz is 21.333 ms, so 47 frames make a second
+        let plan = byte_range_plan(&data, AudioType::AAC, Duration::from_secs(1));
+        assert_eq!(
+            plan.iter().map(|s| s.frames).collect::<Vec<_>>(),
+            vec![47, 47, 6]
+        );
+        assert_eq!(plan[0].range.start, 15);
+        assert_eq!(plan.last().unwrap().range.end, payload_end);
+        assert!(plan.windows(2).all(|w| w[0].range.end == w[1].range.start));
+        assert_eq!(plan[0].duration, Duration::from_nanos(1_002_666_666));
+
+        let playlist = byte_range_playlist(&plan, "audio.aac");
+        assert!(playlist.starts_with("#EXTM3U\n#EXT-X-VERSION:4\n#EXT-X-TARGETDURATION:2\n"));
+        assert!(playlist.contains(&format!(
+            "#EXTINF:1.003,\n#EXT-X-BYTERANGE:{}@15\naudio.aac\n",
+            plan[0].range.end - 15
+        )));
+        assert!(playlist.ends_with("audio.aac\n#EXT-X-ENDLIST\n"));
+    }
+
+    #[test]
+    fn test_byte_range_plan_flac() {
+        let data = std::fs::read("testdata/s24le.wav.flac").unwrap();
+        let plan = byte_range_plan(&data, AudioType::FLAC, Duration::from_secs(1));
+
+        // 86 frames of 4096 samples at 44.1 kHz, 11 frames per segment
+        let frames: usize = plan.iter().map(|s| s.frames).sum();
+        assert_eq!(frames, 86);
+        assert_eq!(plan[0].frames, 11);
+        assert_eq!(plan[0].range.start, 0);
+        assert_eq!(plan.last().unwrap().range.end, data.len() as u64);
+        assert!(plan.windows(2).all(|w| w[0].range.end == w[1].range.start));
+        assert!(plan
+            .iter()
+            .all(|s| s.duration >= Duration::from_secs(1) || s.range.end == data.len() as u64));
+    }
+
+    #[test]
+    fn test_byte_range_plan_native_flac() {
+        let frames = std::fs::read("testdata/s24le.wav.flac").unwrap();
+        let streaminfo = flac::build_streaminfo(&flac::StreamInfo {
+            min_block_size: 4096,
+            max_block_size: 4096,
+            min_frame_size: 0,
+            max_frame_size: 0,
+            sample_rate: 44100,
+            channels: 2,
+            bps: 16,
+            total_samples: 86 * 4096,
+            md5: [0; 16],
+        })
+        .unwrap();
+        let mut data = b"fLaC\x80\x00\x00\x22".to_vec();
+        data.extend_from_slice(&streaminfo);
+        let metadata_len = data.len() as u64;
+        data.extend_from_slice(&frames);
+
+        let native = byte_range_plan(&data, AudioType::FLAC, Duration::from_secs(1));
+        let raw = byte_range_plan(&frames, AudioType::FLAC, Duration::from_secs(1));
+        assert_eq!(native.len(), raw.len());
+        assert_eq!(native[0].range.start, metadata_len);
+        for (native, raw) in native.iter().zip(&raw) {
+            assert_eq!(native.range.start, raw.range.start + metadata_len);
+            assert_eq!(native.frames, raw.frames);
+        }
+
+        // Metadata cut short gives no plan rather than one over its bytes
+        assert!(byte_range_plan(&data[..20], AudioType::FLAC, Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn test_byte_range_plan_flac_header_in_residual() {
+        let data = std::fs::read("testdata/s24le.wav.flac").unwrap();
+        let first = flac::FlacFrameIter::new(&data).next().unwrap().unwrap();
+        let len = first.data.len();
+
+        // Plant the second frame's header, CRC-8 included, inside the first
+        // frame and fix up its CRC-16 so the frame still checks
+        let mut data = data.clone();
+        let header = data[len..len + 7].to_vec();
+        data[len / 2..len / 2 + 7].copy_from_slice(&header);
+        let crc = data[..len - 2]
+            .iter()
+            .fold(0, |crc, &byte| flac::crc16_update(crc, byte));
+        data[len - 2..len].copy_from_slice(&crc.to_be_bytes());
+
+        let plan = byte_range_plan(&data, AudioType::FLAC, Duration::from_secs(1));
+        assert_eq!(plan.iter().map(|s| s.frames).sum::<usize>(), 86);
+        assert_eq!(plan[0].frames, 11);
+    }
+
+    #[test]
+    fn test_cut_audio_only() {
+        let cut = find_cut_points(&[], &audio(), Duration::from_secs(2), TIMESCALE);
+        assert_eq!(cut.video, None);
+        assert_eq!(cut.audio, Some(90_000 + 47 * 1_920));
+        assert_eq!(cut.skew, None);
+    }
+}
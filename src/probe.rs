@@ -0,0 +1,416 @@
+use crate::package::samples_to_duration;
+use crate::{
+    aac, ac3, aiff, caf, detect_media, flac, id3v2_size, ogg, timing, vorbis, AudioType,
+    ContainerType,
+};
+use std::time::Duration;
+
+/// Stream parameters found by [`probe`]. A field is `None` when the format
+/// does not carry it in the bytes that were looked at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AudioProbe {
+    pub audio_type: AudioType,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u8>,
+    pub bits_per_sample: Option<u8>,
+    /// Only from headers that state the length of the whole stream.
+    pub duration: Option<Duration>,
+}
+
+impl AudioProbe {
+    fn new(audio_type: AudioType) -> Self {
+        Self {
+            audio_type,
+            sample_rate: None,
+            channels: None,
+            bits_per_sample: None,
+            duration: None,
+        }
+    }
+}
+
+// Opus always decodes at 48 kHz, whatever rate the input had.
+const OPUS_SAMPLE_RATE: u32 = 48000;
+
+/// Detects the audio format like [`detect_media`] and reads its sample
+/// rate, channel count, bit depth and duration from the first header that
+/// has them: the ADTS or ADIF header, FLAC STREAMINFO or, for raw frames,
+/// a FLAC frame header, an AC-3 or E-AC-3 frame header, the Ogg
+/// identification header, the AIFF COMM chunk, the CAF `desc`
+/// chunk, or the MP4 sample entry and `mdhd`.
+///
+/// Returns `None` when no audio format is recognised.
+pub fn probe(data: &[u8]) -> Option<AudioProbe> {
+    let media = detect_media(data);
+    match media.container {
+        Some(ContainerType::Mp4) => {
+            let (_, moov) =
+                timing::top_level_boxes(data).find(|(box_type, _)| box_type == b"moov")?;
+            mp4_audio_track(moov)
+        }
+        Some(ContainerType::Ogg) => probe_ogg(data, media.codec?),
+        Some(ContainerType::Caf) => probe_caf(data),
+        Some(_) => None,
+        None => {
+            let frame = data.get(id3v2_size(data).unwrap_or(0)..)?;
+            probe_bare(frame, media.codec?)
+        }
+    }
+}
+
+fn probe_bare(frame: &[u8], audio_type: AudioType) -> Option<AudioProbe> {
+    let mut probe = AudioProbe::new(audio_type);
+    match audio_type {
+        AudioType::AIFF => {
+            let info = aiff::parse_comm(frame)?;
+            probe.sample_rate = nonzero(info.sample_rate);
+            probe.channels = u8::try_from(info.channels).ok();
+            probe.bits_per_sample = u8::try_from(info.bits_per_sample).ok();
+            probe.duration = probe
+                .sample_rate
+                .map(|rate| samples_to_duration(info.sample_frames as u64, rate));
+        }
+        AudioType::AmrNb | AudioType::AmrWb => {
+            probe.sample_rate = Some(if audio_type == AudioType::AmrNb {
+                8000
+            } else {
+                16000
+            });
+            probe.channels = Some(1);
+        }
+        AudioType::AAC => {
+            if let Some(header) = aac::parse_adif_header(frame) {
+                probe.sample_rate = nonzero(header.program.sample_rate);
+                probe.channels = nonzero(header.program.channels);
+            } else if aac::is_aac_single_frame(frame) {
+                probe.sample_rate = aac::sample_rate_from_index((frame[2] >> 2) & 0x0F);
+                probe.channels = match ((frame[2] & 0x01) << 2) | (frame[3] >> 6) {
+                    // Zero means the configuration is in the payload
+                    0 => None,
+                    7 => Some(8),
+                    config => Some(config),
+                };
+            }
+            // LOAS keeps its configuration in the bitstream; left unset
+        }
+        AudioType::FLAC if frame.starts_with(b"fLaC") => {
+            // STREAMINFO comes first, so later blocks cut short by the end
+            // of the data do not hide it
+            let info = match flac::parse_stream(frame) {
+                Ok(stream) => stream.stream_info,
+                Err(_) if frame.get(4).map(|byte| byte & 0x7F) == Some(0) => {
+                    flac::parse_streaminfo(frame.get(8..)?)?
+                }
+                Err(_) => return None,
+            };
+            fill_streaminfo(&mut probe, &info);
+        }
+        AudioType::FLAC => {
+            let info = flac::decode_frame_header(frame).ok()?;
+            probe.sample_rate = nonzero(info.sample_rate);
+            probe.channels = nonzero(info.channels);
+            probe.bits_per_sample = nonzero(info.bps);
+        }
+        AudioType::AC3 | AudioType::EAC3 => {
+            let header = ac3::parse_syncframe_header(frame)?;
+            probe.sample_rate = nonzero(header.sample_rate);
+            probe.channels = nonzero(header.channels);
+        }
+        _ => {}
+    }
+    Some(probe)
+}
+
+// A total sample count of zero means the length is unknown.
+fn fill_streaminfo(probe: &mut AudioProbe, info: &flac::StreamInfo) {
+    probe.sample_rate = nonzero(info.sample_rate);
+    probe.channels = nonzero(info.channels);
+    probe.bits_per_sample = nonzero(info.bps);
+    probe.duration = probe
+        .sample_rate
+        .filter(|_| info.total_samples > 0)
+        .map(|rate| samples_to_duration(info.total_samples, rate));
+}
+
+fn probe_ogg(data: &[u8], audio_type: AudioType) -> Option<AudioProbe> {
+    let packet = ogg::first_packet(data)?;
+    let mut probe = AudioProbe::new(audio_type);
+    match audio_type {
+        AudioType::Opus => {
+            probe.sample_rate = Some(OPUS_SAMPLE_RATE);
+            probe.channels = packet.get(9).copied().and_then(nonzero);
+        }
+        AudioType::FLAC => {
+            let info = flac::parse_ogg_mapping_header(packet)?;
+            fill_streaminfo(&mut probe, &info);
+        }
+        AudioType::Vorbis => {
+            let header = vorbis::parse_vorbis_id_header(packet)?;
+            probe.sample_rate = Some(header.sample_rate);
+            probe.channels = Some(header.channels);
+        }
+        _ => {}
+    }
+    Some(probe)
+}
+
+fn probe_caf(data: &[u8]) -> Option<AudioProbe> {
+    let desc = caf::parse_audio_description(data)?;
+    Some(AudioProbe {
+        audio_type: desc.codec()?,
+        sample_rate: nonzero(desc.sample_rate as u32),
+        channels: u8::try_from(desc.channels).ok().and_then(nonzero),
+        bits_per_sample: u8::try_from(desc.bits_per_channel).ok().and_then(nonzero),
+        duration: None,
+    })
+}
+
+/// Probes the first audio track in the body of an MP4 `moov` box.
+pub(crate) fn mp4_audio_track(moov: &[u8]) -> Option<AudioProbe> {
+    timing::top_level_boxes(moov)
+        .filter(|(box_type, _)| box_type == b"trak")
+        .find_map(|(_, trak)| {
+            let mdia = child(trak, b"mdia")?;
+            let stsd = child(child(child(mdia, b"minf")?, b"stbl")?, b"stsd")?;
+            // Version, flags and entry count precede the sample entries
+            let (format, entry) = timing::top_level_boxes(stsd.get(8..)?).next()?;
+            let audio_type = match &format {
+                b"mp4a" => AudioType::AAC,
+                b"fLaC" => AudioType::FLAC,
+                b"Opus" => AudioType::Opus,
+                b"ac-3" => AudioType::AC3,
+                b"ec-3" => AudioType::EAC3,
+                _ => return None,
+            };
+
+            // AudioSampleEntry: channel count, sample size, then a 16.16
+            // fixed-point sample rate after four reserved bytes
+            let u16_at = |pos: usize| {
+                let bytes = entry.get(pos..pos + 2)?;
+                Some(u16::from_be_bytes(bytes.try_into().ok()?))
+            };
+            let sample_rate = entry
+                .get(24..28)
+                .and_then(|bytes| bytes.try_into().ok())
+                .map(|bytes| u32::from_be_bytes(bytes) >> 16)
+                .and_then(nonzero);
+            Some(AudioProbe {
+                audio_type,
+                sample_rate,
+                channels: u16_at(16)
+                    .and_then(|n| u8::try_from(n).ok())
+                    .and_then(nonzero),
+                bits_per_sample: u16_at(18)
+                    .and_then(|n| u8::try_from(n).ok())
+                    .and_then(nonzero),
+                duration: child(mdia, b"mdhd").and_then(mdhd_duration),
+            })
+        })
+}
+
+fn child<'a>(data: &'a [u8], box_type: &[u8; 4]) -> Option<&'a [u8]> {
+    timing::top_level_boxes(data)
+        .find(|(found, _)| found == box_type)
+        .map(|(_, body)| body)
+}
+
+// All ones marks an unknown duration.
+fn mdhd_duration(mdhd: &[u8]) -> Option<Duration> {
+    let (timescale, duration) = match *mdhd.first()? {
+        0 => (
+            u32::from_be_bytes(mdhd.get(12..16)?.try_into().ok()?),
+            u32::from_be_bytes(mdhd.get(16..20)?.try_into().ok()?) as u64,
+        ),
+        1 => (
+            u32::from_be_bytes(mdhd.get(20..24)?.try_into().ok()?),
+            u64::from_be_bytes(mdhd.get(24..32)?.try_into().ok()?),
+        ),
+        _ => return None,
+    };
+    if timescale == 0 || duration == u32::MAX as u64 || duration == u64::MAX {
+        return None;
+    }
+    Some(samples_to_duration(duration, timescale))
+}
+
+fn nonzero<T: Default + PartialEq>(value: T) -> Option<T> {
+    (value != T::default()).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ogg::tests::page;
+
+    fn mp4_box(box_type: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut b = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(box_type);
+        b.extend_from_slice(body);
+        b
+    }
+
+    #[test]
+    fn test_probe_flac_fixture() {
+        let flac = std::fs::read("testdata/s24le.wav.flac").unwrap();
+        assert_eq!(
+            probe(&flac),
+            Some(AudioProbe {
+                audio_type: AudioType::FLAC,
+                sample_rate: Some(44100),
+                channels: Some(2),
+                bits_per_sample: Some(16),
+                duration: None,
+            })
+        );
+        assert_eq!(probe(&[0u8; 64]), None);
+    }
+
+    #[test]
+    fn test_probe_native_flac() {
+        let frames = std::fs::read("testdata/s24le.wav.flac").unwrap();
+        let streaminfo = flac::build_streaminfo(&flac::StreamInfo {
+            min_block_size: 4096,
+            max_block_size: 4096,
+            min_frame_size: 0,
+            max_frame_size: 0,
+            sample_rate: 44100,
+            channels: 2,
+            bps: 16,
+            total_samples: 441_000,
+            md5: [0; 16],
+        })
+        .unwrap();
+        let mut native = b"fLaC\x00\x00\x00\x22".to_vec();
+        native.extend_from_slice(&streaminfo);
+        // A PADDING block longer than the data that follows
+        native.extend_from_slice(b"\x81\x01\x00\x00");
+        native.extend_from_slice(&frames);
+
+        let expected = AudioProbe {
+            audio_type: AudioType::FLAC,
+            sample_rate: Some(44100),
+            channels: Some(2),
+            bits_per_sample: Some(16),
+            duration: Some(Duration::from_secs(10)),
+        };
+        assert_eq!(probe(&native[..1024]), Some(expected));
+
+        // With the metadata complete
+        native.truncate(8 + streaminfo.len());
+        native[4] = 0x80;
+        native.extend_from_slice(&frames);
+        assert_eq!(probe(&native), Some(expected));
+    }
+
+    #[test]
+    fn test_probe_elementary_streams() {
+        let mut adts =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 48000, 4, false).unwrap();
+        adts.extend_from_slice(&[0; 4]);
+        let probed = probe(&adts).unwrap();
+        assert_eq!(probed.audio_type, AudioType::AAC);
+        assert_eq!(probed.sample_rate, Some(48000));
+        assert_eq!(probed.channels, Some(2));
+        assert_eq!(probed.bits_per_sample, None);
+
+        let ac3 = [0x0B, 0x77, 0x00, 0x00, 0x1C, 0x40, 0xE1, 0x40];
+        let probed = probe(&ac3).unwrap();
+        assert_eq!(probed.audio_type, AudioType::AC3);
+        assert_eq!(probed.sample_rate, Some(48000));
+        assert_eq!(probed.channels, Some(6));
+
+        let probed = probe(b"#!AMR-WB\n").unwrap();
+        assert_eq!(probed.sample_rate, Some(16000));
+        assert_eq!(probed.channels, Some(1));
+    }
+
+    #[test]
+    fn test_probe_aiff_and_caf() {
+        let rate_44100 = [0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0];
+        assert_eq!(
+            probe(&aiff::tests::aiff_file(None, rate_44100)),
+            Some(AudioProbe {
+                audio_type: AudioType::AIFF,
+                sample_rate: Some(44100),
+                channels: Some(2),
+                bits_per_sample: Some(16),
+                duration: Some(Duration::from_secs(1)),
+            })
+        );
+
+        let probed = probe(&caf::tests::caf_file(b"aac ", 16)).unwrap();
+        assert_eq!(probed.audio_type, AudioType::AAC);
+        assert_eq!(probed.sample_rate, Some(44100));
+        assert_eq!(probed.channels, Some(2));
+        // Compressed formats have no bit depth
+        assert_eq!(probed.bits_per_sample, None);
+        assert_eq!(probe(&caf::tests::caf_file(b"lpcm", 16)), None);
+    }
+
+    #[test]
+    fn test_probe_ogg() {
+        let opus = page(
+            0x02,
+            &[b"OpusHead\x01\x02\x38\x01\x44\xAC\x00\x00\x00\x00\x00"],
+        );
+        let probed = probe(&opus).unwrap();
+        assert_eq!(probed.audio_type, AudioType::Opus);
+        assert_eq!(probed.sample_rate, Some(48000));
+        assert_eq!(probed.channels, Some(2));
+
+        let mut mapping = b"\x7FFLAC\x01\x00\x00\x01fLaC\x00\x00\x00\x22".to_vec();
+        mapping.extend(
+            flac::create_streaminfo(&flac::FLACFrameInfo {
+                block_size: 4096,
+                sample_rate: 48000,
+                channels: 2,
+                bps: 24,
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let probed = probe(&page(0x02, &[&mapping])).unwrap();
+        assert_eq!(probed.audio_type, AudioType::FLAC);
+        assert_eq!(probed.bits_per_sample, Some(24));
+
+        let vorbis = page(0x02, &[&vorbis::tests::id_header(1, 22050)]);
+        let probed = probe(&vorbis).unwrap();
+        assert_eq!(probed.sample_rate, Some(22050));
+        assert_eq!(probed.channels, Some(1));
+    }
+
+    #[test]
+    fn test_probe_mp4() {
+        let mut mdhd = vec![0; 12];
+        mdhd.extend(44100u32.to_be_bytes());
+        mdhd.extend((44100u32 * 90).to_be_bytes());
+        mdhd.extend([0; 4]);
+
+        let mut mp4a = vec![0; 16];
+        mp4a.extend([0, 2, 0, 16, 0, 0, 0, 0]);
+        mp4a.extend((44100u32 << 16).to_be_bytes());
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(mp4_box(b"mp4a", &mp4a));
+        let stbl = mp4_box(b"stbl", &mp4_box(b"stsd", &stsd));
+
+        let mut mdia = mp4_box(b"mdhd", &mdhd);
+        mdia.extend(mp4_box(b"minf", &stbl));
+        let mut mp4 = mp4_box(b"ftyp", b"M4A \x00\x00\x00\x00");
+        mp4.extend(mp4_box(
+            b"moov",
+            &mp4_box(b"trak", &mp4_box(b"mdia", &mdia)),
+        ));
+
+        assert_eq!(
+            probe(&mp4),
+            Some(AudioProbe {
+                audio_type: AudioType::AAC,
+                sample_rate: Some(44100),
+                channels: Some(2),
+                bits_per_sample: Some(16),
+                duration: Some(Duration::from_secs(90)),
+            })
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::AccessUnit;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+const PTS_WRAP: i128 = 1 << 33;
+
+/// Restores DTS order to access units that arrive slightly out of order,
+/// holding each one until either `max_depth` units are buffered or the
+/// newest DTS is `max_latency_90k` ticks past it.
+///
+/// DTS values are unwrapped across the 33-bit boundary relative to the
+/// newest unit, so a unit just after a wrap sorts after those just before
+/// it. Units with equal DTS come out in the order they were pushed. A unit
+/// arriving after a later one has already been popped is still returned,
+/// just out of order.
+#[derive(Debug)]
+pub struct ReorderBuffer {
+    max_depth: usize,
+    max_latency_90k: u64,
+    heap: BinaryHeap<Reverse<Entry>>,
+    newest_dts: Option<i128>,
+    next_seq: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    dts: i128,
+    seq: u64,
+    au: AccessUnit,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.dts, self.seq).cmp(&(other.dts, other.seq))
+    }
+}
+
+impl ReorderBuffer {
+    /// A `max_depth` of zero is treated as one, which passes units through.
+    pub fn new(max_depth: usize, max_latency_90k: u64) -> Self {
+        Self {
+            max_depth: max_depth.max(1),
+            max_latency_90k,
+            heap: BinaryHeap::new(),
+            newest_dts: None,
+            next_seq: 0,
+        }
+    }
+
+    pub fn push(&mut self, au: AccessUnit) {
+        let dts = self.unwrap_dts(au.dts);
+        self.newest_dts = Some(self.newest_dts.map_or(dts, |newest| newest.max(dts)));
+        self.heap.push(Reverse(Entry {
+            dts,
+            seq: self.next_seq,
+            au,
+        }));
+        self.next_seq += 1;
+    }
+
+    /// The unit with the lowest DTS, once the depth or latency bound says
+    /// nothing earlier can still arrive.
+    pub fn pop(&mut self) -> Option<AccessUnit> {
+        let Reverse(oldest) = self.heap.peek()?;
+        let waited = self.newest_dts? - oldest.dts;
+        if self.heap.len() >= self.max_depth || waited >= self.max_latency_90k as i128 {
+            self.heap.pop().map(|Reverse(entry)| entry.au)
+        } else {
+            None
+        }
+    }
+
+    /// Every buffered unit, in DTS order.
+    pub fn flush(&mut self) -> Vec<AccessUnit> {
+        let mut units = Vec::with_capacity(self.heap.len());
+        while let Some(Reverse(entry)) = self.heap.pop() {
+            units.push(entry.au);
+        }
+        units
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    // Picks the unrolled value nearest the newest DTS seen.
+    fn unwrap_dts(&self, dts: u64) -> i128 {
+        let dts = dts as i128 % PTS_WRAP;
+        match self.newest_dts {
+            Some(reference) => {
+                let base = reference - reference.rem_euclid(PTS_WRAP);
+                [base - PTS_WRAP, base, base + PTS_WRAP]
+                    .into_iter()
+                    .map(|base| base + dts)
+                    .min_by_key(|candidate| (candidate - reference).abs())
+                    .unwrap_or(dts)
+            }
+            None => dts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::corpus::Rng;
+    use crate::StreamType;
+    use bytes::Bytes;
+
+    fn au(dts: u64, id: u64) -> AccessUnit {
+        AccessUnit {
+            key: false,
+            pts: dts,
+            dts,
+            data: Bytes::new(),
+            stream_type: StreamType::H264.into(),
+            id,
+        }
+    }
+
+    fn drain(buffer: &mut ReorderBuffer, out: &mut Vec<AccessUnit>) {
+        while let Some(au) = buffer.pop() {
+            out.push(au);
+        }
+    }
+
+    #[test]
+    fn test_depth_and_latency_bounds() {
+        let mut buffer = ReorderBuffer::new(3, 9000);
+        buffer.push(au(6006, 0));
+        buffer.push(au(3003, 1));
+        assert!(buffer.pop().is_none());
+
+        buffer.push(au(9009, 2));
+        assert_eq!(buffer.pop().unwrap().dts, 3003);
+        assert!(buffer.pop().is_none());
+
+        // 12012 - 6006 is under the latency bound, 18018 - 6006 is not
+        let mut buffer = ReorderBuffer::new(16, 9000);
+        buffer.push(au(6006, 0));
+        buffer.push(au(12012, 1));
+        assert!(buffer.pop().is_none());
+        buffer.push(au(18018, 2));
+        assert_eq!(buffer.pop().unwrap().dts, 6006);
+        assert!(buffer.pop().is_none());
+
+        let rest: Vec<u64> = buffer.flush().iter().map(|au| au.dts).collect();
+        assert_eq!(rest, [12012, 18018]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_dts_is_stable() {
+        let mut buffer = ReorderBuffer::new(8, u64::MAX);
+        for id in 0..5 {
+            buffer.push(au(if id == 2 { 1000 } else { 3000 }, id));
+        }
+        let ids: Vec<u64> = buffer.flush().iter().map(|au| au.id).collect();
+        assert_eq!(ids, [2, 0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn test_wraparound() {
+        let wrap = 1u64 << 33;
+        let mut buffer = ReorderBuffer::new(8, u64::MAX);
+        buffer.push(au(wrap - 3003, 0));
+        buffer.push(au(1000, 2));
+        buffer.push(au(wrap - 1000, 1));
+        let ids: Vec<u64> = buffer.flush().iter().map(|au| au.id).collect();
+        assert_eq!(ids, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_restores_shuffled_sequences() {
+        for seed in 0..32 {
+            let mut rng = Rng(seed);
+            let depth = 2 + rng.below(6);
+            let start = (1u64 << 33) - 3003 * rng.below(40) as u64;
+            let units: Vec<AccessUnit> = (0..200)
+                .map(|i| au((start + 3003 * i) % (1 << 33), i))
+                .collect();
+
+            // Shuffle within windows no larger than the buffer's depth
+            let mut shuffled = units.clone();
+            for window in shuffled.chunks_mut(depth) {
+                for i in (1..window.len()).rev() {
+                    window.swap(i, rng.below(i + 1));
+                }
+            }
+
+            let mut buffer = ReorderBuffer::new(depth, u64::MAX);
+            let mut out = Vec::new();
+            for au in shuffled {
+                buffer.push(au);
+                drain(&mut buffer, &mut out);
+            }
+            out.extend(buffer.flush());
+
+            let ids: Vec<u64> = out.iter().map(|au| au.id).collect();
+            let expected: Vec<u64> = units.iter().map(|au| au.id).collect();
+            assert_eq!(ids, expected, "seed {}", seed);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{aac, flac, AudioType};
+use bytes::{Bytes, BytesMut};
+use std::ops::Range;
+
+#[derive(Debug, Clone)]
+pub struct SanitizeResult {
+    /// The frames that passed validation, concatenated.
+    pub clean: Bytes,
+    /// Byte ranges of the input that were dropped, in order.
+    pub removed: Vec<Range<usize>>,
+    pub frames_kept: usize,
+}
+
+/// Re-emits an elementary stream keeping only frames that pass header (and,
+/// for FLAC, CRC-16) validation with parameters consistent with the first
+/// good frame. Everything else is dropped and reported in `removed`.
+///
+/// Supports ADTS and raw FLAC frame streams; other types are returned
+/// unchanged with `frames_kept` set to zero.
+pub fn sanitize(data: &[u8], audio_type: AudioType) -> SanitizeResult {
+    match audio_type {
+        AudioType::AAC => keep_frames(data, adts_frames(data)),
+        AudioType::FLAC => keep_frames(data, flac_frames(data)),
+        _ => SanitizeResult {
+            clean: Bytes::copy_from_slice(data),
+            removed: Vec::new(),
+            frames_kept: 0,
+        },
+    }
+}
+
+fn keep_frames(data: &[u8], frames: impl Iterator<Item = Range<usize>>) -> SanitizeResult {
+    let mut clean = BytesMut::with_capacity(data.len());
+    let mut removed = Vec::new();
+    let mut frames_kept = 0;
+    let mut pos = 0;
+
+    for frame in frames {
+        if frame.start > pos {
+            removed.push(pos..frame.start);
+        }
+        clean.extend_from_slice(&data[frame.clone()]);
+        frames_kept += 1;
+        pos = frame.end;
+    }
+    if pos < data.len() {
+        removed.push(pos..data.len());
+    }
+
+    SanitizeResult {
+        clean: clean.freeze(),
+        removed,
+        frames_kept,
+    }
+}
+
+// Profile, sampling frequency index and channel configuration.
+type AdtsParams = (u8, u8, u8);
+
+fn adts_frames(data: &[u8]) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut locked = None;
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        while pos < data.len() {
+            let start = pos;
+            match next_adts_frame(&data[pos..], &mut locked) {
+                Some(len) => {
+                    pos += len;
+                    return Some(start..pos);
+                }
+                None => pos += 1,
+            }
+        }
+        None
+    })
+}
+
+fn next_adts_frame(frame: &[u8], locked: &mut Option<AdtsParams>) -> Option<usize> {
+    let header = aac::parse_adts_header(frame).ok()?;
+    if header.frame_length > frame.len() {
+        return None;
+    }
+
+    let params = (
+        header.profile,
+        header.sampling_frequency_index,
+        header.channel_configuration,
+    );
+    match locked {
+        Some(locked) if *locked != params => return None,
+        Some(_) => {}
+        None => {
+            // The first frame is only trusted once the next one lines up
+            let next = &frame[header.frame_length..];
+            if !next.is_empty() && !aac::is_aac_single_frame(next) {
+                return None;
+            }
+            *locked = Some(params);
+        }
+    }
+
+    Some(header.frame_length)
+}
+
+// Sample rate, channels and bits per sample.
+type FlacParams = (u32, u8, u8);
+
+fn flac_frames(data: &[u8]) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut locked: Option<FlacParams> = None;
+    let mut frames = flac::FlacFrameIter::new(data);
+    std::iter::from_fn(move || loop {
+        let Ok(frame) = frames.next()? else {
+            continue;
+        };
+        let params = (frame.info.sample_rate, frame.info.channels, frame.info.bps);
+        if *locked.get_or_insert(params) != params {
+            continue;
+        }
+        let start = data.len() - frames.remaining() - frame.data.len();
+        return Some(start..start + unpadded_len(frame.data));
+    })
+}
+
+// Trailing zeros leave a zero-initialised CRC untouched, so padding after
+// a frame passes as part of it. A zero run longer than the footer is cut
+// back to where the CRC-16 checks.
+fn unpadded_len(frame: &[u8]) -> usize {
+    let zeros = frame.iter().rev().take_while(|&&b| b == 0).count();
+    if zeros <= 2 {
+        return frame.len();
+    }
+    (frame.len() - zeros..=frame.len() - zeros + 2)
+        .find(|&len| flac::verify_frame_crc(&frame[..len]))
+        .unwrap_or(frame.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn flac_frame_offsets(data: &[u8]) -> Vec<usize> {
+        (0..data.len())
+            .filter(|&i| data[i] == 0xFF && flac::decode_frame_header_checked(&data[i..]).is_ok())
+            .collect()
+    }
+
+    #[test]
+    fn test_sanitize_adts() {
+        let mut frame =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 64, false).unwrap();
+        frame.extend_from_slice(&[0x11; 64]);
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&frame);
+        data.extend_from_slice(&frame);
+        data.extend_from_slice(&[0u8; 10]);
+        // A frame with a different sample rate is inconsistent with the stream
+        let mut other =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 48000, 64, false).unwrap();
+        other.extend_from_slice(&[0x11; 64]);
+        data.extend_from_slice(&other);
+        data.extend_from_slice(&frame);
+        // Partial trailing frame
+        data.extend_from_slice(&frame[..20]);
+
+        let result = sanitize(&data, AudioType::AAC);
+        assert_eq!(result.frames_kept, 3);
+        assert_eq!(result.removed, vec![142..223, 294..314]);
+        assert_eq!(result.clean.len(), 3 * 71);
+        assert_eq!(&result.clean[..71], &frame[..]);
+    }
+
+    #[test]
+    fn test_sanitize_flac_clean() {
+        let data = fs::read("testdata/s24le.wav.flac").unwrap();
+        let result = sanitize(&data, AudioType::FLAC);
+
+        assert!(result.removed.is_empty());
+        assert_eq!(result.frames_kept, 86);
+        assert_eq!(&result.clean[..], &data[..]);
+    }
+
+    #[test]
+    fn test_sanitize_flac_corrupted() {
+        let original = fs::read("testdata/s24le.wav.flac").unwrap();
+        let offsets = flac_frame_offsets(&original);
+
+        // Zeros between frames 10 and 11, and a flipped byte inside frame 20
+        let mut data = original[..offsets[11]].to_vec();
+        data.extend_from_slice(&[0u8; 300]);
+        data.extend_from_slice(&original[offsets[11]..]);
+        let frame_20 = offsets[20] + 300..offsets[21] + 300;
+        data[frame_20.start + 100] ^= 0x5A;
+
+        let result = sanitize(&data, AudioType::FLAC);
+        assert_eq!(result.frames_kept, 85);
+        assert_eq!(
+            result.removed,
+            vec![offsets[11]..offsets[11] + 300, frame_20]
+        );
+
+        let mut expected = original[..offsets[20]].to_vec();
+        expected.extend_from_slice(&original[offsets[21]..]);
+        assert_eq!(&result.clean[..], &expected[..]);
+    }
+}
@@ -0,0 +1,481 @@
+use crate::{aac, detect_audio, h264, opus, AccessUnit, AudioType, StreamType};
+use std::fmt;
+
+/// What a payload appeared to contain when it failed validation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DetectedPayload {
+    Audio(AudioType),
+    H264,
+    Mp3,
+    Empty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PayloadMismatch {
+    pub stream_type: u8,
+    pub detected: DetectedPayload,
+}
+
+impl fmt::Display for PayloadMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expected = match StreamType::from(self.stream_type) {
+            StreamType::AAC | StreamType::Mpeg4AAC => "AAC (ADTS)",
+            StreamType::H264 => "H.264 (Annex B)",
+            StreamType::MP3 => "MPEG audio",
+            StreamType::Opus => "Opus",
+            _ => "unknown",
+        };
+        let detected = match self.detected {
+            DetectedPayload::Audio(AudioType::Unknown) => "unrecognised data".to_string(),
+            DetectedPayload::Audio(audio_type) => format!("{:?}", audio_type),
+            DetectedPayload::H264 => "H.264".to_string(),
+            DetectedPayload::Mp3 => "MPEG audio".to_string(),
+            DetectedPayload::Empty => "an empty payload".to_string(),
+        };
+        write!(
+            f,
+            "stream type 0x{:02x} expects {} but payload looks like {}",
+            self.stream_type, expected, detected
+        )
+    }
+}
+
+impl std::error::Error for PayloadMismatch {}
+
+/// Checks that the payload of `au` matches what its `stream_type` claims.
+/// Private data and stream types without a validator always pass.
+pub fn validate_payload(au: &AccessUnit) -> Result<(), PayloadMismatch> {
+    let data = &au.data[..];
+
+    let valid = match au.stream_type() {
+        StreamType::AAC | StreamType::Mpeg4AAC => aac::is_aac(data),
+        StreamType::H264 => h264::is_nalu(data),
+        StreamType::MP3 => is_mpeg_audio_header(data),
+        StreamType::Opus => opus::is_opus_packet(data),
+        _ => return Ok(()),
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(PayloadMismatch {
+            stream_type: au.stream_type,
+            detected: detect_payload(data),
+        })
+    }
+}
+
+fn detect_payload(data: &[u8]) -> DetectedPayload {
+    if data.is_empty() {
+        return DetectedPayload::Empty;
+    }
+
+    match detect_audio(data) {
+        AudioType::Unknown => {
+            if h264::is_nalu(data) {
+                DetectedPayload::H264
+            } else if is_mpeg_audio_header(data) {
+                DetectedPayload::Mp3
+            } else {
+                DetectedPayload::Audio(AudioType::Unknown)
+            }
+        }
+        audio_type => DetectedPayload::Audio(audio_type),
+    }
+}
+
+fn is_mpeg_audio_header(data: &[u8]) -> bool {
+    if data.len() < 4 {
+        return false;
+    }
+
+    // 11-bit frame sync
+    if data[0] != 0xFF || (data[1] & 0xE0) != 0xE0 {
+        return false;
+    }
+
+    let version = (data[1] >> 3) & 0x03;
+    let layer = (data[1] >> 1) & 0x03;
+    let bitrate_index = data[2] >> 4;
+    let sample_rate_index = (data[2] >> 2) & 0x03;
+
+    // Reserved values
+    version != 1 && layer != 0 && bitrate_index != 0x0F && sample_rate_index != 3
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DiffOptions {
+    /// Largest pts or dts difference, in ticks, still treated as equal.
+    pub tolerance: u64,
+    /// Compare H.264 payloads with their SEI NAL units removed.
+    pub ignore_sei: bool,
+    pub ignore_id: bool,
+}
+
+/// One disagreement between two streams. `a` and `b` are unit indices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnitDifference {
+    /// A unit of the first stream with no counterpart in the second.
+    Missing {
+        a: usize,
+        dts: u64,
+    },
+    /// A unit of the second stream with no counterpart in the first.
+    Extra {
+        b: usize,
+        dts: u64,
+    },
+    /// Payloads differ from `offset` on, counted in the compared bytes
+    /// (the non-SEI NAL units when SEI is ignored).
+    Payload {
+        a: usize,
+        b: usize,
+        offset: usize,
+    },
+    /// `b - a` for each timestamp, at least one beyond the tolerance.
+    Timestamp {
+        a: usize,
+        b: usize,
+        pts_delta: i64,
+        dts_delta: i64,
+    },
+    Key {
+        a: usize,
+        b: usize,
+        expected: bool,
+    },
+    Id {
+        a: usize,
+        b: usize,
+    },
+}
+
+impl fmt::Display for UnitDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            UnitDifference::Missing { a, dts } => write!(f, "a[{}] dts {} missing from b", a, dts),
+            UnitDifference::Extra { b, dts } => write!(f, "b[{}] dts {} not in a", b, dts),
+            UnitDifference::Payload { a, b, offset } => {
+                write!(f, "a[{}] b[{}] payload differs at byte {}", a, b, offset)
+            }
+            UnitDifference::Timestamp {
+                a,
+                b,
+                pts_delta,
+                dts_delta,
+            } => write!(
+                f,
+                "a[{}] b[{}] pts off by {}, dts off by {}",
+                a, b, pts_delta, dts_delta
+            ),
+            UnitDifference::Key { a, b, expected } => {
+                write!(f, "a[{}] b[{}] key flag {} in a only", a, b, expected)
+            }
+            UnitDifference::Id { a, b } => write!(f, "a[{}] b[{}] id differs", a, b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StreamDiff {
+    /// Units aligned between the two streams, equal or not.
+    pub matched: usize,
+    pub differences: Vec<UnitDifference>,
+}
+
+impl StreamDiff {
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+impl fmt::Display for StreamDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} units matched, {} differences",
+            self.matched,
+            self.differences.len()
+        )?;
+        for difference in &self.differences {
+            write!(f, "\n  {}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares two streams unit by unit. Both must be in decode order; units
+/// are paired when their dts agree within the tolerance.
+pub fn diff(a: &[AccessUnit], b: &[AccessUnit], opts: DiffOptions) -> StreamDiff {
+    let mut result = StreamDiff::default();
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() && j < b.len() {
+        let (x, y) = (&a[i], &b[j]);
+        if x.dts.abs_diff(y.dts) > opts.tolerance {
+            if x.dts < y.dts {
+                result
+                    .differences
+                    .push(UnitDifference::Missing { a: i, dts: x.dts });
+                i += 1;
+            } else {
+                result
+                    .differences
+                    .push(UnitDifference::Extra { b: j, dts: y.dts });
+                j += 1;
+            }
+            continue;
+        }
+
+        result.matched += 1;
+        if x.pts.abs_diff(y.pts) > opts.tolerance {
+            result.differences.push(UnitDifference::Timestamp {
+                a: i,
+                b: j,
+                pts_delta: y.pts as i64 - x.pts as i64,
+                dts_delta: y.dts as i64 - x.dts as i64,
+            });
+        }
+        if x.key != y.key {
+            result.differences.push(UnitDifference::Key {
+                a: i,
+                b: j,
+                expected: x.key,
+            });
+        }
+        if !opts.ignore_id && x.id != y.id {
+            result.differences.push(UnitDifference::Id { a: i, b: j });
+        }
+        if let Some(offset) = payload_difference(x, y, opts.ignore_sei) {
+            result
+                .differences
+                .push(UnitDifference::Payload { a: i, b: j, offset });
+        }
+        i += 1;
+        j += 1;
+    }
+
+    for (i, x) in a.iter().enumerate().skip(i) {
+        result
+            .differences
+            .push(UnitDifference::Missing { a: i, dts: x.dts });
+    }
+    for (j, y) in b.iter().enumerate().skip(j) {
+        result
+            .differences
+            .push(UnitDifference::Extra { b: j, dts: y.dts });
+    }
+
+    result
+}
+
+fn payload_difference(a: &AccessUnit, b: &AccessUnit, ignore_sei: bool) -> Option<usize> {
+    let without_sei = |au: &AccessUnit| -> Vec<u8> {
+        h264::split_nalus(&au.data)
+            .into_iter()
+            .filter(|nalu| nalu[0] & 0x1F != h264::SEI_NAL_TYPE)
+            .flatten()
+            .copied()
+            .collect()
+    };
+
+    if ignore_sei && a.stream_type() == StreamType::H264 && b.stream_type() == StreamType::H264 {
+        first_difference(&without_sei(a), &without_sei(b))
+    } else {
+        first_difference(&a.data, &b.data)
+    }
+}
+
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    match a.iter().zip(b).position(|(x, y)| x != y) {
+        Some(offset) => Some(offset),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn au(stream_type: StreamType, data: Vec<u8>) -> AccessUnit {
+        AccessUnit {
+            key: true,
+            pts: 0,
+            dts: 0,
+            data: Bytes::from(data),
+            stream_type: stream_type.into(),
+            id: 0,
+        }
+    }
+
+    #[test]
+    fn test_validate_payload_accepts_matching_payloads() {
+        let mut adts =
+            aac::create_adts_header_for(aac::AacObjectType::Lc, 2, 44100, 16, false).unwrap();
+        adts.extend_from_slice(&[0u8; 16]);
+        assert!(validate_payload(&au(StreamType::AAC, adts)).is_ok());
+
+        let nalu = vec![0x00, 0x00, 0x00, 0x01, 0x09, 0xF0];
+        assert!(validate_payload(&au(StreamType::H264, nalu)).is_ok());
+
+        let mp3 = vec![0xFF, 0xFB, 0x90, 0x64, 0x00];
+        assert!(validate_payload(&au(StreamType::MP3, mp3)).is_ok());
+
+        // CELT 20 ms, code 3 with 6 empty frames = 120 ms
+        let opus = vec![0xFB, 0x06];
+        assert!(validate_payload(&au(StreamType::Opus, opus)).is_ok());
+
+        let private = vec![0xDE, 0xAD];
+        assert!(validate_payload(&au(StreamType::PrivateData, private)).is_ok());
+    }
+
+    #[test]
+    fn test_validate_payload_reports_detected_type() {
+        let nalu = vec![0x00, 0x00, 0x00, 0x01, 0x09, 0xF0];
+        let err = validate_payload(&au(StreamType::AAC, nalu)).unwrap_err();
+        assert_eq!(err.stream_type, u8::from(StreamType::AAC));
+        assert_eq!(err.detected, DetectedPayload::H264);
+
+        let flac = vec![0xFF, 0xF8, 0xC9, 0xA8, 0x00, 0x8D, 0x4E];
+        let err = validate_payload(&au(StreamType::H264, flac)).unwrap_err();
+        assert_eq!(err.detected, DetectedPayload::Audio(AudioType::FLAC));
+        assert_eq!(
+            err.to_string(),
+            "stream type 0x1b expects H.264 (Annex B) but payload looks like FLAC"
+        );
+
+        let err = validate_payload(&au(StreamType::Opus, vec![])).unwrap_err();
+        assert_eq!(err.detected, DetectedPayload::Empty);
+
+        // 7 x 20 ms frames exceeds the 120 ms packet limit
+        let opus = vec![0xFB, 0x07, 0x00];
+        assert!(validate_payload(&au(StreamType::Opus, opus)).is_err());
+
+        // A single frame longer than the 1275-byte limit
+        let mut opus = vec![0xF8];
+        opus.resize(1301, 0);
+        assert!(validate_payload(&au(StreamType::Opus, opus)).is_err());
+    }
+
+    fn timed(dts: u64, key: bool, data: Vec<u8>) -> AccessUnit {
+        AccessUnit {
+            key,
+            pts: dts + 3_000,
+            dts,
+            data: Bytes::from(data),
+            stream_type: StreamType::H264.into(),
+            id: dts,
+        }
+    }
+
+    #[test]
+    fn test_diff_identical_streams() {
+        let a: Vec<_> = (0..10)
+            .map(|i| timed(i * 3_000, i == 0, vec![0, 0, 1, 0x41, i as u8]))
+            .collect();
+        let result = diff(&a, &a.clone(), DiffOptions::default());
+        assert!(result.is_empty());
+        assert_eq!(result.matched, 10);
+        assert_eq!(result.to_string(), "10 units matched, 0 differences");
+    }
+
+    #[test]
+    fn test_diff_reports_each_kind() {
+        let a: Vec<_> = (0..5)
+            .map(|i| timed(i * 3_000, i == 0, vec![0, 0, 1, 0x41, i as u8]))
+            .collect();
+        let mut b = a.clone();
+        b[1].pts += 1;
+        b[1].dts += 1;
+        b[1].id = 99;
+        b[2].data = Bytes::from(vec![0, 0, 1, 0x41, 0xFF]);
+        b[3].key = true;
+        b.remove(4);
+        b.push(timed(20_000, false, vec![]));
+
+        let options = DiffOptions {
+            tolerance: 1,
+            ..Default::default()
+        };
+        let result = diff(&a, &b, options);
+        assert_eq!(result.matched, 4);
+        assert_eq!(
+            result.differences,
+            vec![
+                UnitDifference::Id { a: 1, b: 1 },
+                UnitDifference::Payload {
+                    a: 2,
+                    b: 2,
+                    offset: 4
+                },
+                UnitDifference::Key {
+                    a: 3,
+                    b: 3,
+                    expected: false
+                },
+                UnitDifference::Missing { a: 4, dts: 12_000 },
+                UnitDifference::Extra { b: 4, dts: 20_000 },
+            ]
+        );
+
+        let strict = diff(&a, &b, DiffOptions::default());
+        assert_eq!(strict.matched, 3);
+        assert!(strict
+            .differences
+            .contains(&UnitDifference::Missing { a: 1, dts: 3_000 }));
+        assert!(strict
+            .differences
+            .contains(&UnitDifference::Extra { b: 1, dts: 3_001 }));
+
+        b[1].pts += 5;
+        let result = diff(
+            &a,
+            &b,
+            DiffOptions {
+                ignore_id: true,
+                ..options
+            },
+        );
+        assert_eq!(
+            result.differences[0],
+            UnitDifference::Timestamp {
+                a: 1,
+                b: 1,
+                pts_delta: 6,
+                dts_delta: 1
+            }
+        );
+        assert!(result
+            .to_string()
+            .starts_with("4 units matched, 5 differences\n"));
+    }
+
+    #[test]
+    fn test_diff_ignores_sei() {
+        let slice = [0x00, 0x00, 0x01, 0x65, 0x88, 0x84];
+        let mut with_sei = vec![0x00, 0x00, 0x01, 0x06, 0x05, 0x01, 0xAA, 0x80];
+        with_sei.extend_from_slice(&slice);
+        let a = vec![timed(0, true, with_sei)];
+        let b = vec![timed(0, true, slice.to_vec())];
+
+        assert_eq!(
+            diff(&a, &b, DiffOptions::default()).differences,
+            vec![UnitDifference::Payload {
+                a: 0,
+                b: 0,
+                offset: 3
+            }]
+        );
+        let options = DiffOptions {
+            ignore_sei: true,
+            ..Default::default()
+        };
+        assert!(diff(&a, &b, options).is_empty());
+    }
+}
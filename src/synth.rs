@@ -0,0 +1,218 @@
+use crate::{aac, AccessUnit, StreamType};
+use bytes::Bytes;
+use std::fmt;
+use std::time::Duration;
+
+// Raw AAC-LC frames that decode to 1024 samples of digital silence.
+const AAC_SILENT_MONO: [u8; 6] = [0x00, 0xC8, 0x00, 0x80, 0x23, 0x80];
+const AAC_SILENT_STEREO: [u8; 9] = [0x21, 0x00, 0x49, 0x90, 0x02, 0x19, 0x00, 0x23, 0x80];
+const AAC_FRAME_SAMPLES: u64 = 1024;
+
+// 20 ms CELT silence; the TOC stereo bit is set per track.
+const OPUS_SILENT: [u8; 3] = [0xF8, 0xFF, 0xFE];
+
+const PCM_FRAME_SAMPLES: u64 = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SilentCodec {
+    /// ADTS-framed AAC-LC, mono or stereo.
+    AAC,
+    /// 20 ms Opus packets, mono or stereo.
+    Opus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SynthError {
+    UnsupportedChannels(u8),
+    UnsupportedSampleRate(u32),
+}
+
+impl fmt::Display for SynthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SynthError::UnsupportedChannels(channels) => {
+                write!(f, "Unsupported channel count: {}", channels)
+            }
+            SynthError::UnsupportedSampleRate(rate) => {
+                write!(f, "Unsupported sample rate: {}", rate)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SynthError {}
+
+/// Builds a silent track covering at least `duration`. Timestamps are in
+/// `timescale` ticks from zero. Compressed frames have a fixed length, so
+/// the last one may run past `duration` by less than one frame.
+pub fn silent_track(
+    codec: SilentCodec,
+    duration: Duration,
+    sample_rate: u32,
+    channels: u8,
+    timescale: u32,
+) -> Result<Vec<AccessUnit>, SynthError> {
+    let (frame, frame_samples, stream_type) = match codec {
+        SilentCodec::AAC => {
+            if aac::sample_rate_index(sample_rate) == 0x0F {
+                return Err(SynthError::UnsupportedSampleRate(sample_rate));
+            }
+            let raw: &[u8] = match channels {
+                1 => &AAC_SILENT_MONO,
+                2 => &AAC_SILENT_STEREO,
+                _ => return Err(SynthError::UnsupportedChannels(channels)),
+            };
+            let mut frame = aac::create_adts_header_for(
+                aac::AacObjectType::Lc,
+                channels,
+                sample_rate,
+                raw.len(),
+                false,
+            )
+            .expect("LC has an ADTS profile");
+            frame.extend_from_slice(raw);
+            (frame, AAC_FRAME_SAMPLES, StreamType::AAC)
+        }
+        SilentCodec::Opus => {
+            if ![8000, 12000, 16000, 24000, 48000].contains(&sample_rate) {
+                return Err(SynthError::UnsupportedSampleRate(sample_rate));
+            }
+            let mut frame = OPUS_SILENT.to_vec();
+            match channels {
+                1 => {}
+                2 => frame[0] |= 0x04,
+                _ => return Err(SynthError::UnsupportedChannels(channels)),
+            }
+            (frame, sample_rate as u64 / 50, StreamType::Opus)
+        }
+    };
+
+    let frame = Bytes::from(frame);
+    let total = duration_to_samples(duration, sample_rate);
+    let count = total.div_ceil(frame_samples);
+
+    Ok((0..count)
+        .map(|i| {
+            let pts = samples_to_ticks(i * frame_samples, sample_rate, timescale);
+            AccessUnit {
+                key: true,
+                pts,
+                dts: pts,
+                data: frame.clone(),
+                stream_type: stream_type.into(),
+                id: i,
+            }
+        })
+        .collect())
+}
+
+/// Builds a sine tone of exactly `duration` as 16-bit little-endian
+/// interleaved PCM, 1024 samples per unit with a shorter last unit.
+pub fn tone_track(
+    freq_hz: f64,
+    duration: Duration,
+    sample_rate: u32,
+    channels: u8,
+    timescale: u32,
+) -> Result<Vec<AccessUnit>, SynthError> {
+    if sample_rate == 0 {
+        return Err(SynthError::UnsupportedSampleRate(sample_rate));
+    }
+    if channels == 0 {
+        return Err(SynthError::UnsupportedChannels(channels));
+    }
+
+    let total = duration_to_samples(duration, sample_rate);
+    let step = 2.0 * std::f64::consts::PI * freq_hz / sample_rate as f64;
+    let mut units = Vec::new();
+    let mut start = 0;
+
+    while start < total {
+        let end = (start + PCM_FRAME_SAMPLES).min(total);
+        let mut data = Vec::with_capacity((end - start) as usize * channels as usize * 2);
+        for n in start..end {
+            // Half scale leaves headroom for resampling
+            let sample = ((n as f64 * step).sin() * i16::MAX as f64 / 2.0) as i16;
+            for _ in 0..channels {
+                data.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+
+        let pts = samples_to_ticks(start, sample_rate, timescale);
+        units.push(AccessUnit {
+            key: true,
+            pts,
+            dts: pts,
+            data: Bytes::from(data),
+            stream_type: StreamType::PrivateData.into(),
+            id: units.len() as u64,
+        });
+        start = end;
+    }
+
+    Ok(units)
+}
+
+// Rounds to the nearest sample.
+fn duration_to_samples(duration: Duration, sample_rate: u32) -> u64 {
+    ((duration.as_nanos() * sample_rate as u128 + 500_000_000) / 1_000_000_000) as u64
+}
+
+fn samples_to_ticks(samples: u64, sample_rate: u32, timescale: u32) -> u64 {
+    (samples as u128 * timescale as u128 / sample_rate as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::validate_payload;
+
+    #[test]
+    fn test_silent_aac_track() {
+        let track =
+            silent_track(SilentCodec::AAC, Duration::from_secs(10), 48000, 2, 90_000).unwrap();
+
+        // 480_000 samples is 468.75 frames
+        assert_eq!(track.len(), 469);
+        assert!(track.iter().all(|au| validate_payload(au).is_ok()));
+        assert_eq!(track[1].pts, 1_920);
+
+        let end = track.last().unwrap().pts + 1_920;
+        assert!(end >= 900_000 && end - 900_000 < 1_920);
+    }
+
+    #[test]
+    fn test_silent_opus_track() {
+        let track =
+            silent_track(SilentCodec::Opus, Duration::from_secs(1), 48000, 2, 48000).unwrap();
+        assert_eq!(track.len(), 50);
+        assert_eq!(track[49].pts, 49 * 960);
+        assert_eq!(&track[0].data[..], &[0xFC, 0xFF, 0xFE]);
+        assert!(track.iter().all(|au| validate_payload(au).is_ok()));
+
+        assert_eq!(
+            silent_track(SilentCodec::Opus, Duration::from_secs(1), 44100, 2, 48000).unwrap_err(),
+            SynthError::UnsupportedSampleRate(44100)
+        );
+        assert_eq!(
+            silent_track(SilentCodec::AAC, Duration::from_secs(1), 48000, 6, 48000).unwrap_err(),
+            SynthError::UnsupportedChannels(6)
+        );
+    }
+
+    #[test]
+    fn test_tone_track_exact_duration() {
+        let track = tone_track(440.0, Duration::from_millis(10_000), 44100, 2, 90_000).unwrap();
+
+        let samples: usize = track.iter().map(|au| au.data.len() / 4).sum();
+        assert_eq!(samples, 441_000);
+        // 441_000 = 430 * 1024 + 680
+        assert_eq!(track.len(), 431);
+        assert_eq!(track.last().unwrap().data.len(), 680 * 4);
+        assert_eq!(track.last().unwrap().pts, 430 * 1024 * 90_000 / 44100);
+
+        // Both channels carry the same signal, starting at zero
+        assert_eq!(&track[0].data[..4], &[0, 0, 0, 0]);
+        assert_eq!(track[0].data[4..6], track[0].data[6..8]);
+    }
+}
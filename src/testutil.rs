@@ -0,0 +1,19 @@
+//! Helpers shared by the crate's tests.
+
+pub mod corpus;
+
+/// Encodes a FLAC frame or sample number with the header's UTF-8-like
+/// coding.
+pub fn flac_coded_number(value: u64) -> Vec<u8> {
+    if value < 0x80 {
+        return vec![value as u8];
+    }
+    let extra = (1..=6).find(|&n| value < 1 << (5 * n + 6)).unwrap();
+    let mut coded = vec![(0xFF00u16 >> (extra + 1)) as u8 | (value >> (6 * extra)) as u8];
+    coded.extend(
+        (0..extra)
+            .rev()
+            .map(|n| 0x80 | ((value >> (6 * n)) & 0x3F) as u8),
+    );
+    coded
+}
@@ -0,0 +1,279 @@
+//! Deterministic generator of structurally valid, parameter-randomised
+//! elementary streams plus mutated variants, used to check the parsers
+//! against each other without external tools.
+
+use crate::{aac, bits::BitWriter, flac, AudioType};
+
+#[derive(Debug, Clone, Copy)]
+pub struct CorpusSpec {
+    pub buffers_per_format: usize,
+    pub max_frames: usize,
+    pub mutations_per_buffer: usize,
+}
+
+impl Default for CorpusSpec {
+    fn default() -> Self {
+        Self {
+            buffers_per_format: 16,
+            max_frames: 64,
+            mutations_per_buffer: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NamedBuffer {
+    pub name: String,
+    pub audio_type: AudioType,
+    /// Frame count, sample count and sample rate the buffer was generated
+    /// with. Mutated buffers keep their source's values.
+    pub frames: usize,
+    pub samples: u64,
+    pub sample_rate: u32,
+    pub mutated: bool,
+    pub data: Vec<u8>,
+}
+
+pub fn generate(seed: u64, spec: CorpusSpec) -> Vec<NamedBuffer> {
+    let mut rng = Rng(seed);
+    let mut corpus = Vec::new();
+
+    for i in 0..spec.buffers_per_format {
+        corpus.push(adts_buffer(&mut rng, i, spec.max_frames));
+        corpus.push(flac_buffer(&mut rng, i, spec.max_frames));
+    }
+
+    let mut mutated = Vec::new();
+    for buffer in &corpus {
+        for _ in 0..spec.mutations_per_buffer {
+            mutated.push(mutate(&mut rng, buffer));
+        }
+    }
+    corpus.extend(mutated);
+
+    corpus
+}
+
+// SplitMix64, so the corpus is identical on every platform.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n.max(1) as u64) as usize
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        for byte in buf {
+            *byte = self.next_u64() as u8;
+        }
+    }
+}
+
+fn adts_buffer(rng: &mut Rng, index: usize, max_frames: usize) -> NamedBuffer {
+    let sample_rate = rng.pick(&[8000, 11025, 16000, 22050, 24000, 32000, 44100, 48000, 96000]);
+    let channels = 1 + rng.below(6) as u8;
+    let frames = 2 + rng.below(max_frames.saturating_sub(1));
+    let mean_payload = 16 + rng.below(512);
+
+    let mut data = Vec::new();
+    for _ in 0..frames {
+        let mut payload = vec![0u8; mean_payload / 2 + rng.below(mean_payload)];
+        rng.fill(&mut payload);
+        data.extend(
+            aac::create_adts_header_for(
+                aac::AacObjectType::Lc,
+                channels,
+                sample_rate,
+                payload.len(),
+                false,
+            )
+            .unwrap(),
+        );
+        data.extend(payload);
+    }
+
+    NamedBuffer {
+        name: format!("adts-{}-{}hz-{}ch", index, sample_rate, channels),
+        audio_type: AudioType::AAC,
+        frames,
+        samples: frames as u64 * 1024,
+        sample_rate,
+        mutated: false,
+        data,
+    }
+}
+
+// (value, header code) pairs from the FLAC frame header tables.
+const FLAC_SAMPLE_RATES: [(u32, u8); 8] = [
+    (8000, 4),
+    (16000, 5),
+    (22050, 6),
+    (24000, 7),
+    (32000, 8),
+    (44100, 9),
+    (48000, 10),
+    (96000, 11),
+];
+const FLAC_BLOCK_SIZES: [(u16, u8); 6] = [
+    (192, 1),
+    (576, 2),
+    (1152, 3),
+    (256, 8),
+    (1024, 10),
+    (4096, 12),
+];
+const FLAC_SAMPLE_SIZES: [(u8, u8); 5] = [(8, 1), (12, 2), (16, 4), (20, 5), (24, 6)];
+
+fn flac_buffer(rng: &mut Rng, index: usize, max_frames: usize) -> NamedBuffer {
+    let (sample_rate, sr_code) = rng.pick(&FLAC_SAMPLE_RATES);
+    let (block_size, bs_code) = rng.pick(&FLAC_BLOCK_SIZES);
+    let (bps, bps_code) = rng.pick(&FLAC_SAMPLE_SIZES);
+    let channels = 1 + rng.below(8) as u8;
+    let frames = 2 + rng.below(max_frames.max(2) - 1);
+
+    let mut data = Vec::new();
+    let mut samples = 0u64;
+    for frame_number in 0..frames {
+        // The last frame of a fixed-blocksize stream may be shorter
+        let last_block = 1 + rng.below(block_size as usize) as u16;
+        let (frame_block, header_bs_code) = if frame_number + 1 == frames {
+            (last_block, 7)
+        } else {
+            (block_size, bs_code)
+        };
+        samples += frame_block as u64;
+
+        let mut frame = vec![
+            0xFF,
+            0xF8,
+            (header_bs_code << 4) | sr_code,
+            ((channels - 1) << 4) | (bps_code << 1),
+        ];
+        frame.extend(super::flac_coded_number(frame_number as u64));
+        if header_bs_code == 7 {
+            frame.extend_from_slice(&(frame_block - 1).to_be_bytes());
+        }
+        frame.push(flac::crc8(&frame));
+
+        // One CONSTANT subframe per channel
+        let mut bits = BitWriter::default();
+        for _ in 0..channels {
+            bits.write(0, 8);
+            bits.write(rng.next_u64(), bps as u32);
+        }
+        frame.extend(bits.finish());
+
+        let crc = frame.iter().fold(0, |crc, &b| flac::crc16_update(crc, b));
+        frame.extend_from_slice(&crc.to_be_bytes());
+        data.extend(frame);
+    }
+
+    NamedBuffer {
+        name: format!("flac-{}-{}hz-{}ch-{}bit", index, sample_rate, channels, bps),
+        audio_type: AudioType::FLAC,
+        frames,
+        samples,
+        sample_rate,
+        mutated: false,
+        data,
+    }
+}
+
+fn mutate(rng: &mut Rng, source: &NamedBuffer) -> NamedBuffer {
+    let mut data = source.data.clone();
+    let operation = match rng.below(3) {
+        0 => {
+            data.truncate(rng.below(data.len()));
+            "truncate"
+        }
+        1 => {
+            for _ in 0..1 + rng.below(8) {
+                let at = rng.below(data.len());
+                data[at] ^= 1 << rng.below(8);
+            }
+            "bitflip"
+        }
+        _ => {
+            let start = rng.below(data.len());
+            let end = start + rng.below(data.len() - start);
+            let region = data[start..end].to_vec();
+            let at = rng.below(data.len());
+            data.splice(at..at, region);
+            "duplicate"
+        }
+    };
+
+    NamedBuffer {
+        name: format!("{}-{}", source.name, operation),
+        mutated: true,
+        data,
+        ..source.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{detect_audio, duration::duration_hint, sanitize::sanitize};
+    use std::time::Duration;
+
+    fn frame_duration(buffer: &NamedBuffer) -> Duration {
+        let samples_per_frame = buffer.samples.div_ceil(buffer.frames as u64);
+        Duration::from_secs_f64(samples_per_frame as f64 / buffer.sample_rate as f64)
+    }
+
+    #[test]
+    fn test_generate_is_deterministic() {
+        let a = generate(7, CorpusSpec::default());
+        let b = generate(7, CorpusSpec::default());
+        let c = generate(8, CorpusSpec::default());
+
+        assert_eq!(a.len(), 2 * 16 * 4);
+        assert!(a.iter().zip(&b).all(|(a, b)| a.data == b.data));
+        assert!(a.iter().zip(&c).any(|(a, c)| a.data != c.data));
+    }
+
+    #[test]
+    fn test_differential_invariants() {
+        for seed in 0..4 {
+            for buffer in generate(seed, CorpusSpec::default()) {
+                let name = &buffer.name;
+                let expected =
+                    Duration::from_secs_f64(buffer.samples as f64 / buffer.sample_rate as f64);
+                let (walked, _) = duration_hint(&buffer.data, None);
+                let cleaned = sanitize(&buffer.data, buffer.audio_type);
+
+                // Whatever sanitize keeps and drops must tile the input
+                let removed: usize = cleaned.removed.iter().map(|r| r.len()).sum();
+                assert_eq!(cleaned.clean.len() + removed, buffer.data.len(), "{}", name);
+
+                if buffer.mutated {
+                    continue;
+                }
+
+                assert_eq!(detect_audio(&buffer.data), buffer.audio_type, "{}", name);
+                assert!(
+                    expected.abs_diff(walked) <= frame_duration(&buffer),
+                    "{}: walked {:?}, expected {:?}",
+                    name,
+                    walked,
+                    expected
+                );
+                assert!(cleaned.removed.is_empty(), "{}", name);
+                assert_eq!(cleaned.frames_kept, buffer.frames, "{}", name);
+            }
+        }
+    }
+}
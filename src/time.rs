@@ -0,0 +1,171 @@
+//! The 90 kHz MPEG clock that [`AccessUnit`](crate::AccessUnit) timestamps
+//! count in.
+//!
+//! Every conversion rounds to the nearest unit, halves rounding up, so
+//! 1024 samples at 44.1 kHz (2089.8 ticks) is 2090 ticks. Results too large
+//! for a `u64` saturate.
+
+use std::ops::{Add, Sub};
+use std::time::Duration;
+
+pub const CLOCK_RATE: u64 = 90_000;
+/// PES timestamps are 33 bits wide and wrap every 26.5 hours.
+pub const WRAP: u64 = 1 << 33;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ticks90k(pub u64);
+
+impl Ticks90k {
+    pub const ZERO: Ticks90k = Ticks90k(0);
+
+    pub fn from_duration(duration: Duration) -> Self {
+        Self(rescale(
+            duration.as_nanos(),
+            CLOCK_RATE as u128,
+            NANOS_PER_SEC,
+        ))
+    }
+
+    pub fn to_duration(self) -> Duration {
+        Duration::from_nanos(rescale(self.0 as u128, NANOS_PER_SEC, CLOCK_RATE as u128))
+    }
+
+    /// A sample rate of zero gives zero ticks.
+    pub fn from_samples(samples: u64, sample_rate: u32) -> Self {
+        if sample_rate == 0 {
+            return Self::ZERO;
+        }
+        Self(rescale(
+            samples as u128,
+            CLOCK_RATE as u128,
+            sample_rate as u128,
+        ))
+    }
+
+    pub fn to_samples(self, sample_rate: u32) -> u64 {
+        rescale(self.0 as u128, sample_rate as u128, CLOCK_RATE as u128)
+    }
+
+    /// The value as a 33-bit PES timestamp.
+    pub fn wrapped(self) -> Self {
+        Self(self.0 % WRAP)
+    }
+
+    pub fn saturating_add(self, other: Ticks90k) -> Self {
+        Self(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Ticks90k) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+
+    /// Sum modulo 2^33.
+    pub fn wrapping_add(self, other: Ticks90k) -> Self {
+        Self(self.0.wrapping_add(other.0) % WRAP)
+    }
+
+    /// Forward distance from `other` to `self` on the 33-bit clock, so a
+    /// timestamp just past a wrap is a small step after one just before it.
+    pub fn wrapping_sub(self, other: Ticks90k) -> Self {
+        Self(self.0.wrapping_sub(other.0) % WRAP)
+    }
+}
+
+impl Add for Ticks90k {
+    type Output = Ticks90k;
+
+    fn add(self, other: Ticks90k) -> Ticks90k {
+        Ticks90k(self.0 + other.0)
+    }
+}
+
+impl Sub for Ticks90k {
+    type Output = Ticks90k;
+
+    fn sub(self, other: Ticks90k) -> Ticks90k {
+        Ticks90k(self.0 - other.0)
+    }
+}
+
+impl From<u64> for Ticks90k {
+    fn from(ticks: u64) -> Self {
+        Ticks90k(ticks)
+    }
+}
+
+impl From<Ticks90k> for u64 {
+    fn from(ticks: Ticks90k) -> u64 {
+        ticks.0
+    }
+}
+
+// value * num / den, rounded half up and saturated.
+fn rescale(value: u128, num: u128, den: u128) -> u64 {
+    let scaled = value
+        .saturating_mul(num)
+        .saturating_add(den / 2)
+        .checked_div(den)
+        .unwrap_or(0);
+    u64::try_from(scaled).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_conversions_round_to_nearest() {
+        // 2089.795 ticks
+        assert_eq!(Ticks90k::from_samples(1024, 44100), Ticks90k(2090));
+        assert_eq!(Ticks90k::from_samples(1024, 48000), Ticks90k(1920));
+        // 1152 samples at 44.1 kHz is 2351.02 ticks
+        assert_eq!(Ticks90k::from_samples(1152, 44100), Ticks90k(2351));
+        assert_eq!(Ticks90k::from_samples(1024, 0), Ticks90k::ZERO);
+
+        assert_eq!(Ticks90k(2090).to_samples(44100), 1024);
+        assert_eq!(Ticks90k(90_000).to_samples(48000), 48000);
+        // Exactly half a sample at 1 Hz rounds up
+        assert_eq!(Ticks90k(45_000).to_samples(1), 1);
+    }
+
+    #[test]
+    fn test_duration_conversions() {
+        assert_eq!(
+            Ticks90k::from_duration(Duration::from_millis(40)),
+            Ticks90k(3600)
+        );
+        // 1/90000 s is 11111.1 ns
+        assert_eq!(Ticks90k(1).to_duration(), Duration::from_nanos(11111));
+        assert_eq!(
+            Ticks90k::from_duration(Duration::from_nanos(5556)),
+            Ticks90k(1)
+        );
+        assert_eq!(
+            Ticks90k::from_duration(Duration::from_nanos(5555)),
+            Ticks90k(0)
+        );
+        assert_eq!(
+            Ticks90k(3003).to_duration(),
+            Duration::from_nanos(33_366_667)
+        );
+        assert_eq!(Ticks90k::from_duration(Duration::MAX), Ticks90k(u64::MAX));
+    }
+
+    #[test]
+    fn test_wrapping_arithmetic() {
+        let before = Ticks90k(WRAP - 1000);
+        let after = Ticks90k(2003);
+        assert_eq!(after.wrapping_sub(before), Ticks90k(3003));
+        assert_eq!(before.wrapping_add(Ticks90k(3003)), after);
+        assert_eq!(Ticks90k(WRAP + 5).wrapped(), Ticks90k(5));
+
+        assert_eq!(Ticks90k(5).saturating_sub(Ticks90k(9)), Ticks90k::ZERO);
+        assert_eq!(
+            Ticks90k(u64::MAX).saturating_add(Ticks90k(1)),
+            Ticks90k(u64::MAX)
+        );
+    }
+}
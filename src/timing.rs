@@ -0,0 +1,402 @@
+use crate::AccessUnit;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const PTS_CLOCK: i128 = 90_000;
+const PTS_WRAP: i128 = 1 << 33;
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+// Seconds between the NTP epoch (1900) and the Unix epoch (1970).
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    // 90 kHz ticks with 33-bit wraps unrolled
+    pts: i128,
+    // Nanoseconds since the Unix epoch
+    nanos: i128,
+}
+
+/// Maps 90 kHz PTS values to wall-clock time using one or more observed
+/// `(pts, SystemTime)` anchors.
+///
+/// With a single anchor the nominal 90 kHz rate is used; with several the
+/// mapping is interpolated linearly between neighbouring anchors, which
+/// absorbs clock drift, and extrapolated from the nearest pair outside them.
+/// PTS values are unwrapped relative to the most recently added anchor.
+#[derive(Debug, Clone, Default)]
+pub struct WallClockMapper {
+    anchors: Vec<Anchor>,
+    last_pts: Option<i128>,
+}
+
+impl WallClockMapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_anchor(&mut self, pts: u64, time: SystemTime) {
+        let pts = self.unwrap_pts(pts);
+        let anchor = Anchor {
+            pts,
+            nanos: system_time_to_nanos(time),
+        };
+
+        match self.anchors.binary_search_by(|a| a.pts.cmp(&pts)) {
+            Ok(index) => self.anchors[index] = anchor,
+            Err(index) => self.anchors.insert(index, anchor),
+        }
+        self.last_pts = Some(pts);
+    }
+
+    /// Seeds an anchor from a `prft` box, `timescale` being that of the
+    /// referenced track.
+    pub fn add_prft(&mut self, prft: &ProducerReferenceTime, timescale: u32) {
+        if timescale == 0 {
+            return;
+        }
+        let pts = (prft.media_time as u128 * PTS_CLOCK as u128 / timescale as u128) as u64;
+        self.add_anchor(pts & (PTS_WRAP as u64 - 1), prft.wall_clock());
+    }
+
+    /// Adds an anchor for every top-level `prft` box in an fMP4 buffer and
+    /// returns how many were found.
+    pub fn seed_from_fmp4(&mut self, data: &[u8], timescale: u32) -> usize {
+        let mut found = 0;
+        for (box_type, body) in top_level_boxes(data) {
+            if &box_type == b"prft" {
+                if let Some(prft) = parse_prft_body(body) {
+                    self.add_prft(&prft, timescale);
+                    found += 1;
+                }
+            }
+        }
+        found
+    }
+
+    pub fn anchor_count(&self) -> usize {
+        self.anchors.len()
+    }
+
+    pub fn to_utc(&self, pts: u64) -> Option<SystemTime> {
+        let pts = self.unwrap_pts(pts);
+        let (a, b) = self.segment_for(|anchor| anchor.pts, pts)?;
+
+        let nanos = match b {
+            Some(b) => a.nanos + (pts - a.pts) * (b.nanos - a.nanos) / (b.pts - a.pts),
+            None => a.nanos + (pts - a.pts) * NANOS_PER_SEC / PTS_CLOCK,
+        };
+        nanos_to_system_time(nanos)
+    }
+
+    /// Returns the 33-bit PTS corresponding to `time`.
+    pub fn to_pts(&self, time: SystemTime) -> Option<u64> {
+        let nanos = system_time_to_nanos(time);
+        let (a, b) = self.segment_for(|anchor| anchor.nanos, nanos)?;
+
+        let pts = match b {
+            Some(b) => a.pts + (nanos - a.nanos) * (b.pts - a.pts) / (b.nanos - a.nanos),
+            None => a.pts + (nanos - a.nanos) * PTS_CLOCK / NANOS_PER_SEC,
+        };
+        Some(pts.rem_euclid(PTS_WRAP) as u64)
+    }
+
+    /// Formats the `#EXT-X-PROGRAM-DATE-TIME` tag for a segment starting with
+    /// `first`.
+    pub fn program_date_time(&self, first: &AccessUnit) -> Option<String> {
+        self.to_utc(first.pts).map(format_program_date_time)
+    }
+
+    fn unwrap_pts(&self, pts: u64) -> i128 {
+        let pts = pts as i128 % PTS_WRAP;
+        match self.last_pts {
+            Some(reference) => {
+                let base = reference - reference.rem_euclid(PTS_WRAP);
+                [base - PTS_WRAP, base, base + PTS_WRAP]
+                    .into_iter()
+                    .map(|base| base + pts)
+                    .min_by_key(|candidate| (candidate - reference).abs())
+                    .unwrap_or(pts)
+            }
+            None => pts,
+        }
+    }
+
+    // Picks the anchor pair to interpolate between, or a single anchor when
+    // only one is known or the pair would be degenerate.
+    fn segment_for(
+        &self,
+        key: impl Fn(&Anchor) -> i128,
+        value: i128,
+    ) -> Option<(Anchor, Option<Anchor>)> {
+        let first = *self.anchors.first()?;
+        if self.anchors.len() == 1 {
+            return Some((first, None));
+        }
+
+        let index = self
+            .anchors
+            .partition_point(|anchor| key(anchor) <= value)
+            .clamp(1, self.anchors.len() - 1);
+        let (a, b) = (self.anchors[index - 1], self.anchors[index]);
+
+        if key(&b) == key(&a) {
+            Some((a, None))
+        } else {
+            Some((a, Some(b)))
+        }
+    }
+}
+
+/// Contents of an ISO BMFF producer reference time (`prft`) box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProducerReferenceTime {
+    pub reference_track_id: u32,
+    /// 64-bit NTP timestamp: seconds since 1900 in the upper 32 bits.
+    pub ntp_timestamp: u64,
+    pub media_time: u64,
+}
+
+impl ProducerReferenceTime {
+    pub fn wall_clock(&self) -> SystemTime {
+        let seconds = (self.ntp_timestamp >> 32).saturating_sub(NTP_UNIX_OFFSET);
+        let fraction = self.ntp_timestamp & 0xFFFF_FFFF;
+        let nanos = (fraction * 1_000_000_000) >> 32;
+        UNIX_EPOCH + Duration::from_secs(seconds) + Duration::from_nanos(nanos)
+    }
+}
+
+/// Parses a complete `prft` box, header included.
+pub fn parse_prft(data: &[u8]) -> Option<ProducerReferenceTime> {
+    let (box_type, body) = top_level_boxes(data).next()?;
+    if &box_type != b"prft" {
+        return None;
+    }
+    parse_prft_body(body)
+}
+
+fn parse_prft_body(body: &[u8]) -> Option<ProducerReferenceTime> {
+    let version = *body.first()?;
+    let media_time_size = if version == 0 { 4 } else { 8 };
+    if body.len() < 16 + media_time_size {
+        return None;
+    }
+
+    let reference_track_id = u32::from_be_bytes(body[4..8].try_into().ok()?);
+    let ntp_timestamp = u64::from_be_bytes(body[8..16].try_into().ok()?);
+    let media_time = if version == 0 {
+        u32::from_be_bytes(body[16..20].try_into().ok()?) as u64
+    } else {
+        u64::from_be_bytes(body[16..24].try_into().ok()?)
+    };
+
+    Some(ProducerReferenceTime {
+        reference_track_id,
+        ntp_timestamp,
+        media_time,
+    })
+}
+
+/// Formats `time` as an HLS `#EXT-X-PROGRAM-DATE-TIME` tag with millisecond
+/// precision in UTC.
+pub fn format_program_date_time(time: SystemTime) -> String {
+    let nanos = system_time_to_nanos(time);
+    let millis = nanos.div_euclid(1_000_000);
+    let seconds = millis.div_euclid(1000);
+    let days = seconds.div_euclid(86_400);
+    let secs_of_day = seconds.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!(
+        "#EXT-X-PROGRAM-DATE-TIME:{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        millis.rem_euclid(1000)
+    )
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian (year, month, day).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn system_time_to_nanos(time: SystemTime) -> i128 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_nanos() as i128,
+        Err(before) => -(before.duration().as_nanos() as i128),
+    }
+}
+
+fn nanos_to_system_time(nanos: i128) -> Option<SystemTime> {
+    if nanos >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_nanos(u64::try_from(nanos).ok()?))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_nanos(u64::try_from(-nanos).ok()?))
+    }
+}
+
+pub(crate) fn top_level_boxes(data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    box_spans(data).map(move |(box_type, span)| (box_type, &data[span.body..span.end]))
+}
+
+/// Where a box lies in the data it was found in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BoxSpan {
+    pub start: usize,
+    /// Start of the body, just past the header.
+    pub body: usize,
+    pub end: usize,
+}
+
+/// Offsets of consecutive boxes, stopping at the first whose size does
+/// not fit in `data`.
+pub(crate) fn box_spans(data: &[u8]) -> impl Iterator<Item = ([u8; 4], BoxSpan)> + '_ {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let header = data.get(pos..pos + 8)?;
+        let box_type: [u8; 4] = header[4..8].try_into().ok()?;
+        let (header_size, size) = match u32::from_be_bytes(header[..4].try_into().ok()?) {
+            0 => (8, data.len() - pos),
+            1 => {
+                let large = data.get(pos + 8..pos + 16)?;
+                (
+                    16,
+                    usize::try_from(u64::from_be_bytes(large.try_into().ok()?)).ok()?,
+                )
+            }
+            size => (8, size as usize),
+        };
+        if size < header_size || size > data.len() - pos {
+            return None;
+        }
+
+        let span = BoxSpan {
+            start: pos,
+            body: pos + header_size,
+            end: pos + size,
+        };
+        pos += size;
+        Some((box_type, span))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn at(seconds: u64, millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds) + Duration::from_millis(millis)
+    }
+
+    fn prft_box(version: u8, ntp_seconds: u64, media_time: u64) -> Vec<u8> {
+        let mut body = vec![version, 0, 0, 0];
+        body.extend_from_slice(&1u32.to_be_bytes());
+        body.extend_from_slice(&((ntp_seconds + NTP_UNIX_OFFSET) << 32).to_be_bytes());
+        if version == 0 {
+            body.extend_from_slice(&(media_time as u32).to_be_bytes());
+        } else {
+            body.extend_from_slice(&media_time.to_be_bytes());
+        }
+
+        let mut prft = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        prft.extend_from_slice(b"prft");
+        prft.extend_from_slice(&body);
+        prft
+    }
+
+    #[test]
+    fn test_single_anchor_uses_nominal_rate() {
+        let mut mapper = WallClockMapper::new();
+        assert_eq!(mapper.to_utc(0), None);
+
+        mapper.add_anchor(90_000, at(1_700_000_000, 0));
+        assert_eq!(mapper.to_utc(180_000), Some(at(1_700_000_001, 0)));
+        assert_eq!(mapper.to_utc(45_000), Some(at(1_699_999_999, 500)));
+        assert_eq!(mapper.to_pts(at(1_700_000_002, 0)), Some(270_000));
+    }
+
+    #[test]
+    fn test_drift_interpolation() {
+        let mut mapper = WallClockMapper::new();
+        // The encoder clock runs 0.1% fast: 10 s of PTS spans 10.01 s of UTC
+        mapper.add_anchor(0, at(1_700_000_000, 0));
+        mapper.add_anchor(900_000, at(1_700_000_010, 10));
+
+        assert_eq!(mapper.to_utc(450_000), Some(at(1_700_000_005, 5)));
+        assert_eq!(mapper.to_utc(1_800_000), Some(at(1_700_000_020, 20)));
+        assert_eq!(mapper.to_pts(at(1_700_000_005, 5)), Some(450_000));
+    }
+
+    #[test]
+    fn test_pts_wrap() {
+        let wrap = 1u64 << 33;
+        let mut mapper = WallClockMapper::new();
+        mapper.add_anchor(wrap - 90_000, at(1_700_000_000, 0));
+
+        // One second later the PTS has wrapped back to zero
+        assert_eq!(mapper.to_utc(0), Some(at(1_700_000_001, 0)));
+        assert_eq!(mapper.to_pts(at(1_700_000_002, 0)), Some(90_000));
+
+        mapper.add_anchor(90_000, at(1_700_000_002, 0));
+        assert_eq!(mapper.anchor_count(), 2);
+        assert_eq!(mapper.to_utc(wrap - 45_000), Some(at(1_700_000_000, 500)));
+    }
+
+    #[test]
+    fn test_program_date_time() {
+        assert_eq!(
+            format_program_date_time(at(1_700_000_000, 123)),
+            "#EXT-X-PROGRAM-DATE-TIME:2023-11-14T22:13:20.123Z"
+        );
+        assert_eq!(
+            format_program_date_time(at(951_782_400, 0)),
+            "#EXT-X-PROGRAM-DATE-TIME:2000-02-29T00:00:00.000Z"
+        );
+
+        let mut mapper = WallClockMapper::new();
+        mapper.add_anchor(0, at(1_700_000_000, 0));
+        let au = AccessUnit {
+            key: true,
+            pts: 9_000,
+            dts: 9_000,
+            data: Bytes::from(vec![0u8]),
+            stream_type: 0,
+            id: 0,
+        };
+        assert_eq!(
+            mapper.program_date_time(&au).unwrap(),
+            "#EXT-X-PROGRAM-DATE-TIME:2023-11-14T22:13:20.100Z"
+        );
+    }
+
+    #[test]
+    fn test_prft_seeding() {
+        let prft = parse_prft(&prft_box(1, 1_700_000_000, 48_000 * 10)).unwrap();
+        assert_eq!(prft.reference_track_id, 1);
+        assert_eq!(prft.wall_clock(), at(1_700_000_000, 0));
+
+        let mut fmp4 = vec![0, 0, 0, 8];
+        fmp4.extend_from_slice(b"styp");
+        fmp4.extend(prft_box(0, 1_700_000_000, 48_000 * 10));
+        fmp4.extend_from_slice(&[0, 0, 0, 8]);
+        fmp4.extend_from_slice(b"moof");
+        fmp4.extend(prft_box(1, 1_700_000_004, 48_000 * 12));
+
+        let mut mapper = WallClockMapper::new();
+        assert_eq!(mapper.seed_from_fmp4(&fmp4, 48_000), 2);
+        assert_eq!(mapper.to_utc(900_000), Some(at(1_700_000_000, 0)));
+        assert_eq!(mapper.to_utc(990_000), Some(at(1_700_000_002, 0)));
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{h264, timing::top_level_boxes, ParseTypeError, StreamType};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VideoType {
+    Unknown,
+    H264,
+    H265,
+    VP8,
+    VP9,
+    AV1,
+}
+
+impl VideoType {
+    pub fn to_psi_stream_type(&self) -> Option<u8> {
+        match self {
+            VideoType::H264 => Some(StreamType::H264.into()),
+            _ => None,
+        }
+    }
+
+    pub fn from_psi_stream_type(stream_type: u8) -> Option<VideoType> {
+        match StreamType::from(stream_type) {
+            StreamType::H264 => Some(VideoType::H264),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for VideoType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VideoType::Unknown => "unknown",
+            VideoType::H264 => "h264",
+            VideoType::H265 => "h265",
+            VideoType::VP8 => "vp8",
+            VideoType::VP9 => "vp9",
+            VideoType::AV1 => "av1",
+        })
+    }
+}
+
+impl FromStr for VideoType {
+    type Err = ParseTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "unknown" => Ok(VideoType::Unknown),
+            "h264" | "avc" | "avc1" | "264" => Ok(VideoType::H264),
+            "h265" | "hevc" | "hvc1" | "hev1" | "265" => Ok(VideoType::H265),
+            "vp8" => Ok(VideoType::VP8),
+            "vp9" => Ok(VideoType::VP9),
+            "av1" | "av01" | "obu" => Ok(VideoType::AV1),
+            _ => Err(ParseTypeError(s.to_string())),
+        }
+    }
+}
+
+// Only the first few NAL units are looked at.
+const PROBE_NALUS: usize = 8;
+
+/// Classifies video data: Annex B H.264 and H.265, a low-overhead AV1 OBU
+/// stream, IVF, and the video sample entry of an MP4 or codec ID of a
+/// WebM file.
+pub fn detect_video(data: &[u8]) -> VideoType {
+    if data.starts_with(b"DKIF") {
+        return ivf_codec(data);
+    }
+    if data.get(4..8) == Some(b"ftyp") {
+        return mp4_video_entry(data).unwrap_or(VideoType::Unknown);
+    }
+    if data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        return webm_codec(data);
+    }
+    if h264::is_nalu(data) {
+        return annex_b_codec(data);
+    }
+    if is_av1_obu_stream(data) {
+        return VideoType::AV1;
+    }
+    VideoType::Unknown
+}
+
+fn annex_b_codec(data: &[u8]) -> VideoType {
+    let nalus: Vec<&[u8]> = h264::split_nalus(data)
+        .into_iter()
+        .take(PROBE_NALUS)
+        .collect();
+    if nalus.is_empty() {
+        return VideoType::Unknown;
+    }
+
+    // An H.264 AUD or SPS has a zero temporal id when read as H.265, and
+    // an H.265 VPS has the unspecified type 0 when read as H.264.
+    if nalus.iter().all(|nalu| is_h265_nalu(nalu)) {
+        VideoType::H265
+    } else if nalus.iter().all(|nalu| is_h264_nalu(nalu)) {
+        VideoType::H264
+    } else {
+        VideoType::Unknown
+    }
+}
+
+fn is_h264_nalu(nalu: &[u8]) -> bool {
+    let nal_unit_type = nalu[0] & 0x1F;
+    nalu[0] & 0x80 == 0 && (1..=21).contains(&nal_unit_type)
+}
+
+fn is_h265_nalu(nalu: &[u8]) -> bool {
+    if nalu.len() < 2 {
+        return false;
+    }
+    let nal_unit_type = (nalu[0] >> 1) & 0x3F;
+    let nuh_layer_id = ((nalu[0] & 0x01) << 5) | (nalu[1] >> 3);
+    let temporal_id_plus1 = nalu[1] & 0x07;
+    nalu[0] & 0x80 == 0
+        && nuh_layer_id == 0
+        && temporal_id_plus1 != 0
+        && matches!(nal_unit_type, 0..=9 | 16..=21 | 32..=40)
+}
+
+// A temporal unit starts with a temporal delimiter or sequence header OBU,
+// and the sizes of the first two OBUs must chain.
+fn is_av1_obu_stream(data: &[u8]) -> bool {
+    let mut pos = 0;
+    for n in 0..2 {
+        let Some(&header) = data.get(pos) else {
+            return n > 0;
+        };
+        let obu_type = (header >> 3) & 0x0F;
+        let has_extension = header & 0x04 != 0;
+        let has_size = header & 0x02 != 0;
+        if header & 0x81 != 0 || !has_size || (n == 0 && !matches!(obu_type, 1 | 2)) {
+            return false;
+        }
+
+        pos += 1 + has_extension as usize;
+        let Some((size, len)) = read_leb128(data.get(pos..).unwrap_or_default()) else {
+            return false;
+        };
+        pos += len + size as usize;
+        if pos > data.len() {
+            return false;
+        }
+    }
+    true
+}
+
+fn read_leb128(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().take(8).enumerate() {
+        value |= ((byte & 0x7F) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+fn ivf_codec(data: &[u8]) -> VideoType {
+    match data.get(8..12) {
+        Some(b"VP80") => VideoType::VP8,
+        Some(b"VP90") => VideoType::VP9,
+        Some(b"AV01") => VideoType::AV1,
+        _ => VideoType::Unknown,
+    }
+}
+
+fn mp4_video_entry(data: &[u8]) -> Option<VideoType> {
+    top_level_boxes(data).find_map(|(box_type, body)| match &box_type {
+        b"moov" | b"trak" | b"mdia" | b"minf" | b"stbl" => mp4_video_entry(body),
+        // Version, flags and entry count precede the sample entries
+        b"stsd" => top_level_boxes(body.get(8..)?).find_map(|(format, _)| match &format {
+            b"avc1" | b"avc3" => Some(VideoType::H264),
+            b"hvc1" | b"hev1" => Some(VideoType::H265),
+            b"vp08" => Some(VideoType::VP8),
+            b"vp09" => Some(VideoType::VP9),
+            b"av01" => Some(VideoType::AV1),
+            _ => None,
+        }),
+        _ => None,
+    })
+}
+
+// Looks for a Matroska video CodecID element (0x86) among the first bytes
+// rather than walking the EBML tree.
+fn webm_codec(data: &[u8]) -> VideoType {
+    const CODEC_IDS: [(&[u8], VideoType); 5] = [
+        (b"V_VP8", VideoType::VP8),
+        (b"V_VP9", VideoType::VP9),
+        (b"V_AV1", VideoType::AV1),
+        (b"V_MPEG4/ISO/AVC", VideoType::H264),
+        (b"V_MPEGH/ISO/HEVC", VideoType::H265),
+    ];
+
+    let probe = &data[..data.len().min(64 * 1024)];
+    probe
+        .windows(2)
+        .enumerate()
+        .filter(|(_, w)| w[0] == 0x86 && w[1] & 0x80 != 0)
+        .find_map(|(i, w)| {
+            let id = probe.get(i + 2..i + 2 + (w[1] & 0x7F) as usize)?;
+            CODEC_IDS
+                .iter()
+                .find(|(codec_id, _)| id == *codec_id)
+                .map(|&(_, video_type)| video_type)
+        })
+        .unwrap_or(VideoType::Unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annex_b(nalus: &[&[u8]]) -> Vec<u8> {
+        nalus
+            .iter()
+            .flat_map(|nalu| [&[0x00, 0x00, 0x00, 0x01][..], nalu].concat())
+            .collect()
+    }
+
+    fn mp4_box(box_type: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut b = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(box_type);
+        b.extend_from_slice(body);
+        b
+    }
+
+    #[test]
+    fn test_type_names_round_trip() {
+        for video_type in [
+            VideoType::Unknown,
+            VideoType::H264,
+            VideoType::H265,
+            VideoType::VP8,
+            VideoType::VP9,
+            VideoType::AV1,
+        ] {
+            assert_eq!(video_type.to_string().parse(), Ok(video_type));
+        }
+        assert_eq!("HEVC".parse(), Ok(VideoType::H265));
+        assert!("mpeg2".parse::<VideoType>().is_err());
+    }
+
+    #[test]
+    fn test_detect_annex_b() {
+        let h264 = annex_b(&[
+            &[0x09, 0xF0],
+            &[0x67, 0x64, 0x00, 0x1F, 0xAC],
+            &[0x68, 0xEE, 0x3C, 0x80],
+            &[0x65, 0x88, 0x84, 0x00],
+        ]);
+        assert_eq!(detect_video(&h264), VideoType::H264);
+
+        let h265 = annex_b(&[
+            &[0x46, 0x01, 0x50],
+            &[0x40, 0x01, 0x0C, 0x01],
+            &[0x42, 0x01, 0x01, 0x01],
+            &[0x44, 0x01, 0xC1, 0x72],
+            &[0x26, 0x01, 0xAF, 0x00],
+        ]);
+        assert_eq!(detect_video(&h265), VideoType::H265);
+
+        assert_eq!(detect_video(&annex_b(&[&[0x80, 0x00]])), VideoType::Unknown);
+    }
+
+    #[test]
+    fn test_detect_av1_and_ivf() {
+        // Temporal delimiter, then a three-byte sequence header
+        let obus = [0x12, 0x00, 0x0A, 0x03, 0x00, 0x00, 0x00];
+        assert_eq!(detect_video(&obus), VideoType::AV1);
+        assert_eq!(detect_video(&obus[..4]), VideoType::Unknown);
+
+        let mut ivf = b"DKIF\x00\x00\x20\x00VP90".to_vec();
+        ivf.resize(32, 0);
+        assert_eq!(detect_video(&ivf), VideoType::VP9);
+    }
+
+    #[test]
+    fn test_detect_mp4_sample_entry() {
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(mp4_box(b"avc1", &[0; 78]));
+        let stbl = mp4_box(b"stbl", &mp4_box(b"stsd", &stsd));
+        let trak = mp4_box(b"trak", &mp4_box(b"mdia", &mp4_box(b"minf", &stbl)));
+
+        let mut mp4 = mp4_box(b"ftyp", b"isom\x00\x00\x02\x00isomavc1");
+        mp4.extend(mp4_box(b"moov", &trak));
+        assert_eq!(detect_video(&mp4), VideoType::H264);
+
+        let mut audio_only = mp4_box(b"ftyp", b"M4A \x00\x00\x00\x00");
+        audio_only.extend(mp4_box(b"moov", &mp4_box(b"trak", &[])));
+        assert_eq!(detect_video(&audio_only), VideoType::Unknown);
+    }
+
+    #[test]
+    fn test_detect_webm_codec_id() {
+        let mut webm = vec![0x1A, 0x45, 0xDF, 0xA3, 0x84, 0x42, 0x82, 0x84];
+        webm.extend_from_slice(b"webm");
+        webm.extend_from_slice(&[0xAE, 0x88, 0x86, 0x85]);
+        webm.extend_from_slice(b"V_VP9");
+        assert_eq!(detect_video(&webm), VideoType::VP9);
+    }
+}
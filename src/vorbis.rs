@@ -0,0 +1,104 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VorbisIdHeader {
+    pub channels: u8,
+    pub sample_rate: u32,
+    /// Bits per second; zero or negative when unset.
+    pub bitrate_maximum: i32,
+    pub bitrate_nominal: i32,
+    pub bitrate_minimum: i32,
+    pub blocksize_0: u16,
+    pub blocksize_1: u16,
+}
+
+const VORBIS_ID_HEADER_LEN: usize = 30;
+
+/// Parses a Vorbis identification header packet (`\x01vorbis`), checking
+/// the fields the specification requires to be valid.
+pub fn parse_vorbis_id_header(packet: &[u8]) -> Option<VorbisIdHeader> {
+    if packet.len() < VORBIS_ID_HEADER_LEN || &packet[..7] != b"\x01vorbis" {
+        return None;
+    }
+
+    let u32_at = |pos: usize| u32::from_le_bytes(packet[pos..pos + 4].try_into().unwrap());
+    let version = u32_at(7);
+    let channels = packet[11];
+    let sample_rate = u32_at(12);
+    let blocksize_0 = packet[28] & 0x0F;
+    let blocksize_1 = packet[28] >> 4;
+    let framing = packet[29] & 0x01;
+
+    if version != 0
+        || channels == 0
+        || sample_rate == 0
+        || !(6..=13).contains(&blocksize_0)
+        || !(6..=13).contains(&blocksize_1)
+        || blocksize_0 > blocksize_1
+        || framing != 1
+    {
+        return None;
+    }
+
+    Some(VorbisIdHeader {
+        channels,
+        sample_rate,
+        bitrate_maximum: u32_at(16) as i32,
+        bitrate_nominal: u32_at(20) as i32,
+        bitrate_minimum: u32_at(24) as i32,
+        blocksize_0: 1 << blocksize_0,
+        blocksize_1: 1 << blocksize_1,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn id_header(channels: u8, sample_rate: u32) -> Vec<u8> {
+        let mut packet = b"\x01vorbis".to_vec();
+        packet.extend_from_slice(&0u32.to_le_bytes());
+        packet.push(channels);
+        packet.extend_from_slice(&sample_rate.to_le_bytes());
+        packet.extend_from_slice(&0i32.to_le_bytes());
+        packet.extend_from_slice(&128_000i32.to_le_bytes());
+        packet.extend_from_slice(&0i32.to_le_bytes());
+        packet.push(0xB8);
+        packet.push(0x01);
+        packet
+    }
+
+    #[test]
+    fn test_parse_vorbis_id_header() {
+        let header = parse_vorbis_id_header(&id_header(2, 44100)).unwrap();
+        assert_eq!(
+            header,
+            VorbisIdHeader {
+                channels: 2,
+                sample_rate: 44100,
+                bitrate_maximum: 0,
+                bitrate_nominal: 128_000,
+                bitrate_minimum: 0,
+                blocksize_0: 256,
+                blocksize_1: 2048,
+            }
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_id_headers() {
+        let mut packet = id_header(0, 44100);
+        assert!(parse_vorbis_id_header(&packet).is_none());
+
+        packet = id_header(2, 44100);
+        packet[29] = 0;
+        assert!(parse_vorbis_id_header(&packet).is_none());
+
+        // blocksize_0 larger than blocksize_1
+        packet = id_header(2, 44100);
+        packet[28] = 0x8B;
+        assert!(parse_vorbis_id_header(&packet).is_none());
+
+        assert!(parse_vorbis_id_header(&id_header(2, 44100)[..29]).is_none());
+        assert!(parse_vorbis_id_header(b"\x03vorbis").is_none());
+    }
+}
@@ -0,0 +1,35 @@
+//! `wasm-bindgen` wrappers for running detection in the browser, enabled by
+//! the `wasm` feature.
+//!
+//! Slices are borrowed from wasm memory: wasm-bindgen copies a `Uint8Array`
+//! in once per call and the detectors work on that copy without allocating
+//! another.
+
+use wasm_bindgen::prelude::*;
+
+/// Result of [`detect_media`]; `undefined` fields were not identified.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaInfo {
+    pub container: Option<String>,
+    pub codec: Option<String>,
+    #[wasm_bindgen(js_name = mimeType)]
+    pub mime_type: Option<String>,
+}
+
+/// The audio codec name, such as `"aac"` or `"flac"`, or `"unknown"`.
+#[wasm_bindgen(js_name = detectAudio)]
+pub fn detect_audio(data: &[u8]) -> String {
+    crate::detect_audio(data).to_string()
+}
+
+/// The container and codec of a file or stream prefix.
+#[wasm_bindgen(js_name = detectMedia)]
+pub fn detect_media(data: &[u8]) -> MediaInfo {
+    let format = crate::detect_media(data);
+    MediaInfo {
+        container: format.container.map(|c| c.to_string()),
+        codec: format.codec.map(|c| c.to_string()),
+        mime_type: format.codec.map(|c| c.mime_type().to_owned()),
+    }
+}
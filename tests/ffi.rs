@@ -0,0 +1,64 @@
+//! Builds the crate as a static library, compiles the C harness in
+//! tests/ffi/harness.c against it and include/access_unit.h, then runs it
+//! to check the header and the exported symbols agree. Set `CC` to pick the
+//! C compiler.
+#![cfg(feature = "ffi")]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+// Libraries the Rust standard library needs when linked from C
+#[cfg(target_os = "linux")]
+const NATIVE_LIBS: &[&str] = &["-lpthread", "-ldl", "-lm"];
+#[cfg(not(target_os = "linux"))]
+const NATIVE_LIBS: &[&str] = &[];
+
+#[test]
+fn test_c_harness() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+
+    // A separate target directory keeps this build from waiting on the lock
+    // held by the cargo invocation running the tests
+    let target_dir = tmp_dir.join("ffi-staticlib");
+    let status = Command::new(env!("CARGO"))
+        .args([
+            "rustc",
+            "--lib",
+            "--features",
+            "ffi",
+            "--crate-type",
+            "staticlib",
+        ])
+        .arg("--manifest-path")
+        .arg(manifest_dir.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .expect("failed to run cargo");
+    assert!(status.success(), "building the static library failed");
+    let archive = target_dir.join("debug/libaccess_unit.a");
+    assert!(archive.exists(), "{} not found", archive.display());
+
+    let harness = tmp_dir.join("au_ffi_harness");
+    let status = Command::new(env::var_os("CC").unwrap_or_else(|| "cc".into()))
+        .args(["-std=c99", "-Wall", "-Werror"])
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/ffi/harness.c"))
+        .arg(&archive)
+        .args(NATIVE_LIBS)
+        .arg("-o")
+        .arg(&harness)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "compiling tests/ffi/harness.c failed");
+
+    let output = Command::new(&harness).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,53 @@
+/* Exercises include/access_unit.h from C. tests/ffi.rs builds it against
+ * the crate's static library and runs it; it exits 0 or prints the line of
+ * the first failed check. */
+#include <stdio.h>
+#include <string.h>
+
+#include "access_unit.h"
+
+#define CHECK(cond) \
+    do {            \
+        if (!(cond)) \
+            return __LINE__; \
+    } while (0)
+
+static int au_ffi_harness(void)
+{
+    /* AAC-LC, 48 kHz, stereo, no CRC, 7 + 2 bytes */
+    const uint8_t adts[] = {0xFF, 0xF1, 0x4C, 0x80, 0x01, 0x3F, 0xFC, 0x21, 0x00};
+    const uint8_t flac[] = {0xFF, 0xF8, 0xC9, 0xA8, 0x00, 0x8D, 0x4E};
+    AuAdtsHeader header;
+    AuFlacFrameInfo info;
+
+    CHECK(au_detect_audio(adts, sizeof adts) == AU_AUDIO_AAC);
+    CHECK(au_detect_audio(NULL, 0) == AU_AUDIO_UNKNOWN);
+    CHECK(au_detect_audio(NULL, 1) == AU_ERROR);
+    CHECK(strcmp(au_last_error_message(), "null data pointer") == 0);
+
+    CHECK(au_aac_parse_adts(adts, sizeof adts, &header) == AU_OK);
+    CHECK(header.profile == 1);
+    CHECK(header.sample_rate == 48000);
+    CHECK(header.channels == 2);
+    CHECK(header.has_crc == 0);
+    CHECK(header.header_length == 7);
+    CHECK(header.frame_length == 9);
+    CHECK(au_aac_parse_adts(adts, 3, &header) == AU_ERROR);
+
+    CHECK(au_flac_decode_header(flac, sizeof flac, &info) == AU_OK);
+    CHECK(info.block_size == 4096);
+    CHECK(info.sample_rate == 44100);
+    CHECK(info.channels == 2);
+    CHECK(au_flac_decode_header(flac, 2, &info) == AU_ERROR);
+    CHECK(au_last_error_message() != NULL);
+
+    return 0;
+}
+
+int main(void)
+{
+    int line = au_ffi_harness();
+    if (line != 0)
+        fprintf(stderr, "harness.c:%d: check failed\n", line);
+    return line != 0;
+}
@@ -0,0 +1,25 @@
+//! Run with `wasm-pack test --node --features wasm`.
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use access_unit::wasm::{detect_audio, detect_media};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+static FLAC: &[u8] = include_bytes!("../testdata/s24le.wav.flac");
+
+#[wasm_bindgen_test]
+fn test_detect_audio() {
+    assert_eq!(detect_audio(FLAC), "flac");
+    assert_eq!(
+        detect_audio(&[0xFF, 0xF1, 0x4C, 0x80, 0x01, 0x3F, 0xFC, 0x21, 0x00]),
+        "aac"
+    );
+    assert_eq!(detect_audio(&[]), "unknown");
+}
+
+#[wasm_bindgen_test]
+fn test_detect_media() {
+    let info = detect_media(FLAC);
+    assert_eq!(info.container, None);
+    assert_eq!(info.codec.as_deref(), Some("flac"));
+    assert_eq!(info.mime_type.as_deref(), Some("audio/flac"));
+}